@@ -78,6 +78,12 @@ SECTIONS
     KEEP(*(.idolatry));
   }
 
+  /* ## .hubris_log */
+  /* Interned log strings (see lib/log-format). */
+  .hubris_log (INFO) : {
+    *(.hubris_log .hubris_log.*);
+  }
+
   /* ## Discarded sections */
   /DISCARD/ :
   {
@@ -89,6 +89,15 @@ SECTIONS
     KEEP(*(.idolatry));
   }
 
+  /* ## .hubris_log */
+  /* Interned log strings (see lib/log-format). These aren't loaded; the
+     offset of each entry in the section is its message id, so we start at
+     1 to keep 0 from being a valid id. */
+  .hubris_log 0 (INFO) : {
+    . = 1;
+    *(.hubris_log .hubris_log.*);
+  }
+
   /* ## Discarded sections */
   /DISCARD/ :
   {
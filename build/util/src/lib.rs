@@ -84,6 +84,12 @@ pub fn task_ids() -> TaskIds {
     )
 }
 
+/// Returns a map from the names of tasks with `start-after` dependencies in
+/// `app.toml` to the names of the tasks they depend on.
+pub fn task_start_after() -> Result<BTreeMap<String, Vec<String>>> {
+    Ok(toml_from_env("HUBRIS_TASK_START_AFTER")?.unwrap_or_default())
+}
+
 /// Map of task names to their IDs.
 pub struct TaskIds(BTreeMap<String, usize>);
 
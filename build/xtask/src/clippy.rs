@@ -73,6 +73,7 @@ pub fn run(
             let kconfig = crate::dist::make_kconfig(
                 &toml,
                 &allocs.tasks,
+                &allocs.shared,
                 &entry_points,
                 &toml.image_names[0],
                 &None,
@@ -84,6 +85,8 @@ pub fn run(
                 &[
                     ("HUBRIS_KCONFIG", &kconfig),
                     ("HUBRIS_IMAGE_ID", "1234"), // dummy image ID
+                    // dummy image key hash, which is only used by stage0
+                    ("HUBRIS_IMAGE_KEY_HASH", &"00".repeat(32)),
                 ],
                 None,
             )
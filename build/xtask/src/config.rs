@@ -30,6 +30,10 @@ struct RawConfig {
     external_images: Vec<String>,
     #[serde(default)]
     signing: Option<Signing>,
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    epoch: u32,
     secure_separation: Option<bool>,
     stacksize: Option<u32>,
     kernel: Kernel,
@@ -37,6 +41,8 @@ struct RawConfig {
     #[serde(default)]
     extratext: IndexMap<String, Peripheral>,
     #[serde(default)]
+    shared_regions: IndexMap<String, SharedRegion>,
+    #[serde(default)]
     config: Option<ordered_toml::Value>,
     #[serde(default)]
     secure_task: Option<String>,
@@ -52,6 +58,12 @@ pub struct Config {
     pub image_names: Vec<String>,
     pub external_images: Vec<String>,
     pub signing: Option<Signing>,
+    /// Version of the image, written to its header and manifest. With no
+    /// boot state, stage0 boots the slot with the higher version.
+    pub version: u32,
+    /// Epoch of the image, written to its header and manifest. Once an image
+    /// is confirmed, stage0 won't boot images from earlier epochs.
+    pub epoch: u32,
     pub secure_separation: Option<bool>,
     pub stacksize: Option<u32>,
     pub kernel: Kernel,
@@ -59,6 +71,7 @@ pub struct Config {
     pub tasks: IndexMap<String, Task>,
     pub peripherals: IndexMap<String, Peripheral>,
     pub extratext: IndexMap<String, Peripheral>,
+    pub shared_regions: IndexMap<String, SharedRegion>,
     pub config: Option<ordered_toml::Value>,
     pub buildhash: u64,
     pub app_toml_path: PathBuf,
@@ -73,6 +86,39 @@ impl Config {
         if toml.tasks.contains_key("kernel") {
             bail!("'kernel' is reserved and cannot be used as a task name");
         }
+        for (name, task) in &toml.tasks {
+            if task.timers == 0 || task.timers > abi::MAX_TIMERS_PER_TASK {
+                bail!(
+                    "task {}: timers must be between 1 and {}, not {}",
+                    name,
+                    abi::MAX_TIMERS_PER_TASK,
+                    task.timers
+                );
+            }
+            if task.async_sends > abi::MAX_ASYNC_SENDS_PER_TASK {
+                bail!(
+                    "task {}: async-sends must be at most {}, not {}",
+                    name,
+                    abi::MAX_ASYNC_SENDS_PER_TASK,
+                    task.async_sends
+                );
+            }
+        }
+
+        if let Some(signing) = &toml.signing {
+            if signing.priv_key.is_some() != signing.root_cert.is_some() {
+                bail!("signing: priv-key and root-cert must be given together");
+            }
+            if signing.priv_key.is_some()
+                && signing.image_key.is_some()
+                && toml.external_images.is_empty()
+            {
+                bail!(
+                    "signing: an image can't be signed with both priv-key \
+                     (for the boot ROM) and image-key (for stage0)"
+                );
+            }
+        }
 
         let mut hasher = DefaultHasher::new();
         hasher.write(&cfg_contents);
@@ -118,7 +164,7 @@ impl Config {
             None => None,
         };
 
-        Ok(Config {
+        let config = Config {
             name: toml.name,
             target: toml.target,
             board: toml.board,
@@ -126,6 +172,8 @@ impl Config {
             external_images: toml.external_images,
             chip: toml.chip,
             signing: toml.signing,
+            version: toml.version,
+            epoch: toml.epoch,
             secure_separation: toml.secure_separation,
             stacksize: toml.stacksize,
             kernel: toml.kernel,
@@ -133,12 +181,190 @@ impl Config {
             tasks: toml.tasks,
             peripherals,
             extratext: toml.extratext,
+            shared_regions: toml.shared_regions,
             config: toml.config,
             auxflash,
             buildhash,
             app_toml_path: cfg.to_owned(),
             secure_task: toml.secure_task,
-        })
+        };
+        config.check_shared_regions()?;
+        config.check_start_after()?;
+        Ok(config)
+    }
+
+    /// Checks that `start-after` dependencies name real tasks, other than the
+    /// supervisor (which never reports itself ready) and tasks that aren't
+    /// started at boot (which might never be), and don't form cycles.
+    fn check_start_after(&self) -> Result<()> {
+        let supervisor = self.tasks.get_index(0).map(|(name, _)| name);
+        for (name, task) in &self.tasks {
+            if task.start_after.is_empty() {
+                continue;
+            }
+            if !task.start {
+                bail!(
+                    "task {}: start-after only applies to tasks with \
+                     start = true",
+                    name
+                );
+            }
+            if Some(name) == supervisor {
+                bail!("task {}: the supervisor can't start-after others", name);
+            }
+            for dep in &task.start_after {
+                if !self.tasks.contains_key(dep) {
+                    bail!(
+                        "task {}: start-after: {}",
+                        name,
+                        self.task_name_suggestion(dep)
+                    );
+                }
+                if Some(dep) == supervisor {
+                    bail!(
+                        "task {}: start-after can't name the supervisor, {}, \
+                         which is always running",
+                        name,
+                        dep
+                    );
+                }
+                if !self.tasks[dep].start {
+                    bail!(
+                        "task {}: start-after can't name {}, which has \
+                         start = false, and so may never report ready",
+                        name,
+                        dep
+                    );
+                }
+            }
+        }
+
+        // Look for cycles with a depth-first search, which finds one if it
+        // reaches a task that's still on its path.
+        #[derive(Copy, Clone, PartialEq)]
+        enum Mark {
+            Unvisited,
+            OnPath,
+            Done,
+        }
+        fn visit<'a>(
+            tasks: &'a IndexMap<String, Task>,
+            marks: &mut BTreeMap<&'a str, Mark>,
+            path: &mut Vec<&'a str>,
+            name: &'a str,
+        ) -> Result<()> {
+            match marks[name] {
+                Mark::Done => return Ok(()),
+                Mark::OnPath => {
+                    let start = path.iter().position(|t| *t == name).unwrap();
+                    bail!(
+                        "start-after dependencies form a cycle: {} -> {}",
+                        path[start..].join(" -> "),
+                        name
+                    );
+                }
+                Mark::Unvisited => (),
+            }
+            marks.insert(name, Mark::OnPath);
+            path.push(name);
+            for dep in &tasks[name].start_after {
+                visit(tasks, marks, path, dep)?;
+            }
+            path.pop();
+            marks.insert(name, Mark::Done);
+            Ok(())
+        }
+
+        let mut marks: BTreeMap<&str, Mark> = self
+            .tasks
+            .keys()
+            .map(|name| (name.as_str(), Mark::Unvisited))
+            .collect();
+        for name in self.tasks.keys() {
+            visit(&self.tasks, &mut marks, &mut Vec::new(), name)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that shared regions name real memories and tasks, and that
+    /// their sizes can be expressed to the MPU. (Their placement is up to the
+    /// allocator, which takes care of alignment.)
+    fn check_shared_regions(&self) -> Result<()> {
+        for (name, region) in &self.shared_regions {
+            // The name turns into a linker symbol and a Rust identifier.
+            if name.is_empty()
+                || name.starts_with(|c: char| c.is_ascii_digit())
+                || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                bail!(
+                    "shared region {}: names may only contain letters, \
+                     digits, and underscores",
+                    name
+                );
+            }
+            if !self.outputs.contains_key(&region.memory) {
+                bail!(
+                    "shared region {}: there is no memory named {}",
+                    name,
+                    region.memory
+                );
+            }
+            let size = u64::from(region.size);
+            if size == 0
+                || self.mpu_alignment().suggest_memory_region_size(size) != size
+            {
+                bail!(
+                    "shared region {}: size {:#x} can't be described to the \
+                     MPU on {}; try {:#x}",
+                    name,
+                    region.size,
+                    self.target,
+                    self.mpu_alignment()
+                        .suggest_memory_region_size(size.max(1))
+                );
+            }
+            for task in region.read_write.iter().chain(&region.read_only) {
+                if !self.tasks.contains_key(task) {
+                    bail!(
+                        "shared region {}: {}",
+                        name,
+                        self.task_name_suggestion(task)
+                    );
+                }
+            }
+            if let Some(task) = region
+                .read_write
+                .iter()
+                .find(|t| region.read_only.contains(t))
+            {
+                bail!(
+                    "shared region {}: task {} is listed as both read-write \
+                     and read-only",
+                    name,
+                    task
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the shared regions that the given task can access, along with
+    /// whether it can write to them.
+    pub fn shared_regions_for<'a>(
+        &'a self,
+        task: &'a str,
+    ) -> impl Iterator<Item = (&'a String, &'a SharedRegion, bool)> + 'a {
+        self.shared_regions
+            .iter()
+            .filter_map(move |(name, region)| {
+                if region.read_write.iter().any(|t| t == task) {
+                    Some((name, region, true))
+                } else if region.read_only.iter().any(|t| t == task) {
+                    Some((name, region, false))
+                } else {
+                    None
+                }
+            })
     }
 
     pub fn task_name_suggestion(&self, name: &str) -> String {
@@ -202,6 +428,18 @@ impl Config {
         let task_names =
             self.tasks.keys().cloned().collect::<Vec<_>>().join(",");
         env.insert("HUBRIS_TASKS".to_string(), task_names);
+        let start_after: BTreeMap<&String, &Vec<String>> = self
+            .tasks
+            .iter()
+            .filter(|(_, task)| !task.start_after.is_empty())
+            .map(|(name, task)| (name, &task.start_after))
+            .collect();
+        if !start_after.is_empty() {
+            env.insert(
+                "HUBRIS_TASK_START_AFTER".to_string(),
+                toml::to_string(&start_after).unwrap(),
+            );
+        }
         env.insert("HUBRIS_BOARD".to_string(), self.board.to_string());
         env.insert(
             "HUBRIS_APP_TOML".to_string(),
@@ -480,8 +718,17 @@ impl std::fmt::Display for SigningMethod {
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct Signing {
-    pub priv_key: PathBuf,
-    pub root_cert: PathBuf,
+    /// RSA key for signing the image for the LPC55's boot ROM, which must be
+    /// given along with `root_cert`.
+    #[serde(default)]
+    pub priv_key: Option<PathBuf>,
+    #[serde(default)]
+    pub root_cert: Option<PathBuf>,
+    /// P-256 key (in PKCS#8 DER form) for signing images that stage0 boots.
+    /// When building stage0 itself (an app with `external-images`), stage0
+    /// is instead built to boot only images signed with this key.
+    #[serde(default)]
+    pub image_key: Option<PathBuf>,
     #[serde(default)]
     pub enable_dice: bool,
     #[serde(default)]
@@ -500,6 +747,14 @@ pub struct Kernel {
     pub stacksize: Option<u32>,
     #[serde(default)]
     pub features: Vec<String>,
+    /// Number of entries in the kernel's event log, which records faults and
+    /// restarts across warm resets. Zero disables the log.
+    #[serde(default)]
+    pub event_log: usize,
+}
+
+fn default_timers() -> u8 {
+    1
 }
 
 fn default_name() -> String {
@@ -531,11 +786,17 @@ pub struct Task {
     pub max_sizes: IndexMap<String, u32>,
     pub priority: u8,
     pub stacksize: Option<u32>,
+    #[serde(default = "default_timers")]
+    pub timers: u8,
+    #[serde(default)]
+    pub async_sends: u8,
     #[serde(default)]
     pub uses: Vec<String>,
     #[serde(default)]
     pub start: bool,
     #[serde(default)]
+    pub start_after: Vec<String>,
+    #[serde(default)]
     pub features: Vec<String>,
     #[serde(default)]
     pub interrupts: IndexMap<String, u32>,
@@ -543,6 +804,12 @@ pub struct Task {
     pub sections: IndexMap<String, String>,
     #[serde(default, deserialize_with = "deserialize_task_slot")]
     pub task_slots: IndexMap<String, String>,
+    /// Idol interface that this task serves, as a path relative to the app
+    /// config. This lets `allowed-callers` refer to operations by name.
+    #[serde(default)]
+    pub interface: Option<PathBuf>,
+    #[serde(default, deserialize_with = "deserialize_allowed_callers")]
+    pub allowed_callers: Option<AllowedCallers>,
     #[serde(default)]
     pub config: Option<ordered_toml::Value>,
     #[serde(default)]
@@ -558,6 +825,20 @@ pub struct Peripheral {
     pub interrupts: BTreeMap<String, u32>,
 }
 
+/// A region of memory that's mapped into more than one task, so that they can
+/// exchange data without copying it through leases. The build allocates it
+/// from the named memory, after the kernel and tasks.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SharedRegion {
+    pub memory: String,
+    pub size: u32,
+    #[serde(default)]
+    pub read_write: Vec<String>,
+    #[serde(default)]
+    pub read_only: Vec<String>,
+}
+
 /// In the common case, task slots map back to a task of the same name (e.g.
 /// `gpio_driver`, `rcc_driver`).  However, certain tasks need generic task
 /// slot names, e.g. they'll have a task slot named `spi_driver` which will
@@ -607,6 +888,123 @@ where
     Ok(out)
 }
 
+/// Tasks can restrict which other tasks may send them messages, and optionally
+/// which operations each of those tasks may use. The kernel enforces this by
+/// faulting any task that sends a message it isn't allowed to.  In `app.toml`,
+/// you can write something like
+/// ```toml
+/// interface = "../../idl/update.idol"
+/// allowed-callers = [
+///     "jefe",
+///     {hiffy = ["write_one_block", "finish_image_update"]},
+/// ]
+/// ```
+/// to let `jefe` send anything, and `hiffy` send only those two operations.
+/// Operation names are looked up in the task's `interface`; operations can
+/// also be given by number, which for Idol interfaces counts from 1 in the
+/// order they appear in the `.idol` file. Tasks without `allowed-callers`
+/// accept messages from anyone.
+fn deserialize_allowed_callers<'de, D>(
+    deserializer: D,
+) -> Result<Option<AllowedCallers>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(untagged)]
+    enum ArrayItem {
+        AnyOperation(String),
+        Operations(IndexMap<String, Vec<Operation>>),
+    }
+    let s: Vec<ArrayItem> = serde::Deserialize::deserialize(deserializer)?;
+    let mut out = IndexMap::new();
+    for a in s {
+        match a {
+            ArrayItem::AnyOperation(s) => {
+                out.insert(s, None);
+            }
+            ArrayItem::Operations(m) => {
+                if m.len() != 1 {
+                    return Err(serde::de::Error::invalid_length(
+                        m.len(),
+                        &"a single key-value pair",
+                    ));
+                }
+                let (k, v) = m.into_iter().next().unwrap();
+                out.insert(k, Some(v));
+            }
+        }
+    }
+    Ok(Some(out))
+}
+
+/// Tasks that may send to a task, each with the operations it may send, or
+/// `None` for any operation.
+pub type AllowedCallers = IndexMap<String, Option<Vec<Operation>>>;
+
+/// An operation listed in `allowed-callers`, either by number or by name.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Operation {
+    Code(u16),
+    Name(String),
+}
+
+impl Task {
+    /// Turns operations listed in this task's `allowed-callers` into operation
+    /// codes, looking up any names in the task's `interface`. `app_dir` is the
+    /// directory containing the app config, which the interface path is
+    /// relative to.
+    pub fn operation_codes(
+        &self,
+        app_dir: &Path,
+        operations: &[Operation],
+    ) -> Result<Vec<u16>> {
+        let by_name = |op: &Operation| matches!(op, Operation::Name(_));
+        let names = if operations.iter().any(by_name) {
+            let path = self.interface.as_ref().ok_or_else(|| {
+                anyhow!(
+                    "operations are given by name, but there's no interface \
+                     to look them up in"
+                )
+            })?;
+            idol_operations(&app_dir.join(path))?
+        } else {
+            vec![]
+        };
+
+        operations
+            .iter()
+            .map(|op| match op {
+                Operation::Code(code) => Ok(*code),
+                Operation::Name(name) => {
+                    let i = names.iter().position(|n| n == name).ok_or_else(
+                        || anyhow!("interface has no operation {}", name),
+                    )?;
+                    // Idol numbers operations from 1.
+                    Ok(u16::try_from(i + 1)?)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Reads the names of the operations in the Idol interface at `path`, in the
+/// order that Idol numbers them.
+fn idol_operations(path: &Path) -> Result<Vec<String>> {
+    // Everything but the order of the operations is left to Idol itself.
+    #[derive(Deserialize)]
+    struct Interface {
+        ops: IndexMap<String, serde::de::IgnoredAny>,
+    }
+
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("reading {}", path.display()))?;
+    let interface: Interface = ron::de::from_str(&text)
+        .with_context(|| format!("parsing {}", path.display()))?;
+    Ok(interface.ops.into_iter().map(|(name, _)| name).collect())
+}
+
 /// Stores arguments and environment variables to run on a particular task.
 pub struct BuildConfig<'a> {
     pub crate_name: String,
@@ -662,3 +1060,229 @@ cmse_nonsecure_entry,array_methods,backtrace,proc_macro_span",
         cmd
     }
 }
+
+/// Loads an app config for tests. `body` holds its tasks and any other keys
+/// and tables it needs; the rest is filled in for an STM32H7 board.
+#[cfg(test)]
+pub fn test_config(body: &str) -> Result<Config> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let chip =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../chips/stm32h7");
+    let text = format!(
+        "name = \"test\"\n\
+         target = \"thumbv7em-none-eabihf\"\n\
+         board = \"test\"\n\
+         chip = {:?}\n\
+         {}\n\
+         [kernel]\n\
+         name = \"test\"\n\
+         requires = {{flash = 1024, ram = 1024}}\n",
+        chip, body
+    );
+
+    // Chip files are found relative to the config, so it has to be a file.
+    let dir = std::env::temp_dir().join(format!(
+        "xtask-config-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("app.toml");
+    std::fs::write(&path, text)?;
+    let config = Config::from_file(&path);
+    std::fs::remove_dir_all(&dir)?;
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idl_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../idl")
+    }
+
+    fn server(extra: &str) -> Task {
+        let text = format!("name = \"update-server\"\npriority = 1\n{}", extra);
+        toml::from_str(&text).unwrap()
+    }
+
+    fn hiffy_ops(task: &Task) -> &[Operation] {
+        task.allowed_callers.as_ref().unwrap()["hiffy"]
+            .as_deref()
+            .unwrap()
+    }
+
+    #[test]
+    fn idol_operations_are_in_file_order() {
+        let ops = idol_operations(&idl_dir().join("update.idol")).unwrap();
+        assert_eq!(
+            ops[..3],
+            ["block_size", "prep_image_update", "write_one_block"]
+        );
+    }
+
+    #[test]
+    fn allowed_callers_resolve_names() {
+        let task = server(
+            r#"
+            interface = "update.idol"
+            allowed-callers = ["jefe", {hiffy = ["write_one_block", 1]}]
+            "#,
+        );
+        assert!(task.allowed_callers.as_ref().unwrap()["jefe"].is_none());
+        let codes = task.operation_codes(&idl_dir(), hiffy_ops(&task));
+        assert_eq!(codes.unwrap(), [3, 1]);
+    }
+
+    #[test]
+    fn allowed_callers_reject_bad_names() {
+        let task = server(
+            r#"
+            interface = "update.idol"
+            allowed-callers = [{hiffy = ["write_many_blocks"]}]
+            "#,
+        );
+        assert!(task.operation_codes(&idl_dir(), hiffy_ops(&task)).is_err());
+
+        // Names need an interface to be looked up in, but numbers don't.
+        let task = server(r#"allowed-callers = [{hiffy = ["block_size"]}]"#);
+        assert!(task.operation_codes(&idl_dir(), hiffy_ops(&task)).is_err());
+        let task = server(r#"allowed-callers = [{hiffy = [1, 2]}]"#);
+        let codes = task.operation_codes(&idl_dir(), hiffy_ops(&task));
+        assert_eq!(codes.unwrap(), [1, 2]);
+    }
+
+    const TASKS: &str = r#"
+        [tasks.jefe]
+        name = "task-jefe"
+        priority = 0
+
+        [tasks.net]
+        name = "task-net"
+        priority = 1
+
+        [tasks.app]
+        name = "task-app"
+        priority = 2
+    "#;
+
+    fn shared_region_error(region: &str) -> String {
+        let body = format!("{}\n[shared-regions.{}", TASKS, region);
+        test_config(&body).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn shared_regions_are_accepted() {
+        let body = format!(
+            "{}\n\
+             [shared-regions.net_rx]\n\
+             memory = \"sram1\"\n\
+             size = 0x800\n\
+             read-write = [\"net\"]\n\
+             read-only = [\"app\"]\n",
+            TASKS
+        );
+        let config = test_config(&body).unwrap();
+        let regions = config
+            .shared_regions_for("app")
+            .map(|(name, _, write)| (name.as_str(), write))
+            .collect::<Vec<_>>();
+        assert_eq!(regions, [("net_rx", false)]);
+        assert_eq!(config.shared_regions_for("jefe").count(), 0);
+    }
+
+    #[test]
+    fn shared_regions_reject_read_write_and_read_only() {
+        let err = shared_region_error(
+            r#"net_rx]
+            memory = "sram1"
+            size = 0x800
+            read-write = ["net", "app"]
+            read-only = ["app"]
+            "#,
+        );
+        assert!(err.contains("app is listed as both"), "{}", err);
+    }
+
+    #[test]
+    fn shared_regions_reject_sizes_the_mpu_cant_describe() {
+        let err = shared_region_error(
+            r#"net_rx]
+            memory = "sram1"
+            size = 0x600
+            read-write = ["net"]
+            "#,
+        );
+        assert!(err.contains("try 0x800"), "{}", err);
+
+        let err = shared_region_error(
+            r#"net_rx]
+            memory = "sram1"
+            size = 0
+            read-write = ["net"]
+            "#,
+        );
+        assert!(err.contains("can't be described"), "{}", err);
+    }
+
+    #[test]
+    fn shared_regions_reject_unknown_names() {
+        let err = shared_region_error(
+            r#"net_rx]
+            memory = "sram4"
+            size = 0x800
+            "#,
+        );
+        assert!(err.contains("no memory named sram4"), "{}", err);
+
+        let err = shared_region_error(
+            r#"net_rx]
+            memory = "sram1"
+            size = 0x800
+            read-only = ["ap"]
+            "#,
+        );
+        assert!(err.starts_with("shared region net_rx:"), "{}", err);
+
+        let err = shared_region_error(
+            r#"net-rx]
+            memory = "sram1"
+            size = 0x800
+            "#,
+        );
+        assert!(err.contains("names may only contain"), "{}", err);
+    }
+
+    fn start_after_error(net: &str) -> String {
+        let body = format!(
+            "{}\n\
+             [tasks.net]\n\
+             name = \"task-net\"\n\
+             priority = 1\n\
+             {}\n\
+             [tasks.app]\n\
+             name = \"task-app\"\n\
+             priority = 2\n\
+             start = true\n\
+             start-after = [\"net\"]\n",
+            "[tasks.jefe]\nname = \"task-jefe\"\npriority = 0\nstart = true",
+            net
+        );
+        test_config(&body).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn start_after_rejects_tasks_not_started_at_boot() {
+        let err = start_after_error("");
+        assert!(err.contains("net, which has start = false"), "{}", err);
+    }
+
+    #[test]
+    fn start_after_rejects_cycles() {
+        let err = start_after_error("start = true\nstart-after = [\"app\"]");
+        assert!(err.contains("form a cycle: net -> app -> net"), "{}", err);
+    }
+}
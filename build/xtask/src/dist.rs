@@ -16,7 +16,8 @@ use colored::*;
 use indexmap::IndexMap;
 use path_slash::PathBufExt;
 use serde::Serialize;
-use zerocopy::AsBytes;
+use sha3::{Digest, Sha3_256};
+use zerocopy::{AsBytes, FromBytes};
 
 use crate::{
     config::{BuildConfig, Config},
@@ -346,8 +347,10 @@ pub fn package(
             );
         }
 
-        // Generate combined SREC, which is our source of truth for combined images.
         let (kentry, _ksymbol_table) = kern_build.unwrap();
+        add_manifest(&cfg, allocs, &mut all_output_sections, image_name)?;
+
+        // Generate combined SREC, which is our source of truth for combined images.
         write_srec(
             &all_output_sections,
             kentry,
@@ -356,9 +359,20 @@ pub fn package(
 
         translate_srec_to_other_formats(&cfg.img_dir(image_name), "combined")?;
 
-        if let Some(signing) = &cfg.toml.signing {
-            let priv_key = &signing.priv_key;
-            let root_cert = &signing.root_cert;
+        // Apps that boot other images are built to trust the image key,
+        // rather than being signed with it.
+        let signing = cfg.toml.signing.as_ref();
+        let image_key = match signing.and_then(|s| s.image_key.as_ref()) {
+            Some(path) if cfg.toml.external_images.is_empty() => {
+                Some(crate::sign::load_key(&cfg.app_src_dir.join(path))?)
+            }
+            _ => None,
+        };
+        let rsa = signing.and_then(|s| {
+            Some((s, s.priv_key.as_ref()?, s.root_cert.as_ref()?))
+        });
+
+        if let Some((signing, priv_key, root_cert)) = rsa {
             let rkth = lpc55_sign::signed_image::sign_image(
                 &cfg.img_file("combined.bin", image_name),
                 &cfg.app_src_dir.join(&priv_key),
@@ -400,6 +414,40 @@ pub fn package(
                 &rkth,
                 &cfg.img_file("CMPA.bin", image_name),
             )?;
+        } else if let Some(key) = image_key {
+            // Stage0 finds the image header right after the vector table,
+            // and so do we.
+            let kernel = std::fs::read(cfg.img_file("kernel", image_name))?;
+            let kernel = goblin::elf::Elf::parse(&kernel)?;
+            let header = elf::get_section_by_name(&kernel, ".header")
+                .ok_or_else(|| anyhow!("kernel has no image header to sign"))?;
+            let flash = cfg
+                .toml
+                .memories(image_name)?
+                .get(&"flash".to_string())
+                .ok_or_else(|| anyhow!("failed to get flash region"))?
+                .clone();
+
+            // As above, this assumes that the binary starts at the beginning
+            // of flash.
+            let image =
+                std::fs::read(cfg.img_file("combined.bin", image_name))?;
+            let signed = crate::sign::sign_image(
+                &image,
+                (header.sh_addr - flash.start as u64) as usize,
+                &key,
+            )?;
+            if signed.len() > flash.len() {
+                bail!("no room in flash for the image's signature");
+            }
+            std::fs::write(cfg.img_file("final.bin", image_name), signed)?;
+            binary_to_srec(
+                &cfg.img_file("final.bin", image_name),
+                flash.start,
+                kentry,
+                &cfg.img_file("final.srec", image_name),
+            )?;
+            translate_srec_to_other_formats(&cfg.img_dir(image_name), "final")?;
         } else {
             // If there's no bootloader, the "combined" and "final" images are
             // identical, so we copy from one to the other
@@ -413,6 +461,7 @@ pub fn package(
             }
         }
         write_gdb_script(&cfg, image_name)?;
+        write_log_strings(&cfg, image_name)?;
         build_archive(&cfg, image_name)?;
     }
     Ok(allocated)
@@ -576,6 +625,27 @@ fn write_gdb_script(cfg: &PackageConfig, image_name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Collects the interned log strings from every task into a table for host
+/// tools, checking each call site's format string as we go.
+fn write_log_strings(cfg: &PackageConfig, image_name: &str) -> Result<()> {
+    let mut table = crate::log::LogStrings::new();
+    for name in cfg.toml.tasks.keys() {
+        let strings =
+            crate::log::extract_strings(&cfg.img_file(name, image_name))
+                .with_context(|| {
+                    format!("collecting log strings from task {}", name)
+                })?;
+        if !strings.is_empty() {
+            table.insert(name.clone(), strings);
+        }
+    }
+    std::fs::write(
+        cfg.img_file(crate::log::LOG_STRINGS_FILE, image_name),
+        serde_json::to_string_pretty(&table)?,
+    )?;
+    Ok(())
+}
+
 fn build_archive(cfg: &PackageConfig, image_name: &str) -> Result<()> {
     // Bundle everything up into an archive.
     let mut archive = Archive::new(
@@ -593,6 +663,8 @@ fn build_archive(cfg: &PackageConfig, image_name: &str) -> Result<()> {
         - elf/tasks/ contains each task by name.\n\
         - elf/kernel is the kernel.\n\
         - img/ contains the final firmware images.\n\
+        - img/manifest.bin is the image manifest measured by stage0, if the\n  \
+          image has one.\n\
         - debug/ contains OpenOCD and GDB scripts, if available.\n",
     )?;
 
@@ -614,6 +686,11 @@ fn build_archive(cfg: &PackageConfig, image_name: &str) -> Result<()> {
     }
     archive.copy(cfg.img_file("kernel", image_name), elf_dir.join("kernel"))?;
 
+    archive.copy(
+        cfg.img_file(crate::log::LOG_STRINGS_FILE, image_name),
+        PathBuf::from("info").join(crate::log::LOG_STRINGS_FILE),
+    )?;
+
     let img_dir = PathBuf::from("img");
 
     for f in ["combined", "final"] {
@@ -624,6 +701,11 @@ fn build_archive(cfg: &PackageConfig, image_name: &str) -> Result<()> {
         }
     }
 
+    let manifest = cfg.img_file("manifest.bin", image_name);
+    if manifest.exists() {
+        archive.copy(manifest, img_dir.join("manifest.bin"))?;
+    }
+
     //
     // To allow for the image to be flashed based only on the archive (e.g.,
     // by Humility), we pull in our flash configuration, flatten it to pull in
@@ -771,6 +853,11 @@ fn link_task(
 ) -> Result<()> {
     println!("linking task '{}'", name);
     let task_toml = &cfg.toml.tasks[name];
+    let shared = cfg
+        .toml
+        .shared_regions_for(name)
+        .map(|(r, _, _)| (r.clone(), allocs.shared[r].clone()))
+        .collect();
     generate_task_linker_script(
         "memory.x",
         &allocs.tasks[name],
@@ -780,6 +867,7 @@ fn link_task(
         })?,
         &cfg.toml.all_regions("flash".to_string())?,
         image_name,
+        &shared,
     )
     .context(format!("failed to generate linker script for {}", name))?;
     fs::copy("build/task-link.x", "target/link.x")?;
@@ -801,12 +889,23 @@ fn link_task(
 fn link_dummy_task(cfg: &PackageConfig, name: &str) -> Result<()> {
     let task_toml = &cfg.toml.tasks[name];
 
-    let memories = cfg
+    let memories: BTreeMap<_, _> = cfg
         .toml
         .memories(&cfg.toml.image_names[0])?
         .into_iter()
         .collect();
 
+    // Shared regions haven't been placed yet, so point them at the start of
+    // their memory; this only needs to be good enough to link.
+    let shared = cfg
+        .toml
+        .shared_regions_for(name)
+        .map(|(r, region, _)| {
+            let start = memories[&region.memory].start;
+            (r.clone(), start..start + region.size)
+        })
+        .collect();
+
     generate_task_linker_script(
         "memory.x",
         &memories, // ALL THE SPACE
@@ -816,6 +915,7 @@ fn link_dummy_task(cfg: &PackageConfig, name: &str) -> Result<()> {
         })?,
         &cfg.toml.all_regions("flash".to_string())?,
         &cfg.toml.image_names[0],
+        &shared,
     )
     .context(format!("failed to generate linker script for {}", name))?;
     fs::copy("build/task-tlink.x", "target/link.x")?;
@@ -885,6 +985,7 @@ fn build_kernel(
     let kconfig = make_kconfig(
         &cfg.toml,
         &allocs.tasks,
+        &allocs.shared,
         entry_points,
         image_name,
         secure,
@@ -899,28 +1000,45 @@ fn build_kernel(
         &allocs.kernel,
         cfg.toml.kernel.stacksize.unwrap_or(DEFAULT_KERNEL_STACK),
         &cfg.toml.image_memories("flash".to_string())?,
+        &cfg.toml.all_regions("flash".to_string())?,
     )?;
 
     fs::copy("build/kernel-link.x", "target/link.x")?;
 
     let image_id = image_id.finish();
 
-    // Build the kernel.
-    let build_config = cfg.toml.kernel_build_config(
-        cfg.verbose,
-        &[
-            ("HUBRIS_KCONFIG", &kconfig),
-            ("HUBRIS_IMAGE_ID", &format!("{}", image_id)),
-        ],
-        Some(&cfg.sysroot),
-    );
+    // Build the kernel. If it boots other images (i.e. it's stage0), tell it
+    // which key they're signed with.
+    let image_id = format!("{}", image_id);
+    let mut env = vec![
+        ("HUBRIS_KCONFIG", kconfig.as_str()),
+        ("HUBRIS_IMAGE_ID", image_id.as_str()),
+    ];
+    let image_key =
+        cfg.toml.signing.as_ref().and_then(|s| s.image_key.as_ref());
+    let key_hash = match image_key {
+        Some(path) if !cfg.toml.external_images.is_empty() => {
+            Some(crate::sign::key_hash(&crate::sign::load_key(
+                &cfg.app_src_dir.join(path),
+            )?))
+        }
+        _ => None,
+    };
+    if let Some(key_hash) = &key_hash {
+        env.push(("HUBRIS_IMAGE_KEY_HASH", key_hash));
+    }
+    let build_config =
+        cfg.toml
+            .kernel_build_config(cfg.verbose, &env, Some(&cfg.sysroot));
     build(cfg, "kernel", build_config, false)?;
     if update_image_header(
+        &cfg.toml,
         &cfg.dist_file("kernel"),
         &cfg.img_file("kernel.modified", image_name),
         all_memories,
         all_output_sections,
         secure,
+        abi::ManifestHeader::len(cfg.toml.tasks.len() as u32 + 1),
     )? {
         std::fs::copy(
             &cfg.dist_file("kernel"),
@@ -946,15 +1064,18 @@ fn build_kernel(
     Ok((kentry, ksymbol_table))
 }
 
-/// Adjusts the hubris image header in the ELF file.
+/// Adjusts the hubris image header in the ELF file, leaving `manifest_len`
+/// bytes at the end of the image for the manifest.
 /// Returns true if the header was found and updated,
 /// false otherwise.
 fn update_image_header(
+    toml: &Config,
     input: &Path,
     output: &Path,
     map: &IndexMap<String, Range<u32>>,
     all_output_sections: &mut BTreeMap<u32, LoadSegment>,
     secure: &Option<SecureData>,
+    manifest_len: u32,
 ) -> Result<bool> {
     use goblin::container::Container;
 
@@ -975,116 +1096,13 @@ fn update_image_header(
                 && (sec.sh_size as usize)
                     >= core::mem::size_of::<abi::ImageHeader>()
             {
-                let flash = map.get("flash").unwrap();
-
-                // Compute the total image size by finding the highest address
-                // from all the tasks built. Because this is the kernel all
-                // tasks must be built
-                let mut end = 0;
-
-                for (addr, sec) in all_output_sections {
-                    if (*addr as u32) > flash.start
-                        && (*addr as u32) < flash.end
-                        && (*addr as u32) > end
-                    {
-                        end = addr + (sec.data.len() as u32);
-                    }
-                }
-
-                let len = end - flash.start;
-
-                let mut header = abi::ImageHeader {
-                    magic: abi::HEADER_MAGIC,
-                    total_image_len: len as u32,
-                    ..Default::default()
-                };
-
-                let last = if let Some(s) = secure {
-                    let mut i = 0;
-
-                    // Our memory layout with a secure task looks like the
-                    // following:
-                    // +---------------+
-                    // |               |
-                    // |   Task        |
-                    // | (Non-secure)  |
-                    // |               |
-                    // |               |
-                    // +---------------+
-                    // |               |
-                    // |   Task        |
-                    // | (Non-secure)  |
-                    // |               |
-                    // |               |
-                    // +---------------+
-                    // |               |
-                    // |   Task        |
-                    // | (Secure)      |
-                    // +---------------+
-                    // |    NSC        |
-                    // +---------------+
-                    // |               |
-                    // |   Task        |
-                    // | (Non-secure)  |
-                    // |               |
-                    // |               |
-                    // +---------------+
-                    //
-                    // The entries in the SAU specify regions that are
-                    // non-secure OR non-secure callable (NSC).
-                    // This means the entry for our flash gets broken
-                    // down into three entries:
-                    // 1) Non-secure range before the secure task
-                    // 2) non-secure range after the secure task
-                    // 3) NSC region in the secure task
-                    for (_, range) in map.iter() {
-                        if range.contains(&s.secure.start) {
-                            // These values correspond to SAU_RBAR and
-                            // SAU_RLAR which are defined in D1.2.221 and
-                            // D1.2.222 of the ARMv8m manual
-                            //
-                            // Bit0 of RLAR indicates a region is valid,
-                            // Bit1 indicates that the region is NSC
-                            // All entries much be 32-byte aligned
-                            header.sau_entries[i].rbar = range.start;
-                            header.sau_entries[i].rlar =
-                                (s.secure.start - 1) & !0x1f | 1;
-
-                            i += 1;
-
-                            header.sau_entries[i].rbar = s.secure.end;
-                            header.sau_entries[i].rlar =
-                                (range.end - 1) & !0x1f | 1;
-
-                            i += 1;
-
-                            header.sau_entries[i].rbar = s.nsc.start;
-                            header.sau_entries[i].rlar =
-                                (s.nsc.end - 1) & !0x1f | 3;
-
-                            i += 1;
-                        } else {
-                            header.sau_entries[i].rbar = range.start;
-                            header.sau_entries[i].rlar =
-                                (range.end - 1) & !0x1f | 1;
-                            i += 1;
-                        }
-                    }
-                    i
-                } else {
-                    for (i, (_, range)) in map.iter().enumerate() {
-                        header.sau_entries[i].rbar = range.start;
-                        header.sau_entries[i].rlar =
-                            (range.end - 1) & !0x1f | 1;
-                    }
-
-                    map.len()
-                };
-
-                // TODO need a better place to put this...
-                header.sau_entries[last].rbar = 0x4000_0000;
-                header.sau_entries[last].rlar = 0x4fff_ffe0 | 1;
-
+                let header = make_image_header(
+                    toml,
+                    map,
+                    all_output_sections,
+                    secure,
+                    manifest_len,
+                );
                 header
                     .write_to_prefix(
                         &mut file_image[(sec.sh_offset as usize)..],
@@ -1099,6 +1117,283 @@ fn update_image_header(
     Ok(false)
 }
 
+/// Builds the hubris image header for an image made of
+/// `all_output_sections`, leaving `manifest_len` bytes at the end of the image
+/// for the manifest.
+fn make_image_header(
+    toml: &Config,
+    map: &IndexMap<String, Range<u32>>,
+    all_output_sections: &BTreeMap<u32, LoadSegment>,
+    secure: &Option<SecureData>,
+    manifest_len: u32,
+) -> abi::ImageHeader {
+    let flash = map.get("flash").unwrap();
+
+    // Compute the total image size by finding the highest address
+    // from all the tasks built. Because this is the kernel all
+    // tasks must be built
+    let mut end = 0;
+
+    for (addr, sec) in all_output_sections {
+        if (*addr as u32) > flash.start
+            && (*addr as u32) < flash.end
+            && (*addr as u32) > end
+        {
+            end = addr + (sec.data.len() as u32);
+        }
+    }
+
+    // The manifest goes after everything else, and is part of
+    // the image (so it's covered by the image's signature).
+    let manifest = (end - flash.start + 3) & !3;
+
+    let mut header = abi::ImageHeader {
+        magic: abi::HEADER_MAGIC,
+        total_image_len: manifest + manifest_len,
+        version: toml.version,
+        epoch: toml.epoch,
+        manifest,
+        ..Default::default()
+    };
+
+    let last = if let Some(s) = secure {
+        let mut i = 0;
+
+        // Our memory layout with a secure task looks like the
+        // following:
+        // +---------------+
+        // |               |
+        // |   Task        |
+        // | (Non-secure)  |
+        // |               |
+        // |               |
+        // +---------------+
+        // |               |
+        // |   Task        |
+        // | (Non-secure)  |
+        // |               |
+        // |               |
+        // +---------------+
+        // |               |
+        // |   Task        |
+        // | (Secure)      |
+        // +---------------+
+        // |    NSC        |
+        // +---------------+
+        // |               |
+        // |   Task        |
+        // | (Non-secure)  |
+        // |               |
+        // |               |
+        // +---------------+
+        //
+        // The entries in the SAU specify regions that are
+        // non-secure OR non-secure callable (NSC).
+        // This means the entry for our flash gets broken
+        // down into three entries:
+        // 1) Non-secure range before the secure task
+        // 2) non-secure range after the secure task
+        // 3) NSC region in the secure task
+        for (_, range) in map.iter() {
+            if range.contains(&s.secure.start) {
+                // These values correspond to SAU_RBAR and
+                // SAU_RLAR which are defined in D1.2.221 and
+                // D1.2.222 of the ARMv8m manual
+                //
+                // Bit0 of RLAR indicates a region is valid,
+                // Bit1 indicates that the region is NSC
+                // All entries much be 32-byte aligned
+                header.sau_entries[i].rbar = range.start;
+                header.sau_entries[i].rlar = (s.secure.start - 1) & !0x1f | 1;
+
+                i += 1;
+
+                header.sau_entries[i].rbar = s.secure.end;
+                header.sau_entries[i].rlar = (range.end - 1) & !0x1f | 1;
+
+                i += 1;
+
+                header.sau_entries[i].rbar = s.nsc.start;
+                header.sau_entries[i].rlar = (s.nsc.end - 1) & !0x1f | 3;
+
+                i += 1;
+            } else {
+                header.sau_entries[i].rbar = range.start;
+                header.sau_entries[i].rlar = (range.end - 1) & !0x1f | 1;
+                i += 1;
+            }
+        }
+        i
+    } else {
+        for (i, (_, range)) in map.iter().enumerate() {
+            header.sau_entries[i].rbar = range.start;
+            header.sau_entries[i].rlar = (range.end - 1) & !0x1f | 1;
+        }
+
+        map.len()
+    };
+
+    // TODO need a better place to put this...
+    header.sau_entries[last].rbar = 0x4000_0000;
+    header.sau_entries[last].rlar = 0x4fff_ffe0 | 1;
+
+    header
+}
+
+/// Fills in the manifest of an image with a header (see
+/// `abi::ManifestHeader`), in the space that `update_image_header` left for
+/// it, and writes a copy to `manifest.bin`.
+fn add_manifest(
+    cfg: &PackageConfig,
+    allocs: &Allocations,
+    all_output_sections: &mut BTreeMap<u32, LoadSegment>,
+    image_name: &str,
+) -> Result<()> {
+    let kernel = std::fs::read(cfg.img_file("kernel", image_name))?;
+    let kernel = goblin::elf::Elf::parse(&kernel)?;
+    let header_addr = match elf::get_section_by_name(&kernel, ".header") {
+        Some(header)
+            if header.sh_size as usize
+                >= core::mem::size_of::<abi::ImageHeader>() =>
+        {
+            header.sh_addr as u32
+        }
+        _ => return Ok(()),
+    };
+
+    let flash = cfg
+        .toml
+        .memories(&image_name.to_string())?
+        .get(&"flash".to_string())
+        .ok_or_else(|| anyhow!("failed to get flash region"))?
+        .clone();
+    let regions = std::iter::once(&allocs.kernel)
+        .chain(cfg.toml.tasks.keys().map(|name| &allocs.tasks[name]))
+        .map(|region| {
+            region
+                .get("flash")
+                .cloned()
+                .ok_or_else(|| anyhow!("component has no flash allocation"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut app_hash = [0; 32];
+    app_hash.copy_from_slice(&Sha3_256::digest(&std::fs::read(
+        &cfg.app_toml_file,
+    )?));
+
+    let (addr, data) = make_manifest(
+        all_output_sections,
+        header_addr,
+        flash,
+        &regions,
+        app_hash,
+    )?;
+
+    std::fs::write(cfg.img_file("manifest.bin", image_name), &data)?;
+    all_output_sections.insert(
+        addr,
+        LoadSegment {
+            source_file: cfg.img_file("manifest.bin", image_name),
+            data,
+        },
+    );
+    Ok(())
+}
+
+/// Builds the manifest of an image whose header is at `header_addr` in
+/// `flash`, listing each of `regions` (the kernel's flash, and then each
+/// task's) as a component. Returns the address the manifest goes at, and its
+/// contents.
+fn make_manifest(
+    all_output_sections: &BTreeMap<u32, LoadSegment>,
+    header_addr: u32,
+    flash: Range<u32>,
+    regions: &[Range<u32>],
+    app_hash: [u8; 32],
+) -> Result<(u32, Vec<u8>)> {
+    let header_size = core::mem::size_of::<abi::ImageHeader>() as u32;
+    let header = abi::ImageHeader::read_from(
+        &flash_contents(
+            all_output_sections,
+            header_addr..header_addr + header_size,
+        )[..],
+    )
+    .unwrap();
+
+    // Each component's hash covers its flash up to the end of the last
+    // segment in it, which is what ends up in the image; the gaps are
+    // zero-filled, as they are by `objcopy` in the binary image.
+    let mut components = vec![];
+    for region in regions {
+        let end = all_output_sections
+            .range(region.clone())
+            .map(|(addr, seg)| (addr + seg.data.len() as u32).min(region.end))
+            .max()
+            .unwrap_or(region.start);
+        let contents = flash_contents(all_output_sections, region.start..end);
+        let mut component = abi::ManifestComponent {
+            base: region.start,
+            len: end - region.start,
+            ..Default::default()
+        };
+        component
+            .hash
+            .copy_from_slice(&Sha3_256::digest(&contents)[..]);
+        components.push(component);
+    }
+
+    let manifest = abi::ManifestHeader {
+        magic: abi::MANIFEST_MAGIC,
+        format: abi::MANIFEST_FORMAT,
+        version: header.version,
+        epoch: header.epoch,
+        app_hash,
+        component_count: components.len() as u32,
+    };
+    let mut data = manifest.as_bytes().to_vec();
+    for component in &components {
+        data.extend_from_slice(component.as_bytes());
+    }
+    if data.len() as u32
+        != header.total_image_len.saturating_sub(header.manifest)
+    {
+        bail!("image header doesn't leave the right space for the manifest");
+    }
+
+    let addr = flash.start + header.manifest;
+    if addr + data.len() as u32 > flash.end {
+        bail!("no room in flash for the image manifest");
+    }
+    if let Some((overlap, _)) = all_output_sections
+        .range(addr..addr + data.len() as u32)
+        .next()
+    {
+        bail!("image manifest at {:#x} overlaps {:#x}", addr, overlap);
+    }
+
+    Ok((addr, data))
+}
+
+/// Returns the contents of a range of flash from the loaded segments, with
+/// anything not covered by a segment left as zero.
+fn flash_contents(
+    all_output_sections: &BTreeMap<u32, LoadSegment>,
+    range: Range<u32>,
+) -> Vec<u8> {
+    let mut out = vec![0; (range.end - range.start) as usize];
+    for (&addr, seg) in all_output_sections.range(..range.end) {
+        let start = addr.max(range.start);
+        let end = (addr + seg.data.len() as u32).min(range.end);
+        if start < end {
+            out[(start - range.start) as usize..(end - range.start) as usize]
+                .copy_from_slice(
+                    &seg.data[(start - addr) as usize..(end - addr) as usize],
+                );
+        }
+    }
+    out
+}
+
 /// Prints warning messages about priority inversions
 fn check_task_priorities(toml: &Config) -> Result<()> {
     let idle_priority = toml.tasks["idle"].priority;
@@ -1138,6 +1433,7 @@ fn generate_task_linker_script(
     stacksize: u32,
     images: &IndexMap<String, Range<u32>>,
     image_name: &str,
+    shared: &BTreeMap<String, Range<u32>>,
 ) -> Result<()> {
     // Put the linker script somewhere the linker can find it
     let mut linkscr = File::create(Path::new(&format!("target/{}", name)))?;
@@ -1195,6 +1491,21 @@ fn generate_task_linker_script(
         )?;
     }
 
+    // Shared regions are found by symbol, since they aren't part of any
+    // memory the task's own sections get placed in.
+    for (name, range) in shared {
+        writeln!(
+            linkscr,
+            "__shared_region_{}_base = {:#010x};",
+            name, range.start
+        )?;
+        writeln!(
+            linkscr,
+            "__shared_region_{}_end = {:#010x};",
+            name, range.end
+        )?;
+    }
+
     // The task may have defined additional section-to-memory mappings.
     if let Some(map) = sections {
         writeln!(linkscr, "SECTIONS {{")?;
@@ -1214,6 +1525,7 @@ fn generate_kernel_linker_script(
     map: &BTreeMap<String, Range<u32>>,
     stacksize: u32,
     images: &IndexMap<String, Range<u32>>,
+    regions: &IndexMap<String, Range<u32>>,
 ) -> Result<()> {
     // Put the linker script somewhere the linker can find it
     let mut linkscr =
@@ -1286,6 +1598,23 @@ fn generate_kernel_linker_script(
         )
         .unwrap();
     }
+
+    // As for tasks, every flash region can be found by symbol, including ones
+    // that aren't images (like stage0's boot state).
+    for (name, out) in regions {
+        writeln!(
+            linkscr,
+            "__IMAGE_{}_BASE = {:#010x};",
+            name.to_ascii_uppercase(),
+            out.start
+        )?;
+        writeln!(
+            linkscr,
+            "__IMAGE_{}_END = {:#010x};",
+            name.to_ascii_uppercase(),
+            out.end
+        )?;
+    }
     Ok(())
 }
 
@@ -1469,6 +1798,8 @@ pub struct Allocations {
     pub kernel: BTreeMap<String, Range<u32>>,
     /// Map from task-name to memory-name to address-range
     pub tasks: BTreeMap<String, BTreeMap<String, Range<u32>>>,
+    /// Map from shared-region-name to address-range
+    pub shared: BTreeMap<String, Range<u32>>,
 }
 
 /// Allocates address space from all regions for the kernel and all tasks.
@@ -1629,13 +1960,67 @@ pub fn allocate_all(
                 // Panic here because otherwise it's a hang.
                 panic!("loop iteration without progess made!");
             }
+
+            // Shared regions go after everything else, largest first to keep
+            // alignment padding down.
+            let mut shared = toml
+                .shared_regions
+                .iter()
+                .filter(|(_, r)| r.memory == *region)
+                .collect::<Vec<_>>();
+            shared.sort_by_key(|(_, r)| std::cmp::Reverse(r.size));
+            for (name, r) in shared {
+                let align = toml.task_memory_alignment(r.size);
+                allocs.shared.insert(
+                    name.to_string(),
+                    allocate_one(region, r.size, align, avail)?,
+                );
+            }
         }
 
+        check_allocation_overlap(&allocs)?;
         result.insert(image_name.to_string(), (allocs, free));
     }
     Ok(result)
 }
 
+/// Double-checks that no two allocations overlap. The allocator shouldn't
+/// allow this, but shared regions are mapped into several tasks at once, so
+/// the consequences of getting it wrong would be unusually confusing.
+fn check_allocation_overlap(allocs: &Allocations) -> Result<()> {
+    let mut ranges = allocs
+        .kernel
+        .iter()
+        .map(|(mem, r)| (format!("kernel {}", mem), r))
+        .chain(allocs.tasks.iter().flat_map(|(task, mems)| {
+            mems.iter()
+                .map(move |(mem, r)| (format!("task {} {}", task, mem), r))
+        }))
+        .chain(
+            allocs
+                .shared
+                .iter()
+                .map(|(name, r)| (format!("shared region {}", name), r)),
+        )
+        .collect::<Vec<_>>();
+    ranges.sort_by_key(|(_, r)| r.start);
+    for pair in ranges.windows(2) {
+        let ((a, ra), (b, rb)) = (&pair[0], &pair[1]);
+        if ra.end > rb.start {
+            bail!(
+                "{} ({:#x}..{:#x}) overlaps {} ({:#x}..{:#x})",
+                a,
+                ra.start,
+                ra.end,
+                b,
+                rb.start,
+                rb.end
+            );
+        }
+    }
+    Ok(())
+}
+
 fn allocate_k(
     region: &str,
     size: u32,
@@ -1696,6 +2081,16 @@ pub struct KernelConfig {
     tasks: Vec<abi::TaskDesc>,
     regions: Vec<abi::RegionDesc>,
     irqs: Vec<abi::Interrupt>,
+    acls: Vec<Option<Vec<IpcGrant>>>,
+    event_log: usize,
+}
+
+/// Permission for one task to send to another, as checked by the kernel. See
+/// `kern::task::IpcGrant`.
+#[derive(Serialize)]
+pub struct IpcGrant {
+    caller: u16,
+    operations: Option<Vec<u16>>,
 }
 
 /// Generate the application descriptor table that the kernel uses to find and
@@ -1710,6 +2105,7 @@ pub struct KernelConfig {
 pub fn make_kconfig(
     toml: &Config,
     task_allocations: &BTreeMap<String, BTreeMap<String, Range<u32>>>,
+    shared_allocations: &BTreeMap<String, Range<u32>>,
     entry_points: &HashMap<String, u32>,
     image_name: &str,
     secure: &Option<SecureData>,
@@ -1718,6 +2114,11 @@ pub fn make_kconfig(
     let mut regions = vec![];
     let mut task_descs = vec![];
     let mut irqs = vec![];
+    let mut acls = vec![];
+
+    // Paths in the config, like task interfaces, are relative to its
+    // directory.
+    let app_dir = toml.app_toml_path.parent().unwrap();
 
     // Region 0 is the NULL region, used as a placeholder. It gives no access to
     // memory.
@@ -1797,6 +2198,48 @@ pub fn make_kconfig(
         });
     }
 
+    // Shared regions come next. Each gets up to two entries: one for the tasks
+    // that can write to it, and one for the tasks that can only read it.
+    let mut shared_index: HashMap<&str, [Option<u8>; 2]> = HashMap::new();
+    for (name, shared) in &toml.shared_regions {
+        let range = &shared_allocations[name];
+        let out = toml.outputs[&shared.memory]
+            .iter()
+            .find(|o| o.name == *image_name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "shared region {}: memory {} has no region for image {}",
+                    name,
+                    shared.memory,
+                    image_name
+                )
+            })?;
+        // Shared regions are never executable.
+        let mut attributes = abi::RegionAttributes::READ;
+        if out.dma {
+            attributes |= abi::RegionAttributes::DMA;
+        }
+
+        let mut entries = [None; 2];
+        for (entry, (tasks, attributes)) in entries.iter_mut().zip([
+            (
+                &shared.read_write,
+                attributes | abi::RegionAttributes::WRITE,
+            ),
+            (&shared.read_only, attributes),
+        ]) {
+            if !tasks.is_empty() {
+                *entry = Some(regions.len() as u8);
+                regions.push(abi::RegionDesc {
+                    base: range.start,
+                    size: range.end - range.start,
+                    attributes,
+                });
+            }
+        }
+        shared_index.insert(name, entries);
+    }
+
     // The remaining regions are allocated to tasks on a first-come first-serve
     // basis. We don't check power-of-two requirements in task_allocations
     // because it's the result of autosizing, which already takes the MPU into
@@ -1806,12 +2249,15 @@ pub fn make_kconfig(
         // Each task has up to 8, chosen from its 'requires' and 'uses' keys.
         let mut task_regions = [0; 8];
 
-        if task.uses.len() + task_allocations[name].len() > 8 {
-            panic!(
-                "task {} uses {} peripherals and {} memories (too many)",
+        let shared = toml.shared_regions_for(name).collect::<Vec<_>>();
+        if task.uses.len() + task_allocations[name].len() + shared.len() > 8 {
+            bail!(
+                "task {} uses {} peripherals, {} memories, and {} shared \
+                 regions (too many)",
                 name,
                 task.uses.len(),
-                task_allocations[name].len()
+                task_allocations[name].len(),
+                shared.len()
             );
         }
 
@@ -1870,8 +2316,19 @@ pub fn make_kconfig(
             }
         }
 
+        // Shared regions were also set up in advance; pick the entry that
+        // matches this task's access.
+        let base = allocs.len() + task.uses.len();
+        for (j, (shared_name, _, writable)) in shared.iter().enumerate() {
+            let [rw, ro] = shared_index[shared_name.as_str()];
+            let entry = if *writable { rw } else { ro };
+            task_regions[base + j] = entry.unwrap();
+        }
+
+        // Tasks with start-after dependencies are left for the supervisor to
+        // start, once those dependencies are ready.
         let mut flags = abi::TaskFlags::empty();
-        if task.start {
+        if task.start && task.start_after.is_empty() {
             flags |= abi::TaskFlags::START_AT_BOOT;
         }
 
@@ -1881,10 +2338,41 @@ pub fn make_kconfig(
             initial_stack: task_allocations[name]["ram"].start
                 + task.stacksize.or(toml.stacksize).unwrap(),
             priority: task.priority,
+            timers: task.timers,
+            async_sends: task.async_sends,
             flags,
             index: u16::try_from(i).expect("more than 2**16 tasks?"),
         });
 
+        // IPC access control.
+        let grants = task.allowed_callers.as_ref().map(|callers| {
+            callers
+                .iter()
+                .map(|(caller, operations)| {
+                    let caller =
+                        toml.tasks.get_index_of(caller).ok_or_else(|| {
+                            anyhow!(
+                                "task {}: allowed caller {} is not a task",
+                                name,
+                                caller
+                            )
+                        })?;
+                    let operations = operations
+                        .as_ref()
+                        .map(|ops| task.operation_codes(app_dir, ops))
+                        .transpose()
+                        .with_context(|| {
+                            format!("task {}: allowed-callers", name)
+                        })?;
+                    Ok(IpcGrant {
+                        caller: caller as u16,
+                        operations,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        });
+        acls.push(grants.transpose()?);
+
         // Interrupts.
         for (irq_str, &notification) in &task.interrupts {
             // The irq_str can be either a base-ten number, or a reference to a
@@ -1976,6 +2464,8 @@ pub fn make_kconfig(
         irqs,
         tasks: task_descs,
         regions,
+        acls,
+        event_log: toml.kernel.event_log,
     })
 }
 
@@ -2376,3 +2866,256 @@ fn resolve_task_slots(
 
     Ok(std::fs::write(task_bin, out_task_bin)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+
+    fn range(start: u32, len: u32) -> Range<u32> {
+        start..start + len
+    }
+
+    #[test]
+    fn allocations_may_touch_but_not_overlap() {
+        let mut allocs = Allocations::default();
+        allocs
+            .kernel
+            .insert("ram".to_string(), range(0x2000_0000, 0x1000));
+        allocs.tasks.insert(
+            "net".to_string(),
+            [("ram".to_string(), range(0x2000_1000, 0x1000))]
+                .into_iter()
+                .collect(),
+        );
+        allocs
+            .shared
+            .insert("net_rx".to_string(), range(0x2000_2000, 0x800));
+        check_allocation_overlap(&allocs).unwrap();
+
+        allocs
+            .shared
+            .insert("net_tx".to_string(), range(0x2000_1800, 0x800));
+        let err = check_allocation_overlap(&allocs).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "task net ram (0x20001000..0x20002000) overlaps \
+             shared region net_tx (0x20001800..0x20002000)"
+        );
+    }
+
+    #[test]
+    fn tasks_are_limited_to_eight_regions() {
+        // The task's RAM and eight read-only shared regions need one region
+        // too many.
+        let mut body = r#"
+            [tasks.net]
+            name = "task-net"
+            priority = 0
+        "#
+        .to_string();
+        let mut shared = BTreeMap::new();
+        for i in 0..8 {
+            body += &format!(
+                "[shared-regions.buf{}]\n\
+                 memory = \"sram1\"\n\
+                 size = 0x100\n\
+                 read-only = [\"net\"]\n",
+                i
+            );
+            shared.insert(
+                format!("buf{}", i),
+                range(0x3000_0000 + i * 0x100, 0x100),
+            );
+        }
+        let toml = test_config(&body).unwrap();
+        let tasks = [(
+            "net".to_string(),
+            [("ram".to_string(), range(0x2000_1000, 0x1000))]
+                .into_iter()
+                .collect(),
+        )]
+        .into_iter()
+        .collect();
+
+        let err = make_kconfig(
+            &toml,
+            &tasks,
+            &shared,
+            &HashMap::new(),
+            "default",
+            &None,
+        )
+        .map(|_| ())
+        .unwrap_err()
+        .to_string();
+        assert_eq!(
+            err,
+            "task net uses 0 peripherals, 1 memories, and 8 shared regions \
+             (too many)"
+        );
+    }
+
+    fn segment(data: Vec<u8>) -> LoadSegment {
+        LoadSegment {
+            source_file: PathBuf::from("test"),
+            data,
+        }
+    }
+
+    #[test]
+    fn flash_contents_zero_fills_gaps() {
+        let mut sections = BTreeMap::new();
+        sections.insert(0x100, segment(vec![1, 2, 3, 4]));
+        sections.insert(0x106, segment(vec![5, 6]));
+
+        assert_eq!(
+            flash_contents(&sections, 0xfe..0x10a),
+            [0, 0, 1, 2, 3, 4, 0, 0, 5, 6, 0, 0]
+        );
+        // Segments that only partly overlap the range are clipped.
+        assert_eq!(flash_contents(&sections, 0x101..0x107), [2, 3, 4, 0, 0, 5]);
+        assert_eq!(flash_contents(&sections, 0x200..0x202), [0, 0]);
+    }
+
+    #[test]
+    fn image_header_carries_version_and_epoch() {
+        let body = r#"
+            version = 3
+            epoch = 2
+
+            [tasks.net]
+            name = "task-net"
+            priority = 0
+        "#;
+        let toml = test_config(body).unwrap();
+        let map = [("flash".to_string(), 0x1000..0x2000)]
+            .into_iter()
+            .collect();
+        let mut sections = BTreeMap::new();
+        sections.insert(0x1100, segment(vec![0xaa; 0x102]));
+
+        let header = make_image_header(&toml, &map, &sections, &None, 0x40);
+        assert_eq!(header.magic, abi::HEADER_MAGIC);
+        assert_eq!((header.version, header.epoch), (3, 2));
+        // The manifest goes at the next word after the image.
+        assert_eq!(header.manifest, 0x204);
+        assert_eq!(header.total_image_len, 0x244);
+    }
+
+    const FLASH: Range<u32> = 0x1000..0x2000;
+    const HEADER_ADDR: u32 = 0x1008;
+
+    /// Lays out an image like `dist` does: a kernel with a vector table and
+    /// header, and one task, with space for the manifest after them.
+    fn manifest_image() -> (BTreeMap<u32, LoadSegment>, Vec<Range<u32>>) {
+        let regions = vec![0x1000..0x1400, 0x1400..0x1800];
+        let manifest = 0x1510 - FLASH.start;
+        let header = abi::ImageHeader {
+            magic: abi::HEADER_MAGIC,
+            version: 5,
+            epoch: 1,
+            manifest,
+            total_image_len: manifest + abi::ManifestHeader::len(2),
+            ..Default::default()
+        };
+
+        let mut sections = BTreeMap::new();
+        sections.insert(0x1000, segment(vec![0xaa; 8]));
+        sections.insert(HEADER_ADDR, segment(header.as_bytes().to_vec()));
+        sections.insert(0x1200, segment(vec![0xbb; 0x40]));
+        sections.insert(0x1400, segment((0..0x100).map(|i| i as u8).collect()));
+        sections.insert(0x1500, segment(vec![0xcc; 0x10]));
+        (sections, regions)
+    }
+
+    /// Checks the image's manifest the way stage0 does, returning it if it's
+    /// good.
+    fn check_manifest(
+        sections: &BTreeMap<u32, LoadSegment>,
+    ) -> Option<Vec<u8>> {
+        let header_size = core::mem::size_of::<abi::ImageHeader>() as u32;
+        let header =
+            abi::ImageHeader::read_from(
+                &flash_contents(
+                    sections,
+                    HEADER_ADDR..HEADER_ADDR + header_size,
+                )[..],
+            )
+            .unwrap();
+        let image = flash_contents(
+            sections,
+            FLASH.start..FLASH.start + header.total_image_len,
+        );
+        let sha3 = |contents: &[u8]| {
+            let mut hash = [0; 32];
+            hash.copy_from_slice(&Sha3_256::digest(contents));
+            hash
+        };
+        abi::check_manifest(&image, FLASH.start, &header, sha3)
+            .map(<[u8]>::to_vec)
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let (mut sections, regions) = manifest_image();
+        let (addr, data) =
+            make_manifest(&sections, HEADER_ADDR, FLASH, &regions, [7; 32])
+                .unwrap();
+        assert_eq!(addr, 0x1510);
+
+        let manifest =
+            abi::ManifestHeader::read_from_prefix(&data[..]).unwrap();
+        assert_eq!((manifest.version, manifest.epoch), (5, 1));
+        assert_eq!(manifest.app_hash, [7; 32]);
+        assert_eq!(manifest.component_count, 2);
+        let components = &data[core::mem::size_of::<abi::ManifestHeader>()..];
+        let task = abi::ManifestComponent::read_from_prefix(
+            &components[core::mem::size_of::<abi::ManifestComponent>()..],
+        )
+        .unwrap();
+        // The task's component ends with its last segment.
+        assert_eq!((task.base, task.len), (0x1400, 0x110));
+
+        sections.insert(addr, segment(data.clone()));
+        assert_eq!(check_manifest(&sections), Some(data));
+    }
+
+    #[test]
+    fn manifest_detects_tampered_component() {
+        let (mut sections, regions) = manifest_image();
+        let (addr, data) =
+            make_manifest(&sections, HEADER_ADDR, FLASH, &regions, [0; 32])
+                .unwrap();
+        sections.insert(addr, segment(data));
+        sections.get_mut(&0x1400).unwrap().data[0x80] ^= 1;
+        assert_eq!(check_manifest(&sections), None);
+    }
+
+    #[test]
+    fn manifest_needs_space_left_by_header() {
+        let (mut sections, regions) = manifest_image();
+        sections.insert(0x1518, segment(vec![0; 4]));
+        let err =
+            make_manifest(&sections, HEADER_ADDR, FLASH, &regions, [0; 32])
+                .unwrap_err()
+                .to_string();
+        assert_eq!(err, "image manifest at 0x1510 overlaps 0x1518");
+
+        // A header that leaves room for a different number of components.
+        let (sections, regions) = manifest_image();
+        let err = make_manifest(
+            &sections,
+            HEADER_ADDR,
+            FLASH,
+            &regions[..1],
+            [0; 32],
+        )
+        .unwrap_err()
+        .to_string();
+        assert_eq!(
+            err,
+            "image header doesn't leave the right space for the manifest"
+        );
+    }
+}
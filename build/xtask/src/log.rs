@@ -0,0 +1,340 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use log_format::{
+    decode_args, Arg, Level, LogHeader, LogRecord, LOG_MAGIC, LOG_SECTION,
+};
+use serde::{Deserialize, Serialize};
+use zerocopy::FromBytes;
+
+use crate::config::Config;
+
+/// Name of the string table that `dist` writes into each image directory.
+pub const LOG_STRINGS_FILE: &str = "log-strings.json";
+
+/// A log call site, as interned into a task's ELF file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogString {
+    /// `file:line` of the call site.
+    pub location: String,
+    /// Its format string.
+    pub format: String,
+}
+
+/// String tables for every task in an image, keyed by task name and then by
+/// message id.
+pub type LogStrings = BTreeMap<String, BTreeMap<u32, LogString>>;
+
+/// Reads the interned log strings out of a linked task, checking that each
+/// call site passes as many arguments as its format string expects.
+pub fn extract_strings(task_elf: &Path) -> Result<BTreeMap<u32, LogString>> {
+    let bytes = std::fs::read(task_elf)?;
+    let elf = goblin::elf::Elf::parse(&bytes)?;
+    let mut strings = BTreeMap::new();
+
+    let section = elf
+        .section_headers
+        .iter()
+        .position(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(LOG_SECTION));
+    let section = match section {
+        Some(i) => i,
+        None => return Ok(strings),
+    };
+    let header = &elf.section_headers[section];
+    let data = &bytes[header.sh_offset as usize..][..header.sh_size as usize];
+
+    // Each entry has a symbol, which is more robust than walking the section
+    // and hoping the linker didn't pad anything.
+    for sym in elf.syms.iter() {
+        if sym.st_shndx != section || sym.st_size == 0 {
+            continue;
+        }
+        let start = (sym.st_value - header.sh_addr) as usize;
+        let entry = match data.get(start..start + sym.st_size as usize) {
+            Some(entry) => entry,
+            None => bail!("log string at {:#x} is out of bounds", sym.st_value),
+        };
+        let (string, nargs) = parse_entry(entry).with_context(|| {
+            format!("bad log string at {:#x}", sym.st_value)
+        })?;
+
+        let placeholders = parse_format(&string.format)
+            .with_context(|| format!("{}: bad log format", string.location))?
+            .iter()
+            .filter(|p| matches!(p, Piece::Arg(_)))
+            .count();
+        if placeholders != nargs {
+            bail!(
+                "{}: log format {:?} has {} placeholders, but {} arguments \
+                 were given",
+                string.location,
+                string.format,
+                placeholders,
+                nargs
+            );
+        }
+        strings.insert(sym.st_value as u32, string);
+    }
+    Ok(strings)
+}
+
+/// Splits an entry into its argument count, location and format string.
+fn parse_entry(entry: &[u8]) -> Result<(LogString, usize)> {
+    let (&nargs, rest) = match entry.split_first() {
+        Some(x) => x,
+        None => bail!("empty entry"),
+    };
+    let mut fields = rest.split(|&b| b == 0);
+    let mut next = || -> Result<String> {
+        match fields.next() {
+            Some(field) => Ok(std::str::from_utf8(field)?.to_string()),
+            None => bail!("truncated entry"),
+        }
+    };
+    let location = next()?;
+    let format = next()?;
+    Ok((LogString { location, format }, nargs as usize))
+}
+
+/// How to format an argument.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Style {
+    Display,
+    Debug,
+    LowerHex { alternate: bool },
+    UpperHex { alternate: bool },
+    Binary { alternate: bool },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Arg(Style),
+}
+
+/// Parses the subset of `format!` syntax that the logging macros support.
+fn parse_format(fmt: &str) -> Result<Vec<Piece>> {
+    let mut pieces = vec![];
+    let mut literal = String::new();
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let rest = chars.as_str();
+                let end = match rest.find('}') {
+                    Some(end) => end,
+                    None => bail!("unterminated placeholder in {:?}", fmt),
+                };
+                let style = match &rest[..end] {
+                    "" | ":" => Style::Display,
+                    ":?" => Style::Debug,
+                    ":x" => Style::LowerHex { alternate: false },
+                    ":#x" => Style::LowerHex { alternate: true },
+                    ":X" => Style::UpperHex { alternate: false },
+                    ":#X" => Style::UpperHex { alternate: true },
+                    ":b" => Style::Binary { alternate: false },
+                    ":#b" => Style::Binary { alternate: true },
+                    spec => bail!("unsupported placeholder {{{}}}", spec),
+                };
+                chars = rest[end + 1..].chars();
+                if !literal.is_empty() {
+                    pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(Piece::Arg(style));
+            }
+            '}' => bail!("unmatched `}}` in {:?}", fmt),
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+fn format_arg(arg: Arg, style: Style) -> String {
+    let arg = match (arg, style) {
+        (Arg::Signed(x), Style::Display | Style::Debug) => {
+            return x.to_string();
+        }
+        // As with `format!`, these show the two's complement.
+        (Arg::Signed(x), _) => Arg::Unsigned(x as u64),
+        _ => arg,
+    };
+    match (arg, style) {
+        (Arg::Unsigned(x), Style::LowerHex { alternate: false }) => {
+            format!("{:x}", x)
+        }
+        (Arg::Unsigned(x), Style::LowerHex { alternate: true }) => {
+            format!("{:#x}", x)
+        }
+        (Arg::Unsigned(x), Style::UpperHex { alternate: false }) => {
+            format!("{:X}", x)
+        }
+        (Arg::Unsigned(x), Style::UpperHex { alternate: true }) => {
+            format!("{:#X}", x)
+        }
+        (Arg::Unsigned(x), Style::Binary { alternate: false }) => {
+            format!("{:b}", x)
+        }
+        (Arg::Unsigned(x), Style::Binary { alternate: true }) => {
+            format!("{:#b}", x)
+        }
+        (Arg::Unsigned(x), _) => x.to_string(),
+        (Arg::Signed(x), _) => x.to_string(),
+        (Arg::Bool(x), _) => x.to_string(),
+        (Arg::Char(x), Style::Debug) => format!("{:?}", x),
+        (Arg::Char(x), _) => x.to_string(),
+        (Arg::Str(x), Style::Debug) => format!("{:?}", x),
+        (Arg::Str(x), _) => x.to_string(),
+        (Arg::Truncated, _) => "<truncated>".to_string(),
+        (Arg::Malformed, _) => "<malformed>".to_string(),
+    }
+}
+
+/// Puts a message back together from its format string and encoded
+/// arguments.
+fn format_message(fmt: &str, args: &[u8]) -> String {
+    let pieces = match parse_format(fmt) {
+        Ok(pieces) => pieces,
+        Err(_) => return fmt.to_string(),
+    };
+    let mut args = decode_args(args);
+    let mut out = String::new();
+    let mut missing = false;
+    for piece in pieces {
+        match piece {
+            Piece::Literal(s) => out.push_str(&s),
+            Piece::Arg(_) if missing => out.push('?'),
+            Piece::Arg(style) => match args.next() {
+                Some(arg) => {
+                    missing = matches!(arg, Arg::Truncated | Arg::Malformed);
+                    out.push_str(&format_arg(arg, style));
+                }
+                None => {
+                    missing = true;
+                    out.push('?');
+                }
+            },
+        }
+    }
+    out
+}
+
+/// Returns the path of the string table that `dist` wrote for an image.
+fn strings_path(toml: &Config, image_name: &str) -> PathBuf {
+    Path::new("target")
+        .join(&toml.name)
+        .join("dist")
+        .join(image_name)
+        .join(LOG_STRINGS_FILE)
+}
+
+/// Decodes a raw dump of the log task's `HUBRIS_LOG` buffer and prints the
+/// messages, oldest first, using the string table from the last `dist` of
+/// `cfg`.
+pub fn decode(
+    cfg: &Path,
+    dump: &Path,
+    image_name: Option<&str>,
+    verbose: bool,
+) -> Result<()> {
+    let toml = Config::from_file(cfg)?;
+    let image_name = match image_name {
+        Some(name) => {
+            if !toml.check_image_name(&name.to_string()) {
+                bail!("Image name {} not declared in TOML", name);
+            }
+            name
+        }
+        None => toml.image_names[0].as_str(),
+    };
+    let path = strings_path(&toml, image_name);
+    let json = std::fs::read(&path).with_context(|| {
+        format!("reading {}; has the image been built?", path.display())
+    })?;
+    let strings: LogStrings = serde_json::from_slice(&json)?;
+    let bytes = std::fs::read(dump)
+        .with_context(|| format!("reading {}", dump.display()))?;
+
+    let header = match LogHeader::read_from_prefix(&bytes[..]) {
+        Some(header) => header,
+        None => bail!("log dump is too short to contain a header"),
+    };
+    if header.magic != LOG_MAGIC {
+        bail!(
+            "bad log magic {:#x} (expected {:#x}); is the log task running?",
+            header.magic,
+            LOG_MAGIC
+        );
+    }
+
+    let records = &bytes[std::mem::size_of::<LogHeader>()..];
+    let capacity = header.capacity as usize;
+    let record_size = std::mem::size_of::<LogRecord>();
+    if records.len() < capacity * record_size {
+        bail!(
+            "log dump holds {} bytes of records, but the header claims {}",
+            records.len(),
+            capacity * record_size
+        );
+    }
+    let record = |i: usize| {
+        LogRecord::read_from(&records[i * record_size..][..record_size])
+            .unwrap()
+    };
+
+    let count = header.count as usize;
+    let (first, len) = if count > capacity {
+        (count % capacity, capacity)
+    } else {
+        (0, count)
+    };
+    if count > capacity {
+        println!("({} older messages were overwritten)", count - capacity);
+    }
+    if header.filtered != 0 {
+        println!("({} messages were filtered out)", header.filtered);
+    }
+
+    println!("{:>10} {:<16} {:<5} MESSAGE", "TIME", "TASK", "LEVEL");
+    for i in 0..len {
+        let r = record((first + i) % capacity);
+        let task = toml.tasks.keys().nth(r.task as usize);
+        let level = Level::from_u8(r.level).map_or("?", Level::name);
+        let args = &r.args[..(r.len as usize).min(r.args.len())];
+        let string = task
+            .and_then(|task| strings.get(task))
+            .and_then(|table| table.get(&r.id));
+        let message = match string {
+            Some(s) => format_message(&s.format, args),
+            None => format!("unknown message {:#x} {:x?}", r.id, args),
+        };
+        print!(
+            "{:>10} {:<16} {:<5} {}",
+            r.timestamp,
+            task.map_or("?", String::as_str),
+            level,
+            message
+        );
+        match string {
+            Some(s) if verbose => println!(" ({})", s.location),
+            _ => println!(),
+        }
+    }
+
+    Ok(())
+}
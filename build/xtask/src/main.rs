@@ -16,8 +16,11 @@ mod dist;
 mod elf;
 mod flash;
 mod humility;
+mod log;
+mod sign;
 mod sizes;
 mod task_slot;
+mod trace;
 
 #[derive(Debug, Parser)]
 #[clap(max_term_width = 80, about = "extra tasks to help you work on Hubris")]
@@ -145,6 +148,34 @@ enum Xtask {
         /// Path to task executable
         task_bin: PathBuf,
     },
+
+    /// Decode a dump of the kernel's RAM trace buffer (the
+    /// `HUBRIS_KERNEL_TRACE` symbol) into a timeline of kernel events.
+    Trace {
+        /// Path to the image configuration file, in TOML.
+        cfg: PathBuf,
+
+        /// Path to a raw binary dump of the trace buffer.
+        dump: PathBuf,
+    },
+
+    /// Decode a dump of the log task's buffer (the `HUBRIS_LOG` symbol) into
+    /// text, using the log strings from the last `dist` of the image.
+    Log {
+        /// Path to the image configuration file, in TOML.
+        cfg: PathBuf,
+
+        /// Path to a raw binary dump of the log buffer.
+        dump: PathBuf,
+
+        /// Image the dump was taken from, if not the first in the TOML.
+        #[clap(long)]
+        image_name: Option<String>,
+
+        /// Show where each message was logged.
+        #[clap(short, long)]
+        verbose: bool,
+    },
 }
 
 #[derive(Clone, Debug, Parser)]
@@ -299,6 +330,17 @@ fn run(xtask: Xtask) -> Result<()> {
         Xtask::TaskSlots { task_bin } => {
             task_slot::dump_task_slot_table(&task_bin)?;
         }
+        Xtask::Trace { cfg, dump } => {
+            trace::decode(&cfg, &dump)?;
+        }
+        Xtask::Log {
+            cfg,
+            dump,
+            image_name,
+            verbose,
+        } => {
+            log::decode(&cfg, &dump, image_name.as_deref(), verbose)?;
+        }
     }
 
     Ok(())
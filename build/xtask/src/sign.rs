@@ -0,0 +1,90 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Signing images for stage0.
+//!
+//! An app that stage0 boots is signed with the `image-key` from its
+//! `[signing]` section: `dist` appends an `abi::ImageSignature` trailer to the
+//! image. Stage0 itself is built from an app with `external-images`, and is
+//! given the SHA3-256 hash of the same key's public half, so that it only
+//! boots images with a trailer that it can verify.
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::pkcs8::FromPrivateKey;
+use sha3::{Digest, Sha3_256};
+use zerocopy::{AsBytes, FromBytes};
+
+/// Loads an image signing key, which is a P-256 private key in PKCS#8 DER
+/// form.
+pub fn load_key(path: &Path) -> Result<SigningKey> {
+    let der = std::fs::read(path)
+        .with_context(|| format!("reading image key {}", path.display()))?;
+    let secret = p256::SecretKey::from_pkcs8_der(&der).map_err(|e| {
+        anyhow!("{} is not a PKCS#8 P-256 key: {}", path.display(), e)
+    })?;
+    Ok(SigningKey::from(secret))
+}
+
+/// Returns a key's public half in the form stored in the trailer: the x and y
+/// coordinates, without a SEC1 tag.
+fn public_key(key: &SigningKey) -> [u8; 64] {
+    let point = key.verifying_key().to_encoded_point(false);
+    let mut out = [0; 64];
+    out.copy_from_slice(&point.as_bytes()[1..]);
+    out
+}
+
+/// Returns the hex-encoded SHA3-256 hash of a key's public half, for
+/// stage0's build.
+pub fn key_hash(key: &SigningKey) -> String {
+    Sha3_256::digest(public_key(key))
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Signs a flat image, which starts with the vector table and then the image
+/// header at `header_offset`, returning it with the signature trailer
+/// appended.
+pub fn sign_image(
+    image: &[u8],
+    header_offset: usize,
+    key: &SigningKey,
+) -> Result<Vec<u8>> {
+    let header = image
+        .get(header_offset..)
+        .and_then(abi::ImageHeader::read_from_prefix)
+        .ok_or_else(|| anyhow!("image is too short to contain a header"))?;
+    if header.magic != abi::HEADER_MAGIC {
+        bail!("bad image header magic {:#x}", header.magic);
+    }
+    let len = header.total_image_len as usize;
+    if len > image.len() {
+        bail!(
+            "image header claims {} bytes, but the image is {}",
+            len,
+            image.len()
+        );
+    }
+
+    let signature: Signature = key.sign(&image[..len]);
+    let mut trailer = abi::ImageSignature {
+        magic: abi::SIGNATURE_MAGIC,
+        public_key: public_key(key),
+        signature: [0; 64],
+    };
+    trailer.signature.copy_from_slice(signature.as_ref());
+
+    // Anything past the end of the image (there shouldn't be anything) is
+    // replaced by the trailer, which is padded to the next word as stage0
+    // expects.
+    let mut out = image[..len].to_vec();
+    out.resize(abi::ImageSignature::offset(len as u32) as usize, 0xff);
+    out.extend_from_slice(trailer.as_bytes());
+    Ok(out)
+}
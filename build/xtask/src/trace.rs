@@ -0,0 +1,237 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::Path;
+
+use abi::{Sysnum, TraceEvent, TraceHeader, TraceRecord, TRACE_MAGIC};
+use anyhow::{bail, Context, Result};
+use zerocopy::FromBytes;
+
+use crate::config::Config;
+
+/// Decodes a raw dump of the kernel's `HUBRIS_KERNEL_TRACE` buffer and prints
+/// the recorded events, oldest first, with context switches resolved to the
+/// names of the tasks in `cfg`.
+pub fn decode(cfg: &Path, dump: &Path) -> Result<()> {
+    let toml = Config::from_file(cfg)?;
+    let bytes = std::fs::read(dump)
+        .with_context(|| format!("reading {}", dump.display()))?;
+    let names: Vec<&str> = toml.tasks.keys().map(String::as_str).collect();
+
+    let trace = decode_bytes(&bytes, &names)?;
+    if trace.overwritten > 0 {
+        println!("({} older events were overwritten)", trace.overwritten);
+    }
+    println!("{:>10} {:>10} EVENT", "TIME", "DELTA");
+    for e in &trace.events {
+        println!("{:>10} {:>10} {}", e.timestamp, e.delta, e.what);
+    }
+
+    Ok(())
+}
+
+/// The contents of a trace dump.
+#[derive(Debug)]
+struct Trace {
+    /// Number of events lost to wraparound before the oldest in `events`.
+    overwritten: usize,
+    /// Events still in the buffer, oldest first.
+    events: Vec<Event>,
+}
+
+#[derive(Debug)]
+struct Event {
+    timestamp: u32,
+    /// Time since the oldest event in the trace.
+    delta: u32,
+    /// Human-readable description of the event.
+    what: String,
+}
+
+/// Decodes a trace buffer dump, given the names of the tasks in the image in
+/// task table order.
+fn decode_bytes(bytes: &[u8], task_names: &[&str]) -> Result<Trace> {
+    let header = match TraceHeader::read_from_prefix(bytes) {
+        Some(header) => header,
+        None => bail!("trace dump is too short to contain a header"),
+    };
+    if header.magic != TRACE_MAGIC {
+        bail!(
+            "bad trace magic {:#x} (expected {:#x}); was tracing configured?",
+            header.magic,
+            TRACE_MAGIC
+        );
+    }
+
+    let records = &bytes[std::mem::size_of::<TraceHeader>()..];
+    let capacity = header.capacity as usize;
+    let record_size = std::mem::size_of::<TraceRecord>();
+    if records.len() < capacity * record_size {
+        bail!(
+            "trace dump holds {} bytes of records, but the header claims {}",
+            records.len(),
+            capacity * record_size
+        );
+    }
+    let record = |i: usize| {
+        TraceRecord::read_from(&records[i * record_size..][..record_size])
+            .unwrap()
+    };
+
+    // Invert the `task_addr >> 4` encoding by computing the code for each
+    // task in the image, which are laid out in the task table in the same
+    // order as in the config.
+    let tasks: HashMap<u32, &str> = task_names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let addr =
+                header.task_table as usize + i * header.task_size as usize;
+            ((addr >> 4) as u32, *name)
+        })
+        .collect();
+
+    let count = header.count as usize;
+    let (first, len) = if count > capacity {
+        (count % capacity, capacity)
+    } else {
+        (0, count)
+    };
+
+    let mut start = None;
+    let events = (0..len)
+        .map(|i| {
+            let r = record((first + i) % capacity);
+            let start = *start.get_or_insert(r.timestamp);
+            let what = match TraceEvent::try_from(r.event) {
+                Ok(TraceEvent::SyscallEnter) => {
+                    format!("syscall enter: {}", syscall_name(r.arg))
+                }
+                Ok(TraceEvent::ContextSwitch) => match tasks.get(&r.arg) {
+                    Some(name) => format!("switch to {}", name),
+                    None => format!("switch to unknown task ({:#x})", r.arg),
+                },
+                Ok(event) => format!("{:?}", event),
+                Err(()) => format!("unknown event {}", r.event),
+            };
+            Event {
+                timestamp: r.timestamp,
+                delta: r.timestamp.wrapping_sub(start),
+                what,
+            }
+        })
+        .collect();
+
+    Ok(Trace {
+        overwritten: count.saturating_sub(capacity),
+        events,
+    })
+}
+
+fn syscall_name(nr: u32) -> String {
+    match Sysnum::try_from(nr) {
+        Ok(sysnum) => format!("{:?}", sysnum),
+        Err(()) => format!("unknown ({})", nr),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zerocopy::AsBytes;
+
+    const TASK_TABLE: u32 = 0x2000_0400;
+    const TASK_SIZE: u32 = 0x70;
+
+    /// Builds a dump of a buffer with room for `capacity` records, into which
+    /// `count` records have been written.
+    fn dump(capacity: u32, count: u32, records: &[TraceRecord]) -> Vec<u8> {
+        let header = TraceHeader {
+            magic: TRACE_MAGIC,
+            task_table: TASK_TABLE,
+            task_size: TASK_SIZE,
+            capacity,
+            count,
+        };
+        let mut bytes = header.as_bytes().to_vec();
+        for r in records {
+            bytes.extend_from_slice(r.as_bytes());
+        }
+        bytes
+    }
+
+    fn record(timestamp: u32, event: TraceEvent, arg: u32) -> TraceRecord {
+        TraceRecord {
+            timestamp,
+            event: event as u32,
+            arg,
+        }
+    }
+
+    fn what(trace: &Trace) -> Vec<&str> {
+        trace.events.iter().map(|e| e.what.as_str()).collect()
+    }
+
+    #[test]
+    fn syscall_names_come_from_sysnum() {
+        assert_eq!(syscall_name(Sysnum::Send as u32), "Send");
+        assert_eq!(syscall_name(Sysnum::CollectAsync as u32), "CollectAsync");
+        assert_eq!(syscall_name(999), "unknown (999)");
+    }
+
+    #[test]
+    fn decodes_events_in_order() {
+        let jefe = (TASK_TABLE + TASK_SIZE) >> 4;
+        let records = [
+            record(100, TraceEvent::SyscallEnter, Sysnum::Recv as u32),
+            record(103, TraceEvent::ContextSwitch, jefe),
+            record(110, TraceEvent::TimerIsrEnter, 0),
+            record(0, TraceEvent::SyscallExit, 0),
+        ];
+        let trace =
+            decode_bytes(&dump(4, 3, &records), &["idle", "jefe"]).unwrap();
+
+        assert_eq!(trace.overwritten, 0);
+        assert_eq!(
+            what(&trace),
+            ["syscall enter: Recv", "switch to jefe", "TimerIsrEnter"]
+        );
+        let deltas: Vec<u32> = trace.events.iter().map(|e| e.delta).collect();
+        assert_eq!(deltas, [0, 3, 10]);
+    }
+
+    #[test]
+    fn wrapped_buffer_starts_at_oldest() {
+        // Six records into a buffer of four: slots 0 and 1 hold the newest.
+        let records = [
+            record(5, TraceEvent::IsrEnter, 0),
+            record(6, TraceEvent::IsrExit, 0),
+            record(3, TraceEvent::SyscallEnter, 99),
+            record(4, TraceEvent::ContextSwitch, 0x1234),
+        ];
+        let trace = decode_bytes(&dump(4, 6, &records), &[]).unwrap();
+
+        assert_eq!(trace.overwritten, 2);
+        assert_eq!(
+            what(&trace),
+            [
+                "syscall enter: unknown (99)",
+                "switch to unknown task (0x1234)",
+                "IsrEnter",
+                "IsrExit",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_dumps() {
+        let mut bytes = dump(2, 0, &[TraceRecord::default(); 2]);
+        assert!(decode_bytes(&bytes[..8], &[]).is_err());
+        assert!(decode_bytes(&bytes[..bytes.len() - 1], &[]).is_err());
+        bytes[0] ^= 1;
+        assert!(decode_bytes(&bytes, &[]).is_err());
+    }
+}
@@ -322,7 +322,7 @@ fn main() -> ! {
     })
     .unwrap();
 
-    jefe.set_state(PowerState::A2 as u32);
+    jefe.set_state_to(PowerState::A2);
 
     ringbuf_entry!(Trace::ClockConfigSuccess);
     ringbuf_entry!(Trace::A2);
@@ -430,7 +430,7 @@ impl NotificationHandler for ServerImpl {
 impl ServerImpl {
     fn update_state_internal(&mut self, state: PowerState) {
         self.state = state;
-        self.jefe.set_state(state as u32);
+        self.jefe.set_state_to(state);
     }
 
     //
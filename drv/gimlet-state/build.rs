@@ -0,0 +1,46 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+fn main() -> Result<()> {
+    println!("cargo:rerun-if-changed=power-states.toml");
+    let text = std::fs::read_to_string("power-states.toml")
+        .context("reading power-states.toml")?;
+    let states: BTreeMap<String, u32> =
+        toml::from_str(&text).context("parsing power-states.toml")?;
+
+    let mut states: Vec<_> = states.into_iter().collect();
+    states.sort_by_key(|&(_, value)| value);
+    for pair in states.windows(2) {
+        if pair[0].1 == pair[1].1 {
+            bail!("{} and {} have the same value", pair[0].0, pair[1].0);
+        }
+    }
+
+    let out_dir = std::env::var("OUT_DIR")?;
+    let dest_path = std::path::Path::new(&out_dir).join("power_state.rs");
+    let mut out =
+        std::fs::File::create(&dest_path).context("creating power_state.rs")?;
+
+    writeln!(
+        out,
+        "#[derive(\n    \
+         Copy, Clone, Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, \
+         AsBytes,\n)]"
+    )?;
+    writeln!(out, "#[repr(u8)]")?;
+    writeln!(out, "pub enum PowerState {{")?;
+    for (name, value) in states {
+        if value > u8::MAX.into() {
+            bail!("{}: value {} doesn't fit in a u8", name, value);
+        }
+        writeln!(out, "    {} = {},", name, value)?;
+    }
+    writeln!(out, "}}")?;
+
+    Ok(())
+}
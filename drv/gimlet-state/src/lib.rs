@@ -7,17 +7,9 @@
 
 #![no_std]
 
-use userlib::FromPrimitive;
+use userlib::{FromPrimitive, ToPrimitive};
 use zerocopy::AsBytes;
 
-#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, AsBytes)]
-#[repr(u8)]
-pub enum PowerState {
-    A2 = 1,
-    A2PlusMono = 2,
-    A2PlusFans = 3,
-    A1 = 4,
-    A0 = 5,
-    A0PlusHP = 6,
-    A0Thermtrip = 7,
-}
+// `PowerState`, generated from `power-states.toml`, which is also where jefe
+// gets the names and values of the states it accepts.
+include!(concat!(env!("OUT_DIR"), "/power_state.rs"));
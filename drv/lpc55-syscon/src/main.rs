@@ -219,6 +219,13 @@ fn main() -> ! {
     // turning on PLLs and such
     syscon.hslspiclksel.modify(|_, w| w.sel().enum_0x2());
 
+    // The windowed watchdog counts the 1Mhz clock, undivided. Turning on its
+    // clock doesn't start it; the supervisor does that if it's configured to,
+    // but can't ask us for the clock since it outranks us.
+    syscon.wdtclkdiv.write(|w| unsafe { w.bits(0) });
+    while syscon.wdtclkdiv.read().reqflag().bit_is_set() {}
+    set_bit!(syscon.ahbclkctrl0, Peripheral::Wwdt.pmask());
+
     set_reset_reason();
 
     let mut server = ServerImpl { syscon };
@@ -9,7 +9,7 @@
 #![no_std]
 #![no_main]
 
-use drv_update_api::{UpdateError, UpdateTarget};
+use drv_update_api::{SlotStatus, UpdateError, UpdateTarget};
 use hypocalls::*;
 use idol_runtime::{ClientError, Leased, LenLimit, RequestError, R};
 use userlib::*;
@@ -123,14 +123,7 @@ impl idl::InOrderUpdateImpl for ServerImpl {
             )
         };
 
-        match result {
-            HypoStatus::Success => Ok(()),
-            HypoStatus::OutOfBounds => Err(UpdateError::OutOfBounds.into()),
-            HypoStatus::RunningImage => Err(UpdateError::RunningImage.into()),
-            // Should probably encode the LPC55 flash status into the update
-            // error for good measure but that takes effort...
-            HypoStatus::FlashError(_) => Err(UpdateError::FlashError.into()),
-        }
+        hypo_result(result)
     }
 
     fn finish_image_update(
@@ -158,6 +151,126 @@ impl idl::InOrderUpdateImpl for ServerImpl {
     ) -> Result<usize, RequestError<UpdateError>> {
         Ok(BLOCK_SIZE_BYTES)
     }
+
+    fn set_pending_boot_slot(
+        &mut self,
+        _: &RecvMessage,
+        image_type: UpdateTarget,
+    ) -> Result<(), RequestError<UpdateError>> {
+        if let UpdateState::InProgress = self.state {
+            return Err(UpdateError::UpdateInProgress.into());
+        }
+
+        let slot = boot_slot(image_type).ok_or(UpdateError::BadImageType)?;
+        if running_slot() == Some(slot) {
+            return Err(UpdateError::RunningImage.into());
+        }
+
+        // Stage0 would refuse to boot the image anyway, but it's better to
+        // find out now.
+        let header = read_image_header(image_type)?;
+        let state = read_boot_state()?;
+        if header.epoch < state.min_epoch {
+            return Err(UpdateError::EpochTooOld.into());
+        }
+
+        write_boot_state(&BootState {
+            preferred: slot as u32,
+            pending: 1,
+            attempts: 0,
+            ..state
+        })
+    }
+
+    fn confirm_image(
+        &mut self,
+        _: &RecvMessage,
+    ) -> Result<(), RequestError<UpdateError>> {
+        let slot = running_slot().ok_or(UpdateError::BadImageType)?;
+        let state = read_boot_state()?;
+        if state.is_valid()
+            && state.preferred == slot as u32
+            && state.pending == 0
+        {
+            return Ok(());
+        }
+
+        write_boot_state(&BootState {
+            preferred: slot as u32,
+            pending: 0,
+            attempts: 0,
+            ..state
+        })
+    }
+
+    fn slot_status(
+        &mut self,
+        _: &RecvMessage,
+        image_type: UpdateTarget,
+    ) -> Result<SlotStatus, RequestError<UpdateError>> {
+        let slot = boot_slot(image_type).ok_or(UpdateError::BadImageType)?;
+        let state = read_boot_state()?;
+        let mut status = SlotStatus::default();
+
+        match read_image_header(image_type) {
+            Ok(header) => {
+                status.version = header.version;
+                status.epoch = header.epoch;
+                status.flags |= SlotStatus::HAS_HEADER;
+                if header.epoch < state.min_epoch {
+                    status.flags |= SlotStatus::TOO_OLD;
+                }
+            }
+            Err(RequestError::Runtime(UpdateError::NoImage)) => (),
+            Err(e) => return Err(e),
+        }
+        if running_slot() == Some(slot) {
+            status.flags |= SlotStatus::RUNNING;
+        }
+        if state.is_valid() && state.preferred == slot as u32 {
+            status.flags |= SlotStatus::PREFERRED;
+            if state.pending != 0 {
+                status.flags |= SlotStatus::PENDING;
+                status.attempts = state.attempts;
+            }
+        }
+        Ok(status)
+    }
+}
+
+/// Reads the boot state. If there isn't a valid one, this returns a zeroed
+/// (and so invalid) state, which is also the right place to start from when
+/// writing the first one.
+fn read_boot_state() -> Result<BootState, RequestError<UpdateError>> {
+    let mut state = BootState::default();
+    hypo_result(tz_table!().read_boot_state(&mut state))?;
+    Ok(state)
+}
+
+fn write_boot_state(
+    state: &BootState,
+) -> Result<(), RequestError<UpdateError>> {
+    hypo_result(tz_table!().write_boot_state(state))
+}
+
+fn read_image_header(
+    image_type: UpdateTarget,
+) -> Result<ImageHeader, RequestError<UpdateError>> {
+    let mut header = ImageHeader::default();
+    hypo_result(tz_table!().read_image_header(image_type, &mut header))?;
+    Ok(header)
+}
+
+fn hypo_result(status: HypoStatus) -> Result<(), RequestError<UpdateError>> {
+    match status {
+        HypoStatus::Success => Ok(()),
+        HypoStatus::OutOfBounds => Err(UpdateError::OutOfBounds.into()),
+        HypoStatus::RunningImage => Err(UpdateError::RunningImage.into()),
+        HypoStatus::NoImage => Err(UpdateError::NoImage.into()),
+        // Should probably encode the LPC55 flash status into the update
+        // error for good measure but that takes effort...
+        HypoStatus::FlashError(_) => Err(UpdateError::FlashError.into()),
+    }
 }
 
 #[export_name = "main"]
@@ -174,7 +287,7 @@ fn main() -> ! {
 }
 
 mod idl {
-    use super::{UpdateError, UpdateTarget};
+    use super::{SlotStatus, UpdateError, UpdateTarget};
 
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
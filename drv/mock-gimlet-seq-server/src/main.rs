@@ -10,7 +10,7 @@
 use drv_gimlet_seq_api::{PowerState, SeqError};
 use idol_runtime::RequestError;
 use task_jefe_api::Jefe;
-use userlib::{FromPrimitive, RecvMessage, UnwrapLite};
+use userlib::{RecvMessage, UnwrapLite};
 
 userlib::task_slot!(JEFE, jefe);
 
@@ -38,11 +38,11 @@ impl ServerImpl {
     fn get_state_impl(&self) -> PowerState {
         // Only we should be setting the state, and we set it to A2 on startup;
         // this conversion should never fail.
-        PowerState::from_u32(self.jefe.get_state()).unwrap_lite()
+        self.jefe.get_state_as().unwrap_lite()
     }
 
     fn set_state_impl(&self, state: PowerState) {
-        self.jefe.set_state(state as u32);
+        self.jefe.set_state_to(state);
     }
 }
 
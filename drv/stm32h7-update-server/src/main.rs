@@ -10,7 +10,7 @@
 #![no_main]
 
 use drv_update_api::stm32h7::{BLOCK_SIZE_BYTES, FLASH_WORD_BYTES};
-use drv_update_api::{UpdateError, UpdateTarget};
+use drv_update_api::{SlotStatus, UpdateError, UpdateTarget};
 use idol_runtime::{ClientError, Leased, LenLimit, RequestError, R};
 use ringbuf::*;
 use stm32h7::stm32h753 as device;
@@ -88,6 +88,13 @@ impl<'a> ServerImpl<'a> {
         Ok(())
     }
 
+    /// Checks whether a bank swap has been programmed into the option bytes,
+    /// to take effect on the next reset.
+    fn swap_pending(&self) -> bool {
+        self.flash.optsr_prg().read().swap_bank_opt().bit()
+            != self.flash.optsr_cur().read().swap_bank_opt().bit()
+    }
+
     fn poll_flash_done(&mut self) -> Result<(), RequestError<UpdateError>> {
         // This method should implement step 5 of the Single Write Sequence from
         // RM0433 Rev 7 section 4.3.9, which states
@@ -378,6 +385,57 @@ impl idl::InOrderUpdateImpl for ServerImpl<'_> {
     ) -> Result<usize, RequestError<UpdateError>> {
         Ok(BLOCK_SIZE_BYTES)
     }
+
+    fn set_pending_boot_slot(
+        &mut self,
+        _: &RecvMessage,
+        img_type: UpdateTarget,
+    ) -> Result<(), RequestError<UpdateError>> {
+        if let UpdateState::InProgress = self.state {
+            return Err(UpdateError::UpdateInProgress.into());
+        }
+
+        match img_type {
+            UpdateTarget::Alternate => (),
+            _ => return Err(UpdateError::BadImageType.into()),
+        }
+
+        // There's no trial boot here: the banks are simply swapped on the
+        // next reset (which `finish_image_update` also does).
+        if !self.swap_pending() {
+            self.unlock();
+            self.swap_banks()?;
+        }
+        Ok(())
+    }
+
+    fn confirm_image(
+        &mut self,
+        _: &RecvMessage,
+    ) -> Result<(), RequestError<UpdateError>> {
+        // Nothing will roll back the image we're running, so there's nothing
+        // to confirm.
+        Ok(())
+    }
+
+    fn slot_status(
+        &mut self,
+        _: &RecvMessage,
+        img_type: UpdateTarget,
+    ) -> Result<SlotStatus, RequestError<UpdateError>> {
+        match img_type {
+            UpdateTarget::Alternate => (),
+            _ => return Err(UpdateError::BadImageType.into()),
+        }
+
+        // Images here don't have headers, so all we know is whether the
+        // alternate bank will be booted next.
+        let mut status = SlotStatus::default();
+        if self.swap_pending() {
+            status.flags |= SlotStatus::PREFERRED;
+        }
+        Ok(status)
+    }
 }
 
 #[export_name = "main"]
@@ -396,7 +454,7 @@ fn main() -> ! {
 }
 
 mod idl {
-    use super::{UpdateError, UpdateTarget};
+    use super::{SlotStatus, UpdateError, UpdateTarget};
 
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
@@ -6,7 +6,7 @@
 
 use derive_idol_err::IdolError;
 use userlib::{sys_send, FromPrimitive};
-use zerocopy::AsBytes;
+use zerocopy::{AsBytes, FromBytes};
 
 #[repr(u8)]
 #[derive(FromPrimitive, AsBytes, Eq, PartialEq, Clone, Copy)]
@@ -44,6 +44,37 @@ pub enum UpdateError {
     UpdateNotStarted = 16,
     RunningImage = 17,
     FlashError = 18,
+    NoImage = 19,
+    EpochTooOld = 20,
+}
+
+/// State of an image slot, as returned by `slot_status`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, AsBytes, FromBytes)]
+#[repr(C)]
+pub struct SlotStatus {
+    /// Version from the image's header, if it has one (see `HAS_HEADER`).
+    pub version: u32,
+    /// Epoch from the image's header, if it has one.
+    pub epoch: u32,
+    /// Number of times the boot loader has tried to boot the slot's image
+    /// while it was pending.
+    pub attempts: u32,
+    /// Any of the flags below.
+    pub flags: u32,
+}
+
+impl SlotStatus {
+    /// The slot holds an image with a header.
+    pub const HAS_HEADER: u32 = 1 << 0;
+    /// We're running from this slot.
+    pub const RUNNING: u32 = 1 << 1;
+    /// This slot will be booted next.
+    pub const PREFERRED: u32 = 1 << 2;
+    /// The slot's image hasn't been confirmed; unless it confirms itself,
+    /// the boot loader will give up on it after a few tries.
+    pub const PENDING: u32 = 1 << 3;
+    /// The image's epoch is older than the boot loader allows.
+    pub const TOO_OLD: u32 = 1 << 4;
 }
 
 pub mod stm32h7 {
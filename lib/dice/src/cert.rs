@@ -205,11 +205,15 @@ impl AliasCertBuilder {
     const FWID_LENGTH: usize =
         alias_cert_tmpl::FWID_RANGE.end - alias_cert_tmpl::FWID_RANGE.start;
 
+    /// The FWID and version go in the cert's DICE TcbInfo extension. The
+    /// FWID is the hash of the image manifest (see `abi::ManifestHeader`),
+    /// which verifiers can use to check individual components of the image.
     pub fn new(
         cert_sn: &CertSerialNumber,
         dname_sn: &SerialNumber,
         public_key: &PublicKey,
         fwid: &[u8; Self::FWID_LENGTH],
+        version: u32,
     ) -> Self {
         Self(alias_cert_tmpl::CERT_TMPL.clone())
             .set_serial_number(cert_sn)
@@ -217,12 +221,25 @@ impl AliasCertBuilder {
             .set_subject_sn(dname_sn)
             .set_pub(public_key.as_bytes())
             .set_fwid(fwid)
+            .set_version(version)
     }
 
     pub fn set_fwid(self, fwid: &[u8; Self::FWID_LENGTH]) -> Self {
         self.set_range(alias_cert_tmpl::FWID_RANGE, fwid)
     }
 
+    /// Sets the TcbInfo version, which is a UTF8String that we fill with the
+    /// version as 8 hex digits.
+    pub fn set_version(self, version: u32) -> Self {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+
+        let mut digits = [0u8; 8];
+        for (i, d) in digits.iter_mut().enumerate() {
+            *d = HEX[((version >> (28 - 4 * i)) & 0xf) as usize];
+        }
+        self.set_range(alias_cert_tmpl::VERSION_RANGE, &digits)
+    }
+
     const SIGNDATA_RANGE: Range<usize> = alias_cert_tmpl::SIGNDATA_RANGE;
 
     pub fn sign(self, keypair: &Keypair) -> AliasCert
@@ -257,6 +274,10 @@ impl AliasCert {
     pub fn get_fwid(&self) -> &[u8] {
         self.get_range(alias_cert_tmpl::FWID_RANGE)
     }
+
+    pub fn get_version(&self) -> &[u8] {
+        self.get_range(alias_cert_tmpl::VERSION_RANGE)
+    }
 }
 
 impl Cert for AliasCert {
@@ -465,6 +486,14 @@ mod tests {
         assert_eq!(cert.get_subject_sn().as_bytes(), sn.as_bytes());
     }
 
+    #[test]
+    fn alias_version() {
+        let cert = AliasCertBuilder(alias_cert_tmpl::CERT_TMPL)
+            .set_version(0x0102_abcd);
+
+        assert_eq!(&cert.0[alias_cert_tmpl::VERSION_RANGE], b"0102abcd");
+    }
+
     // Signature over CERT with issuer / subject SN & PUBKEY set according
     // to 'sign' test below.
     const SIG_EXPECTED: [u8; SIGNATURE_SERIALIZED_LENGTH] = [
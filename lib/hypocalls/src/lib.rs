@@ -7,8 +7,11 @@
 
 //! Hypovisor calls
 
+pub use abi::{BootState, ImageHeader};
 pub use lpc55_flash::{
-    HypoStatus, UpdateTarget, __write_block, FLASH_PAGE_SIZE,
+    __ns_read_boot_state, __ns_read_image_header, __ns_write_boot_state,
+    __read_boot_state, __read_image_header, __write_block, __write_boot_state,
+    boot_slot, running_slot, HypoStatus, UpdateTarget, FLASH_PAGE_SIZE,
 };
 
 pub const TABLE_MAGIC: u32 = 0xabcd_abcd;
@@ -22,6 +25,9 @@ macro_rules! declare_tz_table {
         static TZ_TABLE: SecureTable = SecureTable {
             magic: 0,
             write_to_flash: None,
+            read_boot_state: None,
+            write_boot_state: None,
+            read_image_header: None,
         };
     };
 }
@@ -35,6 +41,9 @@ macro_rules! declare_not_tz_table {
         static TZ_TABLE: SecureTable = SecureTable {
             magic: TABLE_MAGIC,
             write_to_flash: Some(__write_block),
+            read_boot_state: Some(__read_boot_state),
+            write_boot_state: Some(__write_boot_state),
+            read_image_header: Some(__read_image_header),
         };
     };
 }
@@ -54,6 +63,13 @@ pub struct SecureTable {
     // function
     pub write_to_flash:
         Option<unsafe extern "C" fn(UpdateTarget, u32, *mut u8) -> HypoStatus>,
+    pub read_boot_state:
+        Option<unsafe extern "C" fn(*mut BootState) -> HypoStatus>,
+    pub write_boot_state:
+        Option<unsafe extern "C" fn(*const BootState) -> HypoStatus>,
+    pub read_image_header: Option<
+        unsafe extern "C" fn(UpdateTarget, *mut ImageHeader) -> HypoStatus,
+    >,
 }
 
 impl SecureTable {
@@ -84,4 +100,57 @@ impl SecureTable {
         }
         unreachable!()
     }
+
+    // The rest of these are like `write_to_flash`, except that they take
+    // references, since the secure side only reads or writes them during the
+    // call.
+
+    pub fn read_boot_state(&self, state: &mut BootState) -> HypoStatus {
+        self.check_magic();
+        // SAFETY: See `write_to_flash`.
+        unsafe {
+            if let Some(func) = core::ptr::read_volatile(&self.read_boot_state)
+            {
+                return func(state);
+            }
+        }
+        unreachable!()
+    }
+
+    pub fn write_boot_state(&self, state: &BootState) -> HypoStatus {
+        self.check_magic();
+        // SAFETY: See `write_to_flash`.
+        unsafe {
+            if let Some(func) = core::ptr::read_volatile(&self.write_boot_state)
+            {
+                return func(state);
+            }
+        }
+        unreachable!()
+    }
+
+    pub fn read_image_header(
+        &self,
+        img: UpdateTarget,
+        header: &mut ImageHeader,
+    ) -> HypoStatus {
+        self.check_magic();
+        // SAFETY: See `write_to_flash`.
+        unsafe {
+            if let Some(func) =
+                core::ptr::read_volatile(&self.read_image_header)
+            {
+                return func(img, header);
+            }
+        }
+        unreachable!()
+    }
+
+    fn check_magic(&self) {
+        // SAFETY: See `write_to_flash`.
+        let magic = unsafe { core::ptr::read_volatile(&self.magic) };
+        if magic != TABLE_MAGIC {
+            panic!();
+        }
+    }
 }
@@ -0,0 +1,418 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Binary format of log messages, shared by the log task, its clients, and
+//! the host tools that decode them.
+//!
+//! Log messages are never formatted on the target. Instead, the logging macros
+//! in `task-log-api` intern each call site's format string into the
+//! [`LOG_SECTION`] section of the task's ELF file, which isn't loaded into
+//! flash, and send the log task the string's address in that section (its
+//! _id_) along with the arguments, each encoded as a [`Tag`] followed by its
+//! value. The log task stamps that with the time, the sending task, and the
+//! level, and keeps it as a [`LogRecord`] in a circular buffer in RAM.
+//!
+//! `cargo xtask dist` extracts the interned strings from each task into a
+//! table, which `cargo xtask log` uses to turn a dump of the buffer back into
+//! text.
+
+#![cfg_attr(not(test), no_std)]
+
+use zerocopy::{AsBytes, FromBytes};
+
+/// Name of the ELF section holding interned log strings.
+///
+/// Each entry in the section is the number of arguments the call site passes,
+/// as a byte, followed by two NUL-terminated strings: the call site's
+/// `file:line`, and its format string.
+pub const LOG_SECTION: &str = ".hubris_log";
+
+/// Magic number at the start of the log buffer, used by host tools to check
+/// that they've found (and correctly dumped) the right memory.
+pub const LOG_MAGIC: u32 = 0x106b_0ff1;
+
+/// Space for encoded arguments in each record. Arguments that don't fit are
+/// dropped, and replaced with [`Tag::Truncated`].
+pub const ARGS_LEN: usize = 32;
+
+/// Header of the log task's buffer (the `HUBRIS_LOG` symbol), which is
+/// followed in memory by `capacity` `LogRecord`s.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug, FromBytes, AsBytes)]
+pub struct LogHeader {
+    /// Set to `LOG_MAGIC` once the log task has started.
+    pub magic: u32,
+    /// Number of records following the header.
+    pub capacity: u32,
+    /// Total number of records ever written. The most recent record is at
+    /// index `(count - 1) % capacity`; once `count` exceeds `capacity`, the
+    /// oldest records have been overwritten.
+    pub count: u32,
+    /// Number of messages dropped because of their task's level.
+    pub filtered: u32,
+}
+
+/// A single message in the log task's buffer.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug, FromBytes, AsBytes)]
+pub struct LogRecord {
+    /// Kernel timestamp, in ticks since boot (see `sys_get_timer`).
+    pub timestamp: u64,
+    /// Address of the message's interned strings in the sending task's
+    /// `LOG_SECTION`.
+    pub id: u32,
+    /// Index of the sending task.
+    pub task: u16,
+    /// The message's `Level`.
+    pub level: u8,
+    /// Number of valid bytes in `args`.
+    pub len: u8,
+    /// Encoded arguments.
+    pub args: [u8; ARGS_LEN],
+}
+
+/// Message levels, from most to least important.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl Level {
+    pub fn from_u8(x: u8) -> Option<Self> {
+        match x {
+            1 => Some(Self::Error),
+            2 => Some(Self::Warn),
+            3 => Some(Self::Info),
+            4 => Some(Self::Debug),
+            5 => Some(Self::Trace),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+            Self::Trace => "TRACE",
+        }
+    }
+}
+
+/// Type tags for encoded arguments. Integers follow in little-endian order, a
+/// `Char` as a `u32`, and a `Str` as a length byte and that many bytes of
+/// UTF-8.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Tag {
+    U8 = 1,
+    U16 = 2,
+    U32 = 3,
+    U64 = 4,
+    I8 = 5,
+    I16 = 6,
+    I32 = 7,
+    I64 = 8,
+    Bool = 9,
+    Char = 10,
+    Str = 11,
+    /// The remaining arguments didn't fit in the record.
+    Truncated = 0xff,
+}
+
+impl Tag {
+    pub fn from_u8(x: u8) -> Option<Self> {
+        match x {
+            1 => Some(Self::U8),
+            2 => Some(Self::U16),
+            3 => Some(Self::U32),
+            4 => Some(Self::U64),
+            5 => Some(Self::I8),
+            6 => Some(Self::I16),
+            7 => Some(Self::I32),
+            8 => Some(Self::I64),
+            9 => Some(Self::Bool),
+            10 => Some(Self::Char),
+            11 => Some(Self::Str),
+            0xff => Some(Self::Truncated),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes arguments into a buffer, which is normally `ARGS_LEN` bytes.
+pub struct Encoder<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    truncated: bool,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf,
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Space for arguments, keeping a byte for `Tag::Truncated`.
+    fn room(&self) -> usize {
+        self.buf.len().saturating_sub(self.len + 1)
+    }
+
+    /// Appends an argument, unless it doesn't fit or an earlier one didn't.
+    pub fn put(&mut self, tag: Tag, value: &[u8]) {
+        if self.truncated || 1 + value.len() > self.room() {
+            self.truncated = true;
+            return;
+        }
+        self.buf[self.len] = tag as u8;
+        self.buf[self.len + 1..][..value.len()].copy_from_slice(value);
+        self.len += 1 + value.len();
+    }
+
+    /// Appends a string argument, shortening it if it doesn't all fit.
+    pub fn put_str(&mut self, s: &str) {
+        if self.truncated || self.room() < 2 {
+            self.truncated = true;
+            return;
+        }
+        let mut n = s.len().min(self.room() - 2).min(usize::from(u8::MAX));
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len] = Tag::Str as u8;
+        self.buf[self.len + 1] = n as u8;
+        self.buf[self.len + 2..][..n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += 2 + n;
+        if n < s.len() {
+            self.truncated = true;
+        }
+    }
+
+    /// Finishes encoding, returning the number of bytes used.
+    pub fn finish(self) -> usize {
+        if self.truncated && self.len < self.buf.len() {
+            self.buf[self.len] = Tag::Truncated as u8;
+            self.len + 1
+        } else {
+            self.len
+        }
+    }
+}
+
+/// Types that can be passed to the logging macros.
+pub trait LogArg {
+    fn encode(&self, e: &mut Encoder<'_>);
+}
+
+macro_rules! impl_log_arg {
+    ($($t:ty => $tag:ident),* $(,)?) => {
+        $(
+            impl LogArg for $t {
+                fn encode(&self, e: &mut Encoder<'_>) {
+                    e.put(Tag::$tag, &self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_log_arg! {
+    u8 => U8, u16 => U16, u32 => U32, u64 => U64,
+    i8 => I8, i16 => I16, i32 => I32, i64 => I64,
+}
+
+impl LogArg for usize {
+    fn encode(&self, e: &mut Encoder<'_>) {
+        match u32::try_from(*self) {
+            Ok(x) => x.encode(e),
+            Err(_) => (*self as u64).encode(e),
+        }
+    }
+}
+
+impl LogArg for isize {
+    fn encode(&self, e: &mut Encoder<'_>) {
+        match i32::try_from(*self) {
+            Ok(x) => x.encode(e),
+            Err(_) => (*self as i64).encode(e),
+        }
+    }
+}
+
+impl LogArg for bool {
+    fn encode(&self, e: &mut Encoder<'_>) {
+        e.put(Tag::Bool, &[u8::from(*self)]);
+    }
+}
+
+impl LogArg for char {
+    fn encode(&self, e: &mut Encoder<'_>) {
+        e.put(Tag::Char, &u32::from(*self).to_le_bytes());
+    }
+}
+
+impl LogArg for str {
+    fn encode(&self, e: &mut Encoder<'_>) {
+        e.put_str(self);
+    }
+}
+
+impl<T: LogArg + ?Sized> LogArg for &T {
+    fn encode(&self, e: &mut Encoder<'_>) {
+        (**self).encode(e);
+    }
+}
+
+/// A decoded argument.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Arg<'a> {
+    Unsigned(u64),
+    Signed(i64),
+    Bool(bool),
+    Char(char),
+    Str(&'a str),
+    /// The remaining arguments didn't fit in the record.
+    Truncated,
+    /// The remaining bytes couldn't be decoded.
+    Malformed,
+}
+
+/// Decodes the arguments in `bytes`, which must be the valid part of a
+/// record's `args`.
+pub fn decode_args(bytes: &[u8]) -> Args<'_> {
+    Args { bytes }
+}
+
+pub struct Args<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Args<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < n {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Some(head)
+    }
+
+    fn unsigned(&mut self, n: usize) -> Option<u64> {
+        let mut le = [0; 8];
+        le[..n].copy_from_slice(self.take(n)?);
+        Some(u64::from_le_bytes(le))
+    }
+
+    fn signed(&mut self, n: usize) -> Option<i64> {
+        let x = self.unsigned(n)?;
+        let shift = 64 - 8 * n as u32;
+        Some(((x << shift) as i64) >> shift)
+    }
+
+    fn next_arg(&mut self) -> Option<Arg<'a>> {
+        let tag = Tag::from_u8(self.take(1)?[0])?;
+        Some(match tag {
+            Tag::U8 => Arg::Unsigned(self.unsigned(1)?),
+            Tag::U16 => Arg::Unsigned(self.unsigned(2)?),
+            Tag::U32 => Arg::Unsigned(self.unsigned(4)?),
+            Tag::U64 => Arg::Unsigned(self.unsigned(8)?),
+            Tag::I8 => Arg::Signed(self.signed(1)?),
+            Tag::I16 => Arg::Signed(self.signed(2)?),
+            Tag::I32 => Arg::Signed(self.signed(4)?),
+            Tag::I64 => Arg::Signed(self.signed(8)?),
+            Tag::Bool => Arg::Bool(self.take(1)?[0] != 0),
+            Tag::Char => Arg::Char(char::from_u32(self.unsigned(4)? as u32)?),
+            Tag::Str => {
+                let n = self.take(1)?[0];
+                Arg::Str(core::str::from_utf8(self.take(n.into())?).ok()?)
+            }
+            Tag::Truncated => Arg::Truncated,
+        })
+    }
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = Arg<'a>;
+
+    fn next(&mut self) -> Option<Arg<'a>> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        let arg = self.next_arg().unwrap_or(Arg::Malformed);
+        if let Arg::Truncated | Arg::Malformed = arg {
+            self.bytes = &[];
+        }
+        Some(arg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(args: &[&dyn LogArg]) -> ([u8; ARGS_LEN], usize) {
+        let mut buf = [0; ARGS_LEN];
+        let mut e = Encoder::new(&mut buf);
+        for arg in args {
+            arg.encode(&mut e);
+        }
+        let len = e.finish();
+        (buf, len)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (buf, len) = encode(&[&7u8, &-2i16, &0xdead_beefu32, &true, &'ü']);
+        let args: Vec<_> = decode_args(&buf[..len]).collect();
+        assert_eq!(
+            args,
+            [
+                Arg::Unsigned(7),
+                Arg::Signed(-2),
+                Arg::Unsigned(0xdead_beef),
+                Arg::Bool(true),
+                Arg::Char('ü'),
+            ]
+        );
+    }
+
+    #[test]
+    fn truncation() {
+        let (buf, len) = encode(&[&1u64, &2u64, &3u64, &4u64, &5u8]);
+        assert_eq!(len, 3 * 9 + 1);
+        let args: Vec<_> = decode_args(&buf[..len]).collect();
+        assert_eq!(
+            args,
+            [
+                Arg::Unsigned(1),
+                Arg::Unsigned(2),
+                Arg::Unsigned(3),
+                Arg::Truncated,
+            ]
+        );
+
+        let (buf, len) = encode(&[&"a fairly long string that won't fit"]);
+        assert_eq!(len, ARGS_LEN);
+        let args: Vec<_> = decode_args(&buf[..len]).collect();
+        assert_eq!(
+            args,
+            [Arg::Str("a fairly long string that won"), Arg::Truncated]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let args: Vec<_> = decode_args(&[Tag::U32 as u8, 1, 2]).collect();
+        assert_eq!(args, [Arg::Malformed]);
+    }
+}
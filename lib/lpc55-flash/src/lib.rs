@@ -3,7 +3,10 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 #![no_std]
+use abi::{BootSlot, BootState, ImageHeader};
+use cortex_m::cmse::{AccessType, TestTarget};
 use lpc55_romapi::*;
+use zerocopy::{AsBytes, FromBytes};
 
 pub use drv_update_api::UpdateTarget;
 pub use lpc55_romapi::FLASH_PAGE_SIZE;
@@ -15,6 +18,7 @@ pub enum HypoStatus {
     RunningImage,
     OutOfBounds,
     FlashError(FlashStatus),
+    NoImage,
 }
 
 // All these symbols are defined with no space allocated. This is best
@@ -32,6 +36,9 @@ extern "C" {
     static __IMAGE_STAGE0_BASE: ();
     static __IMAGE_STAGE0_END: ();
 
+    // Persistent boot state for stage0; see `abi::BootState`.
+    static __IMAGE_BOOTSTATE_BASE: ();
+
     // This references the base of the currently running image
     static __this_image: ();
 }
@@ -78,6 +85,12 @@ macro_rules! image_stage0_end {
     };
 }
 
+macro_rules! bootstate_base {
+    () => {
+        core::ptr::addr_of!(__IMAGE_BOOTSTATE_BASE) as u32
+    };
+}
+
 fn get_base(which: UpdateTarget) -> u32 {
     match which {
         UpdateTarget::ImageA => unsafe { image_a_base!() },
@@ -100,6 +113,24 @@ fn same_image(which: UpdateTarget) -> bool {
     get_base(which) == unsafe { this_image!() }
 }
 
+/// Returns the boot slot that holds the given target, if it is one.
+pub fn boot_slot(which: UpdateTarget) -> Option<BootSlot> {
+    match which {
+        UpdateTarget::ImageA => Some(BootSlot::A),
+        UpdateTarget::ImageB => Some(BootSlot::B),
+        _ => None,
+    }
+}
+
+/// Returns the boot slot that we're running from.
+pub fn running_slot() -> Option<BootSlot> {
+    [UpdateTarget::ImageA, UpdateTarget::ImageB]
+        .iter()
+        .copied()
+        .find(|&t| same_image(t))
+        .and_then(boot_slot)
+}
+
 fn target_addr(
     image_target: UpdateTarget,
     page_num: u32,
@@ -156,3 +187,179 @@ pub unsafe extern "C" fn __write_block(
 
     HypoStatus::Success
 }
+
+/// Checks that a `T` at `ptr`, which the non-secure world gave us, is aligned
+/// and lies entirely within memory that non-secure, unprivileged code can
+/// access itself: readable, or writable too if `write` is set. Otherwise a
+/// caller could have us read or write secure memory on its behalf.
+///
+/// This is only meaningful for calls that come from the non-secure world,
+/// through the NSC veneers in `task/secure` (see the `__ns_*` functions).
+/// Without TrustZone, the caller's memory is all secure, and it calls the
+/// unchecked functions directly.
+///
+/// This tests both ends of the range with TTAT, and rejects a range whose ends
+/// are in different SAU, IDAU or MPU regions, since something else could lie
+/// between them.
+fn nonsecure_accessible<T>(ptr: *const T, write: bool) -> bool {
+    if ptr as usize % core::mem::align_of::<T>() != 0 {
+        return false;
+    }
+    let target = TestTarget::check_range(
+        ptr as *mut u32,
+        core::mem::size_of::<T>(),
+        AccessType::NonSecureUnprivileged,
+    );
+    match target {
+        Some(t) if write => t.ns_read_and_writable(),
+        Some(t) => t.ns_readable(),
+        None => false,
+    }
+}
+
+fn read_boot_state_page(page: u32) -> Option<BootState> {
+    let addr = unsafe { bootstate_base!() } + page * FLASH_PAGE_SIZE as u32;
+
+    // Reading an erased page would fault.
+    if !validate_programmed(addr, FLASH_PAGE_SIZE as u32) {
+        return None;
+    }
+
+    // SAFETY: We've validated that the page is programmed, and it's in the
+    // region that the linker script gave us.
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            addr as *const u8,
+            core::mem::size_of::<BootState>(),
+        )
+    };
+    BootState::read_from(bytes).filter(BootState::is_valid)
+}
+
+/// Reads the newest valid copy of the boot state, and returns it along with
+/// the page the next copy should go to.
+fn read_boot_state() -> (Option<BootState>, u32) {
+    let (state, next) =
+        BootState::newest([read_boot_state_page(0), read_boot_state_page(1)]);
+    (state, next as u32)
+}
+
+/// Reads the boot state into `out`. If there is no valid boot state (which is
+/// the case until an image is first marked pending or confirmed), `out` is
+/// zeroed, and so won't be valid either.
+#[no_mangle]
+pub unsafe extern "C" fn __read_boot_state(out: *mut BootState) -> HypoStatus {
+    *out = read_boot_state().0.unwrap_or_default();
+    HypoStatus::Success
+}
+
+/// Like `__read_boot_state`, for the non-secure world: `out` must be memory
+/// that the caller could write itself.
+#[no_mangle]
+pub unsafe extern "C" fn __ns_read_boot_state(
+    out: *mut BootState,
+) -> HypoStatus {
+    if !nonsecure_accessible(out, true) {
+        return HypoStatus::OutOfBounds;
+    }
+    __read_boot_state(out)
+}
+
+/// Writes a new copy of the boot state. The minimum epoch can't be lowered:
+/// it's kept at the current minimum if `state` has a lower one.
+#[no_mangle]
+pub unsafe extern "C" fn __write_boot_state(
+    state: *const BootState,
+) -> HypoStatus {
+    let mut state = *state;
+    let (current, page) = read_boot_state();
+    if let Some(current) = current {
+        state.sequence = current.sequence;
+        state.min_epoch = state.min_epoch.max(current.min_epoch);
+    }
+    let state = state.next();
+    if !state.is_valid() {
+        return HypoStatus::OutOfBounds;
+    }
+
+    let mut buf = [0xff; FLASH_PAGE_SIZE];
+    buf[..core::mem::size_of::<BootState>()].copy_from_slice(state.as_bytes());
+
+    let write_addr = bootstate_base!() + page * FLASH_PAGE_SIZE as u32;
+
+    if let Err(result) = flash_erase(write_addr, FLASH_PAGE_SIZE as u32) {
+        return HypoStatus::FlashError(result);
+    }
+
+    if let Err(result) =
+        flash_write(write_addr, buf.as_mut_ptr(), FLASH_PAGE_SIZE as u32)
+    {
+        return HypoStatus::FlashError(result);
+    }
+
+    HypoStatus::Success
+}
+
+/// Like `__write_boot_state`, for the non-secure world: `state` must be memory
+/// that the caller could read itself.
+#[no_mangle]
+pub unsafe extern "C" fn __ns_write_boot_state(
+    state: *const BootState,
+) -> HypoStatus {
+    if !nonsecure_accessible(state, false) {
+        return HypoStatus::OutOfBounds;
+    }
+    __write_boot_state(state)
+}
+
+/// Reads the header of the image in `image_num` into `out`, returning
+/// `NoImage` if there isn't one.
+#[no_mangle]
+pub unsafe extern "C" fn __read_image_header(
+    image_num: UpdateTarget,
+    out: *mut ImageHeader,
+) -> HypoStatus {
+    if boot_slot(image_num).is_none() {
+        return HypoStatus::OutOfBounds;
+    }
+    let base = get_base(image_num);
+
+    // Reading an erased page would fault.
+    if !validate_programmed(base, FLASH_PAGE_SIZE as u32) {
+        return HypoStatus::NoImage;
+    }
+
+    // SAFETY: We've validated that the page is programmed, and it's within the
+    // image that the linker script gave us.
+    let page = core::slice::from_raw_parts(base as *const u8, FLASH_PAGE_SIZE);
+
+    // The header immediately follows the vector table, whose size only the
+    // kernel's linker script knows, so we look for it. Vector table entries
+    // are flash addresses or zero, so they can't be mistaken for the magic.
+    let size = core::mem::size_of::<ImageHeader>();
+    let header = (8..FLASH_PAGE_SIZE - size)
+        .step_by(4)
+        .filter_map(|i| ImageHeader::read_from(&page[i..i + size]))
+        .find(|h| h.magic == abi::HEADER_MAGIC);
+
+    match header {
+        Some(header) => {
+            *out = header;
+            HypoStatus::Success
+        }
+        None => HypoStatus::NoImage,
+    }
+}
+
+/// Like `__read_image_header`, for the non-secure world: `out` must be memory
+/// that the caller could write itself.
+#[no_mangle]
+pub unsafe extern "C" fn __ns_read_image_header(
+    image_num: UpdateTarget,
+    out: *mut ImageHeader,
+) -> HypoStatus {
+    if !nonsecure_accessible(out, true) {
+        return HypoStatus::OutOfBounds;
+    }
+    __read_image_header(image_num, out)
+}
@@ -25,5 +25,26 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         image_id
     )?;
 
+    // We only boot images signed with the key whose SHA3-256 hash `xtask`
+    // gives us here, from the app's `signing.image-key`.
+    let key_hash = env::var("HUBRIS_IMAGE_KEY_HASH").map_err(|_| {
+        "HUBRIS_IMAGE_KEY_HASH is not set; stage0 needs an `image-key` in \
+         the app's [signing] section"
+    })?;
+    println!("cargo:rerun-if-env-changed=HUBRIS_IMAGE_KEY_HASH");
+    if key_hash.len() != 64 {
+        return Err("HUBRIS_IMAGE_KEY_HASH must be 32 hex-encoded bytes".into());
+    }
+    let key_hash = (0..key_hash.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&key_hash[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    writeln!(
+        const_file,
+        "pub const IMAGE_KEY_HASH: [u8; 32] = {:?};",
+        key_hash
+    )?;
+
     Ok(())
 }
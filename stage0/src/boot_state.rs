@@ -0,0 +1,88 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Persistent boot state, kept in the `bootstate` flash region as two copies
+//! in separate pages. See `abi::BootState` for how it's used.
+
+use abi::BootState;
+use lpc55_romapi::FLASH_PAGE_SIZE;
+use zerocopy::{AsBytes, FromBytes};
+
+extern "C" {
+    // As with `__vector_size`, this is a symbol with no space allocated, which
+    // we only take the address of.
+    #[allow(improper_ctypes)]
+    static __IMAGE_BOOTSTATE_BASE: ();
+}
+
+const PAGE_SIZE: u32 = FLASH_PAGE_SIZE as u32;
+
+fn page_addr(page: u32) -> u32 {
+    // SAFETY: This is generated by the linker script, which we trust.
+    let base = unsafe { core::ptr::addr_of!(__IMAGE_BOOTSTATE_BASE) as u32 };
+    base + page * PAGE_SIZE
+}
+
+fn read_page(page: u32) -> Option<BootState> {
+    let addr = page_addr(page);
+
+    // Reading an erased page would fault.
+    if !lpc55_romapi::validate_programmed(addr, PAGE_SIZE) {
+        return None;
+    }
+
+    // SAFETY: We've validated that the page is programmed, and it's in the
+    // region that the linker script gave us.
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            addr as *const u8,
+            core::mem::size_of::<BootState>(),
+        )
+    };
+    BootState::read_from(bytes).filter(BootState::is_valid)
+}
+
+/// The boot state as found in flash.
+pub struct Store {
+    /// The newest valid copy of the state, if there is one.
+    pub state: Option<BootState>,
+    /// The page that the next copy should be written to.
+    next_page: u32,
+}
+
+impl Store {
+    pub fn read() -> Self {
+        let (state, next_page) =
+            BootState::newest([read_page(0), read_page(1)]);
+        Self {
+            state,
+            next_page: next_page as u32,
+        }
+    }
+
+    /// Writes a new copy of the state over the older one. If this fails (or
+    /// is interrupted), the copy we read stays in effect.
+    pub fn write(&mut self, state: BootState) {
+        let state = state.next();
+
+        let mut page = [0xff; FLASH_PAGE_SIZE];
+        page[..core::mem::size_of::<BootState>()]
+            .copy_from_slice(state.as_bytes());
+
+        let addr = page_addr(self.next_page);
+
+        // SAFETY: The address is in the region that the linker script gave
+        // us, which isn't part of either image or stage0.
+        let ok = unsafe {
+            lpc55_romapi::flash_erase(addr, PAGE_SIZE).is_ok()
+                && lpc55_romapi::flash_write(addr, page.as_mut_ptr(), PAGE_SIZE)
+                    .is_ok()
+        };
+
+        if ok {
+            self.state = Some(state);
+            self.next_page ^= 1;
+        }
+    }
+}
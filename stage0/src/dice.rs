@@ -51,16 +51,15 @@ pub fn run(image: &Image) {
     )
     .sign(&deviceid_keypair);
 
-    // Collect hash(es) of TCB. The first TCB Component Identifier (TCI)
-    // calculated is the Hubris image. The DICE specs call this collection
-    // of TCIs the FWID. This hash is stored in keeys certified by the
-    // DeviceId. This hash should be 'updated' with relevant configuration
-    // and code as FWID for Hubris becomes known.
-    // TODO: This is a particularly naive way to calculate the FWID:
-    // https://github.com/oxidecomputer/hubris/issues/736
-    let mut fwid = Sha3_256::new();
-    fwid.update(image.as_bytes());
-    let fwid = fwid.finalize();
+    // The FWID of the Hubris image is the hash of its manifest, which lists
+    // the hashes of the kernel, each task and the app.toml that the image was
+    // built from. This hash is stored in keys certified by the DeviceId. If
+    // the manifest doesn't match the image, we don't certify anything, and
+    // leave tasks with empty handoff memory.
+    let fwid = match image.manifest() {
+        Some(manifest) => Sha3_256::digest(manifest),
+        None => return,
+    };
 
     // create CDI for layer 1 (L1) firmware (the hubris image we're booting)
     let cdi_l1 = CdiL1::new(&cdi, fwid.as_ref());
@@ -74,6 +73,7 @@ pub fn run(image: &Image) {
         &dname_sn,
         &alias_keypair.public,
         fwid.as_ref(),
+        image.get_version(),
     )
     .sign(&deviceid_keypair);
 
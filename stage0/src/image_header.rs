@@ -2,8 +2,12 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use abi::{ImageHeader, ImageVectors};
+use abi::{ImageHeader, ImageSignature, ImageVectors};
+use core::convert::TryFrom;
 use lpc55_romapi::FLASH_PAGE_SIZE;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use sha3::{Digest, Sha3_256};
 
 extern "C" {
     static IMAGEA: abi::ImageVectors;
@@ -73,8 +77,6 @@ impl Image {
 
     #[cfg(feature = "dice")]
     fn get_img_size(&self) -> Option<usize> {
-        use core::convert::TryFrom;
-
         usize::try_from((unsafe { &*self.get_header() }).total_image_len).ok()
     }
 
@@ -87,6 +89,26 @@ impl Image {
         unsafe { core::slice::from_raw_parts(img_ptr, img_size) }
     }
 
+    /// Returns the image's manifest (see `abi::ManifestHeader`) as raw bytes,
+    /// if it's well-formed, agrees with the image header, and every component
+    /// it lists matches what's in flash.
+    #[cfg(feature = "dice")]
+    pub fn manifest(&self) -> Option<&[u8]> {
+        // SAFETY: We checked this previously
+        let header = unsafe { &*self.get_header() };
+
+        abi::check_manifest(
+            self.as_bytes(),
+            self.get_img_start(),
+            header,
+            |contents| {
+                let mut hash = [0; 32];
+                hash.copy_from_slice(&Sha3_256::digest(contents));
+                hash
+            },
+        )
+    }
+
     fn get_header(&self) -> *const ImageHeader {
         // SAFETY: This generated by the linker script which we trust
         // Note that this is generated from _this_ image's linker script
@@ -96,7 +118,8 @@ impl Image {
         (self.get_img_start() + vector_size) as *const ImageHeader
     }
 
-    /// Make sure all of the image flash is programmed
+    /// Make sure all of the image flash is programmed, and that the image is
+    /// signed with our image signing key
     fn validate(&self) -> bool {
         let img_start = self.get_img_start();
 
@@ -122,10 +145,17 @@ impl Image {
         // which we trust.
         let header = unsafe { &*header_ptr };
 
-        // Next make sure the marked image length is programmed
+        // Next make sure the marked image length, and the signature trailer
+        // after it, are programmed
+        let signed_len = match ImageSignature::offset(header.total_image_len)
+            .checked_add(core::mem::size_of::<ImageSignature>() as u32)
+        {
+            Some(len) if len <= u32::MAX - PAGE_SIZE => len,
+            _ => return false,
+        };
         let valid = lpc55_romapi::validate_programmed(
             img_start,
-            (header.total_image_len + (PAGE_SIZE - 1)) & !(PAGE_SIZE - 1),
+            (signed_len + (PAGE_SIZE - 1)) & !(PAGE_SIZE - 1),
         );
 
         if !valid {
@@ -137,7 +167,53 @@ impl Image {
             return false;
         }
 
-        return true;
+        // Finally, was it signed by someone we trust?
+        self.verify_signature(header)
+    }
+
+    /// Checks the image's signature trailer against the image signing key
+    /// that we were built with. The caller must have checked that the image
+    /// and its trailer are programmed.
+    fn verify_signature(&self, header: &ImageHeader) -> bool {
+        let img_start = self.get_img_start();
+
+        // SAFETY: The caller validated that this is programmed, and the
+        // offset is aligned for the trailer.
+        let trailer = unsafe {
+            &*((img_start + ImageSignature::offset(header.total_image_len))
+                as *const ImageSignature)
+        };
+
+        if trailer.magic != abi::SIGNATURE_MAGIC {
+            return false;
+        }
+
+        // The trailer carries the public key, but we only trust it if it's
+        // the one we were built with.
+        if Sha3_256::digest(&trailer.public_key)[..] != crate::IMAGE_KEY_HASH {
+            return false;
+        }
+
+        // The key is stored without the SEC1 tag for an uncompressed point.
+        let mut sec1 = [0x04; 65];
+        sec1[1..].copy_from_slice(&trailer.public_key);
+        let key = match VerifyingKey::from_sec1_bytes(&sec1) {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+        let signature = match Signature::try_from(&trailer.signature[..]) {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
+
+        // SAFETY: The caller validated that this is programmed.
+        let image = unsafe {
+            core::slice::from_raw_parts(
+                img_start as *const u8,
+                header.total_image_len as usize,
+            )
+        };
+        key.verify(image, &signature).is_ok()
     }
 
     pub fn get_vectors(&self) -> u32 {
@@ -159,6 +235,13 @@ impl Image {
         header.version
     }
 
+    pub fn get_epoch(&self) -> u32 {
+        // SAFETY: We checked this previously
+        let header = unsafe { &*self.get_header() };
+
+        header.epoch
+    }
+
     #[cfg(feature = "tz_support")]
     pub fn get_sau_entry<'a>(&self, i: usize) -> Option<&'a abi::SAUEntry> {
         // SAFETY: We checked this previously
@@ -19,9 +19,11 @@ use cortex_m_rt::entry;
 mod dice;
 // FIXME Need to fixup the secure interface calls
 //mod hypo;
+mod boot_state;
 mod image_header;
 
 use crate::image_header::Image;
+use abi::SlotImage;
 
 /// Initial entry point for handling a memory management fault.
 #[allow(non_snake_case)]
@@ -163,6 +165,27 @@ fn check_system_freq() {
     }
 }
 
+/// Picks the image to boot, following (and updating) the persistent boot
+/// state; see `abi::select_slot`.
+fn select_image(store: &mut boot_state::Store) -> Option<Image> {
+    let mut images = [image_header::get_image_a(), image_header::get_image_b()];
+    let slot_image = |image: &Option<Image>| {
+        image.as_ref().map(|image| SlotImage {
+            version: image.get_version(),
+            epoch: image.get_epoch(),
+        })
+    };
+
+    let choice = abi::select_slot(
+        store.state,
+        [slot_image(&images[0]), slot_image(&images[1])],
+    );
+    if let Some(state) = choice.update {
+        store.write(state);
+    }
+    images[choice.slot? as usize].take()
+}
+
 #[entry]
 fn main() -> ! {
     // This is the SYSCON_DIEID register on LPC55 which contains the ROM
@@ -175,22 +198,10 @@ fn main() -> ! {
 
     check_system_freq();
 
-    let (imagea, imageb) =
-        (image_header::get_image_a(), image_header::get_image_b());
-
-    // Image selection is very simple at the moment
-    // Future work: check persistent state and epochs
-    let image = match (imagea, imageb) {
-        (None, None) => panic!(),
-        (Some(a), None) => a,
-        (None, Some(b)) => b,
-        (Some(a), Some(b)) => {
-            if a.get_version() > b.get_version() {
-                a
-            } else {
-                b
-            }
-        }
+    let mut store = boot_state::Store::read();
+    let image = match select_image(&mut store) {
+        Some(image) => image,
+        None => panic!(),
     };
 
     #[cfg(feature = "dice")]
@@ -119,6 +119,12 @@ impl Priority {
     }
 }
 
+/// Largest number of timers a single task can be given.
+pub const MAX_TIMERS_PER_TASK: u8 = 8;
+
+/// Largest number of asynchronous send slots a single task can be given.
+pub const MAX_ASYNC_SENDS_PER_TASK: u8 = 8;
+
 /// Record describing a single task.
 #[derive(Clone, Debug, FromBytes, Serialize, Deserialize)]
 pub struct TaskDesc {
@@ -140,6 +146,13 @@ pub struct TaskDesc {
     pub initial_stack: u32,
     /// Initial priority of this task.
     pub priority: u8,
+    /// Number of timers this task can use, between 1 and
+    /// `MAX_TIMERS_PER_TASK`. Timer 0 is the one `SET_TIMER` and `GET_TIMER`
+    /// operate on by default.
+    pub timers: u8,
+    /// Number of slots this task has for asynchronous sends, up to
+    /// `MAX_ASYNC_SENDS_PER_TASK`. This is usually 0.
+    pub async_sends: u8,
     /// Collection of boolean flags controlling task behavior.
     pub flags: TaskFlags,
     /// Index of this task within the task table.
@@ -309,6 +322,26 @@ pub struct ULease {
     pub length: u32,
 }
 
+/// One step of a vectored borrow (`BORROW_READV` or `BORROW_WRITEV`), copying
+/// between a span of a lease and a buffer in the borrower's memory.
+///
+/// As with the non-vectored borrow syscalls, each step copies as many bytes as
+/// will fit in both the buffer and the remainder of the lease after `offset`.
+///
+/// This is an ABI-committed type.
+#[derive(Copy, Clone, Debug, FromBytes)]
+#[repr(C)]
+pub struct BorrowSegment {
+    /// Index of the lease to copy to or from.
+    pub lease_number: u32,
+    /// Offset into the lease at which to start.
+    pub offset: u32,
+    /// Base address of the borrower's buffer.
+    pub base_address: u32,
+    /// Length of the borrower's buffer, in bytes.
+    pub length: u32,
+}
+
 bitflags::bitflags! {
     #[derive(FromBytes)]
     #[repr(transparent)]
@@ -343,6 +376,26 @@ pub const fn extract_new_generation(code: u32) -> Option<Generation> {
 /// Response code returned by the kernel if a lender has defected.
 pub const DEFECT: u32 = 1;
 
+/// Response code returned by the kernel from `SEND` or `RECV` if the deadline
+/// given for the operation passed before it completed.
+///
+/// This sits just below the dead code range, and like dead codes, a server
+/// could fake it by replying with it deliberately.
+pub const TIMED_OUT: u32 = 0xffff_fe00;
+
+/// Status of an asynchronous send slot, as reported by `COLLECT_ASYNC`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum AsyncSendStatus {
+    /// The slot has no send in it.
+    Idle = 0,
+    /// The send has not been replied to yet.
+    Pending = 1,
+    /// The send has been replied to, and the response code and length are
+    /// available.
+    Complete = 2,
+}
+
 /// State used to make scheduling decisions.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 pub enum TaskState {
@@ -411,6 +464,79 @@ pub enum SchedState {
     InRecv(Option<TaskId>),
 }
 
+/// CPU usage statistics for a task, accumulated by the kernel since boot.
+///
+/// These survive task restarts, so that a task that is repeatedly crashing
+/// doesn't hide its history.
+#[derive(
+    Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize,
+)]
+pub struct TaskRuntime {
+    /// Number of kernel ticks that found this task running. This is a
+    /// statistical measure: a task that runs briefly but often may be charged
+    /// more or less than the time it actually used.
+    pub ticks: u64,
+    /// Number of times the kernel has switched to this task from some other
+    /// task.
+    pub context_switches: u64,
+}
+
+/// Stack usage of a task, as measured by the kernel.
+///
+/// The kernel fills each task's stack with a known pattern whenever the task
+/// is (re)started, and measures usage by looking for the deepest word that no
+/// longer holds the pattern. This can under-report if a task happens to write
+/// the pattern itself, or skips over part of its stack without writing it.
+#[derive(
+    Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize,
+)]
+pub struct TaskStackUsage {
+    /// Size of the task's stack, in bytes. This is zero if the kernel
+    /// couldn't locate the stack.
+    pub size: u32,
+    /// Deepest stack usage since the task was last started, in bytes.
+    pub peak: u32,
+}
+
+/// An entry in the kernel's event log, as returned by the `ReadEventLog` kipc.
+///
+/// The log records faults, restarts, and other supervisory actions, and
+/// survives warm resets, so that it can be examined after the fact.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct KernelEvent {
+    /// Position of this event in the log. This counts up from zero when the
+    /// log is first created, and keeps counting across warm resets.
+    pub seq: u32,
+    /// Kernel timestamp of the event. Timestamps start over at each reset;
+    /// `KernelEventKind::Boot` events mark where that happened.
+    pub timestamp: u64,
+    /// What happened.
+    pub kind: KernelEventKind,
+}
+
+/// Kinds of event recorded in the kernel's event log.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub enum KernelEventKind {
+    /// The kernel started.
+    Boot,
+    /// `task` faulted. Faults delivered with `REPLY_FAULT` or injected by
+    /// another task are recorded here too, as `FaultInfo::FromServer` and
+    /// `FaultInfo::Injected` respectively.
+    Fault {
+        task: TaskId,
+        fault: FaultInfo,
+        /// Program counter of the faulting task, if the kernel could find
+        /// it.
+        pc: Option<u32>,
+    },
+    /// `task` was restarted by `by`. `task` gives the new generation.
+    Restart { task: TaskId, by: TaskId },
+    /// The priority of `task` was changed by the supervisor.
+    SetPriority { task: TaskId, priority: u8 },
+    /// `by` asked the kernel to reset the system.
+    Reset { by: TaskId },
+}
+
 impl From<SchedState> for TaskState {
     fn from(s: SchedState) -> Self {
         Self::Healthy(s)
@@ -513,6 +639,26 @@ pub enum UsageError {
     NoIrq,
     BadKernelMessage,
     BadReplyFaultReason,
+    /// A program that is not the supervisor attempted a kernel IPC that is
+    /// reserved for the supervisor.
+    NotSupervisor,
+    /// A program named a timer beyond the number it was given in its
+    /// configuration.
+    TimerOutOfRange,
+    /// A program attempted to send a message that the recipient's IPC access
+    /// control list doesn't allow: either the recipient doesn't accept
+    /// messages from this program at all, or not with this operation code.
+    IpcNotPermitted,
+    /// A program named an asynchronous send slot beyond the number it was
+    /// given in its configuration.
+    AsyncSlotOutOfRange,
+    /// A program tried to start an asynchronous send in a slot that was still
+    /// in use, or whose result hadn't been collected.
+    AsyncSlotBusy,
+    /// A program tried to send to a task that it already had an asynchronous
+    /// send outstanding with. The recipient wouldn't be able to tell the two
+    /// apart when replying.
+    SendInProgress,
 }
 
 /// Origin of a fault.
@@ -555,6 +701,7 @@ pub enum ReplyFaultReason {
 }
 
 /// Enumeration of syscall numbers.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(u32)]
 pub enum Sysnum {
     Send = 0,
@@ -570,6 +717,12 @@ pub enum Sysnum {
     RefreshTaskId = 10,
     Post = 11,
     ReplyFault = 12,
+    SetSendDeadline = 13,
+    Idle = 14,
+    BorrowReadv = 15,
+    BorrowWritev = 16,
+    SetSendAsync = 17,
+    CollectAsync = 18,
 }
 
 /// We're using an explicit `TryFrom` impl for `Sysnum` instead of
@@ -593,6 +746,12 @@ impl core::convert::TryFrom<u32> for Sysnum {
             10 => Ok(Self::RefreshTaskId),
             11 => Ok(Self::Post),
             12 => Ok(Self::ReplyFault),
+            13 => Ok(Self::SetSendDeadline),
+            14 => Ok(Self::Idle),
+            15 => Ok(Self::BorrowReadv),
+            16 => Ok(Self::BorrowWritev),
+            17 => Ok(Self::SetSendAsync),
+            18 => Ok(Self::CollectAsync),
             _ => Err(()),
         }
     }
@@ -605,6 +764,12 @@ pub enum Kipcnum {
     FaultTask = 3,
     ReadImageId = 4,
     Reset = 5,
+    ReadTaskPriority = 6,
+    SetTaskPriority = 7,
+    ReadTaskRuntime = 8,
+    ReadTaskStackUsage = 9,
+    ReadEventLog = 10,
+    ReadPanicMessage = 11,
 }
 
 impl core::convert::TryFrom<u16> for Kipcnum {
@@ -617,6 +782,12 @@ impl core::convert::TryFrom<u16> for Kipcnum {
             3 => Ok(Self::FaultTask),
             4 => Ok(Self::ReadImageId),
             5 => Ok(Self::Reset),
+            6 => Ok(Self::ReadTaskPriority),
+            7 => Ok(Self::SetTaskPriority),
+            8 => Ok(Self::ReadTaskRuntime),
+            9 => Ok(Self::ReadTaskStackUsage),
+            10 => Ok(Self::ReadEventLog),
+            11 => Ok(Self::ReadPanicMessage),
             _ => Err(()),
         }
     }
@@ -639,6 +810,343 @@ pub struct ImageHeader {
     pub sau_entries: [SAUEntry; 8],
     pub version: u32,
     pub epoch: u32,
+    /// Offset of the image's manifest (see `ManifestHeader`) from the start
+    /// of the image.
+    pub manifest: u32,
+}
+
+pub const MANIFEST_MAGIC: u32 = 0x3a4f_e57d;
+
+/// Version of the manifest format described by `ManifestHeader`.
+pub const MANIFEST_FORMAT: u32 = 1;
+
+/// Manifest of an image booted by stage0, which stage0 measures (with
+/// SHA3-256) as the image's DICE FWID.
+///
+/// `xtask dist` places this at the end of the image, inside
+/// `total_image_len`, and points `ImageHeader::manifest` at it. The header is
+/// followed by `component_count` `ManifestComponent`s: the kernel first, and
+/// then each task in `app.toml` order. Stage0 checks each component against
+/// flash before measuring the manifest, so a verifier that knows the FWID can
+/// match the individual components (e.g. to see which tasks changed between
+/// two builds).
+#[repr(C)]
+#[derive(Default, AsBytes, FromBytes)]
+pub struct ManifestHeader {
+    pub magic: u32,
+    pub format: u32,
+    /// The image's version and epoch, as in its `ImageHeader`.
+    pub version: u32,
+    pub epoch: u32,
+    /// SHA3-256 hash of the `app.toml` the image was built from.
+    pub app_hash: [u8; 32],
+    pub component_count: u32,
+}
+
+/// Flash contents of a component (the kernel or a task) of an image.
+#[repr(C)]
+#[derive(Default, AsBytes, FromBytes)]
+pub struct ManifestComponent {
+    /// Address and length of the component's flash, up to the end of the
+    /// last thing in it.
+    pub base: u32,
+    pub len: u32,
+    /// SHA3-256 hash of those `len` bytes.
+    pub hash: [u8; 32],
+}
+
+impl ManifestHeader {
+    /// Returns the size of a manifest with `component_count` components.
+    pub const fn len(component_count: u32) -> u32 {
+        (core::mem::size_of::<Self>()
+            + component_count as usize
+                * core::mem::size_of::<ManifestComponent>()) as u32
+    }
+}
+
+/// Finds and checks the manifest of an image, given the image's `header` and
+/// its first `total_image_len` bytes, which start at address `base`. Returns
+/// the manifest's bytes if it's well-formed, its version and epoch match the
+/// header's, and every component it lists lies within the image and has the
+/// listed hash, as computed by `hash` (SHA3-256).
+pub fn check_manifest<'a>(
+    image: &'a [u8],
+    base: u32,
+    header: &ImageHeader,
+    hash: impl Fn(&[u8]) -> [u8; 32],
+) -> Option<&'a [u8]> {
+    use core::mem::size_of;
+
+    let start = header.manifest as usize;
+    let mut rest = image.get(start..)?;
+
+    let manifest = ManifestHeader::read_from_prefix(rest)?;
+    if manifest.magic != MANIFEST_MAGIC
+        || manifest.format != MANIFEST_FORMAT
+        || manifest.version != header.version
+        || manifest.epoch != header.epoch
+    {
+        return None;
+    }
+    rest = &rest[size_of::<ManifestHeader>()..];
+
+    for _ in 0..manifest.component_count {
+        let component = ManifestComponent::read_from_prefix(rest)?;
+        rest = &rest[size_of::<ManifestComponent>()..];
+
+        let offset = component.base.checked_sub(base)? as usize;
+        let contents =
+            image.get(offset..offset.checked_add(component.len as usize)?)?;
+        if hash(contents) != component.hash {
+            return None;
+        }
+    }
+
+    image.get(start..image.len() - rest.len())
+}
+
+pub const SIGNATURE_MAGIC: u32 = 0x5157_a7e5;
+
+/// Signature trailer of an image booted by stage0.
+///
+/// `xtask dist` places this right after the image, at `total_image_len`
+/// rounded up to a multiple of 4, when the app is configured with an image
+/// signing key. Stage0 only boots an image if the trailer's public key hashes
+/// (with SHA3-256) to the key hash it was built with, and the signature checks
+/// out.
+#[repr(C)]
+#[derive(AsBytes, FromBytes)]
+pub struct ImageSignature {
+    pub magic: u32,
+    /// P-256 public key, as the big-endian x and y coordinates.
+    pub public_key: [u8; 64],
+    /// ECDSA P-256 signature (r and s, big-endian) over the SHA-256 digest of
+    /// the first `total_image_len` bytes of the image.
+    pub signature: [u8; 64],
+}
+
+impl ImageSignature {
+    /// Returns the offset of the trailer from the start of an image that is
+    /// `total_image_len` bytes long.
+    pub const fn offset(total_image_len: u32) -> u32 {
+        (total_image_len + 3) & !3
+    }
+}
+
+pub const BOOT_STATE_MAGIC: u32 = 0xb007_57a7;
+
+/// Number of times stage0 will boot a pending image before giving up on it
+/// and falling back to the other slot.
+pub const MAX_BOOT_ATTEMPTS: u32 = 3;
+
+/// Persistent boot state, which stage0 uses to pick an image to boot.
+///
+/// This is kept in the `bootstate` flash region, which holds two copies in
+/// separate flash pages. Each write goes to the page holding the older copy,
+/// so that a write torn by a reset leaves the other copy intact; readers use
+/// the valid copy with the higher `sequence`.
+///
+/// When an image is written to a slot, the slot is made `preferred` and
+/// `pending`. Stage0 counts its attempts to boot a pending image, and once
+/// that reaches `MAX_BOOT_ATTEMPTS`, clears `pending` and prefers the other
+/// slot instead; the image has to confirm itself (clearing `pending`) before
+/// then to stick. Once a confirmed image boots, its epoch becomes the
+/// minimum: images from earlier epochs won't be booted again.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, AsBytes, FromBytes)]
+pub struct BootState {
+    pub magic: u32,
+    /// Incremented on each write.
+    pub sequence: u32,
+    /// Slot to boot, as a `BootSlot`.
+    pub preferred: u32,
+    /// Nonzero if the preferred slot holds an image that hasn't confirmed
+    /// itself yet.
+    pub pending: u32,
+    /// Number of times stage0 has booted the pending image.
+    pub attempts: u32,
+    /// Lowest `ImageHeader::epoch` that stage0 will boot.
+    pub min_epoch: u32,
+    /// Checksum of the fields above; see `BootState::checksum`.
+    pub checksum: u32,
+}
+
+impl BootState {
+    /// Computes the checksum of the state (FNV-1a over all fields but
+    /// `checksum`), which is only meant to catch torn or corrupted writes.
+    pub fn checksum(&self) -> u32 {
+        let bytes = self.as_bytes();
+        let fields = &bytes[..bytes.len() - core::mem::size_of::<u32>()];
+        fields.iter().fold(0x811c_9dc5, |hash, &b| {
+            (hash ^ u32::from(b)).wrapping_mul(0x0100_0193)
+        })
+    }
+
+    /// Checks that this is a complete, uncorrupted copy of the state.
+    pub fn is_valid(&self) -> bool {
+        use core::convert::TryFrom;
+
+        self.magic == BOOT_STATE_MAGIC
+            && self.checksum == self.checksum()
+            && BootSlot::try_from(self.preferred).is_ok()
+    }
+
+    /// Given the two copies of the state as read from flash (`None` for a copy
+    /// that isn't valid), returns the newest, along with the index of the
+    /// copy that the next write should replace.
+    pub fn newest(copies: [Option<Self>; 2]) -> (Option<Self>, usize) {
+        match copies {
+            // Sequence numbers are compared so that they can wrap.
+            [Some(a), Some(b)]
+                if (b.sequence.wrapping_sub(a.sequence) as i32) > 0 =>
+            {
+                (Some(b), 0)
+            }
+            [Some(a), _] => (Some(a), 1),
+            [None, Some(b)] => (Some(b), 0),
+            [None, None] => (None, 0),
+        }
+    }
+
+    /// Returns the state that follows this one, with its checksum updated.
+    pub fn next(mut self) -> Self {
+        self.magic = BOOT_STATE_MAGIC;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.checksum = self.checksum();
+        self
+    }
+}
+
+/// Image slots that stage0 boots from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum BootSlot {
+    A = 0,
+    B = 1,
+}
+
+impl core::convert::TryFrom<u32> for BootSlot {
+    type Error = ();
+
+    fn try_from(x: u32) -> Result<Self, Self::Error> {
+        match x {
+            0 => Ok(Self::A),
+            1 => Ok(Self::B),
+            _ => Err(()),
+        }
+    }
+}
+
+impl BootSlot {
+    pub fn other(self) -> Self {
+        match self {
+            Self::A => Self::B,
+            Self::B => Self::A,
+        }
+    }
+}
+
+/// The parts of a slot's image that matter to `select_slot`, for an image
+/// that stage0 has found to be valid.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SlotImage {
+    pub version: u32,
+    pub epoch: u32,
+}
+
+/// What `select_slot` decided.
+#[derive(Copy, Clone, Debug)]
+pub struct BootChoice {
+    /// Slot to boot, or `None` if neither has a usable image.
+    pub slot: Option<BootSlot>,
+    /// New boot state to write before booting, if it's changed.
+    pub update: Option<BootState>,
+}
+
+/// Picks the slot to boot, given the current boot state (`None` if there's no
+/// valid copy) and the valid image in each slot, indexed by `BootSlot`. This
+/// follows the rules described on `BootState`.
+pub fn select_slot(
+    state: Option<BootState>,
+    images: [Option<SlotImage>; 2],
+) -> BootChoice {
+    use core::convert::TryFrom;
+
+    let min_epoch = state.map_or(0, |s| s.min_epoch);
+
+    // Images from before the minimum epoch may have known vulnerabilities, so
+    // we treat them as if they weren't there.
+    let usable = |slot: BootSlot| {
+        images[slot as usize].filter(|image| image.epoch >= min_epoch)
+    };
+
+    let mut state = match state {
+        Some(state) => state,
+        // Without any boot state, the higher version wins.
+        None => {
+            let slot = match (usable(BootSlot::A), usable(BootSlot::B)) {
+                (None, None) => None,
+                (Some(_), None) => Some(BootSlot::A),
+                (None, Some(_)) => Some(BootSlot::B),
+                (Some(a), Some(b)) => {
+                    if a.version > b.version {
+                        Some(BootSlot::A)
+                    } else {
+                        Some(BootSlot::B)
+                    }
+                }
+            };
+            return BootChoice { slot, update: None };
+        }
+    };
+
+    // This can't fail, since only valid states are used.
+    let preferred = BootSlot::try_from(state.preferred).unwrap_or(BootSlot::A);
+    let other = preferred.other();
+
+    if state.pending != 0 {
+        if state.attempts >= MAX_BOOT_ATTEMPTS {
+            // The pending image has had its chances to confirm itself, so go
+            // back to the other slot (or, if that's unusable, keep trying).
+            state.preferred = other as u32;
+            state.pending = 0;
+            state.attempts = 0;
+            let slot = [other, preferred]
+                .iter()
+                .copied()
+                .find(|&slot| usable(slot).is_some());
+            return BootChoice {
+                slot,
+                update: Some(state),
+            };
+        }
+        if usable(preferred).is_some() {
+            state.attempts += 1;
+            return BootChoice {
+                slot: Some(preferred),
+                update: Some(state),
+            };
+        }
+    } else if let Some(image) = usable(preferred) {
+        // Once a confirmed image boots, there's no going back to an earlier
+        // epoch.
+        let update = if image.epoch > state.min_epoch {
+            state.min_epoch = image.epoch;
+            Some(state)
+        } else {
+            None
+        };
+        return BootChoice {
+            slot: Some(preferred),
+            update,
+        };
+    }
+
+    // The preferred slot has no usable image, so we have no choice.
+    BootChoice {
+        slot: usable(other).map(|_| other),
+        update: None,
+    }
 }
 
 // Corresponds to the ARM vector table, limited to what we need
@@ -649,3 +1157,444 @@ pub struct ImageVectors {
     pub sp: u32,
     pub entry: u32,
 }
+
+/// Magic number at the start of the kernel's trace buffer, used by host tools
+/// to check that they've found (and correctly dumped) the right memory.
+pub const TRACE_MAGIC: u32 = 0x7ace_b0f1;
+
+/// Header of the kernel's RAM trace buffer, which is followed in memory by
+/// `capacity` `TraceRecord`s.
+///
+/// The task table fields let a host tool turn the `task_addr >> 4` encoding
+/// used for context switch events back into task indices.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug, FromBytes, AsBytes)]
+pub struct TraceHeader {
+    /// Set to `TRACE_MAGIC` once tracing is configured.
+    pub magic: u32,
+    /// Address of the kernel task table (`HUBRIS_TASK_TABLE_SPACE`).
+    pub task_table: u32,
+    /// Size of a single `Task` in the task table, in bytes.
+    pub task_size: u32,
+    /// Number of records following the header.
+    pub capacity: u32,
+    /// Total number of records ever written. The most recent record is at
+    /// index `(count - 1) % capacity`; once `count` exceeds `capacity`, the
+    /// oldest records have been overwritten.
+    pub count: u32,
+}
+
+/// A single entry in the kernel's RAM trace buffer.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug, FromBytes, AsBytes)]
+pub struct TraceRecord {
+    /// Timestamp from the clock given to the kernel when tracing was
+    /// configured.
+    pub timestamp: u32,
+    /// A `TraceEvent`, or 0 if the record has never been written.
+    pub event: u32,
+    /// Event-specific argument: the syscall number for `SyscallEnter`, and
+    /// `task_addr >> 4` for `ContextSwitch`. Zero otherwise.
+    pub arg: u32,
+}
+
+/// Kinds of event recorded in a `TraceRecord`. These correspond to the hooks
+/// in the kernel's `EventsTable`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum TraceEvent {
+    SyscallEnter = 1,
+    SyscallExit = 2,
+    SecondarySyscallEnter = 3,
+    SecondarySyscallExit = 4,
+    IsrEnter = 5,
+    IsrExit = 6,
+    TimerIsrEnter = 7,
+    TimerIsrExit = 8,
+    ContextSwitch = 9,
+}
+
+impl core::convert::TryFrom<u32> for TraceEvent {
+    type Error = ();
+
+    fn try_from(x: u32) -> Result<Self, Self::Error> {
+        match x {
+            1 => Ok(Self::SyscallEnter),
+            2 => Ok(Self::SyscallExit),
+            3 => Ok(Self::SecondarySyscallEnter),
+            4 => Ok(Self::SecondarySyscallExit),
+            5 => Ok(Self::IsrEnter),
+            6 => Ok(Self::IsrExit),
+            7 => Ok(Self::TimerIsrEnter),
+            8 => Ok(Self::TimerIsrExit),
+            9 => Ok(Self::ContextSwitch),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    fn state(sequence: u32) -> BootState {
+        BootState {
+            sequence: sequence.wrapping_sub(1),
+            ..Default::default()
+        }
+        .next()
+    }
+
+    #[test]
+    fn boot_state_next_is_valid() {
+        let s = state(5);
+        assert!(s.is_valid());
+        assert_eq!(s.sequence, 5);
+        assert_eq!(s.next().sequence, 6);
+    }
+
+    #[test]
+    fn boot_state_detects_corruption() {
+        let mut s = state(5);
+        s.min_epoch += 1;
+        assert!(!s.is_valid());
+
+        let mut s = state(5);
+        s.magic = 0;
+        assert!(!s.is_valid());
+
+        // A bad slot is rejected even with a good checksum.
+        let mut s = state(5);
+        s.preferred = 2;
+        s.checksum = s.checksum();
+        assert!(!s.is_valid());
+    }
+
+    #[test]
+    fn boot_state_newest() {
+        let (a, b) = (state(7), state(8));
+        assert_eq!(BootState::newest([Some(a), Some(b)]).1, 0);
+        assert_eq!(BootState::newest([Some(b), Some(a)]).1, 1);
+        assert_eq!(
+            BootState::newest([Some(b), Some(a)]).0.unwrap().sequence,
+            8
+        );
+        assert_eq!(BootState::newest([None, None]).0.map(|s| s.sequence), None);
+    }
+
+    #[test]
+    fn boot_state_newest_after_torn_write() {
+        // The copy being written is invalid; the other one stays in effect,
+        // and is the one not to overwrite.
+        let good = state(9);
+        let mut torn = state(10);
+        torn.checksum ^= 1;
+        let torn = Some(torn).filter(BootState::is_valid);
+
+        let (s, next) = BootState::newest([Some(good), torn]);
+        assert_eq!(s.unwrap().sequence, 9);
+        assert_eq!(next, 1);
+        let (s, next) = BootState::newest([torn, Some(good)]);
+        assert_eq!(s.unwrap().sequence, 9);
+        assert_eq!(next, 0);
+    }
+
+    #[test]
+    fn boot_state_sequence_wraps() {
+        let old = state(u32::MAX);
+        let new = old.next();
+        assert_eq!(new.sequence, 0);
+        assert!(new.is_valid());
+        assert_eq!(
+            BootState::newest([Some(old), Some(new)])
+                .0
+                .unwrap()
+                .sequence,
+            0
+        );
+        assert_eq!(
+            BootState::newest([Some(new), Some(old)])
+                .0
+                .unwrap()
+                .sequence,
+            0
+        );
+    }
+
+    /// The two copies of the boot state in flash, written the way stage0
+    /// writes them.
+    struct Flash {
+        pages: [Option<BootState>; 2],
+    }
+
+    impl Flash {
+        fn read(&self) -> (Option<BootState>, usize) {
+            let valid =
+                |page: Option<BootState>| page.filter(BootState::is_valid);
+            BootState::newest([valid(self.pages[0]), valid(self.pages[1])])
+        }
+
+        /// Boots once, returning the slot booted.
+        fn boot(&mut self, images: [Option<SlotImage>; 2]) -> Option<BootSlot> {
+            let (state, next) = self.read();
+            let choice = select_slot(state, images);
+            if let Some(state) = choice.update {
+                self.pages[next] = Some(state.next());
+            }
+            choice.slot
+        }
+    }
+
+    fn image(version: u32, epoch: u32) -> Option<SlotImage> {
+        Some(SlotImage { version, epoch })
+    }
+
+    fn pending(slot: BootSlot) -> BootState {
+        BootState {
+            preferred: slot as u32,
+            pending: 1,
+            ..state(1)
+        }
+        .next()
+    }
+
+    #[test]
+    fn select_without_state_prefers_higher_version() {
+        let choose = |a, b| select_slot(None, [a, b]).slot;
+        assert_eq!(choose(image(2, 0), image(1, 0)), Some(BootSlot::A));
+        assert_eq!(choose(image(1, 0), image(2, 0)), Some(BootSlot::B));
+        assert_eq!(choose(None, image(1, 0)), Some(BootSlot::B));
+        assert_eq!(choose(None, None), None);
+    }
+
+    #[test]
+    fn select_falls_back_after_max_attempts() {
+        let images = [image(1, 0), image(2, 0)];
+        let mut flash = Flash {
+            pages: [Some(pending(BootSlot::B)), None],
+        };
+        for attempt in 1..=MAX_BOOT_ATTEMPTS {
+            assert_eq!(flash.boot(images), Some(BootSlot::B));
+            assert_eq!(flash.read().0.unwrap().attempts, attempt);
+        }
+
+        // The image never confirmed itself, so we go back to A for good.
+        for _ in 0..2 {
+            assert_eq!(flash.boot(images), Some(BootSlot::A));
+            let state = flash.read().0.unwrap();
+            assert_eq!(state.preferred, BootSlot::A as u32);
+            assert_eq!(state.pending, 0);
+        }
+    }
+
+    #[test]
+    fn select_keeps_a_confirmed_image() {
+        let images = [image(1, 0), image(2, 0)];
+        let mut flash = Flash {
+            pages: [Some(pending(BootSlot::B)), None],
+        };
+        assert_eq!(flash.boot(images), Some(BootSlot::B));
+
+        // The image confirms itself, as the update server would.
+        let (state, next) = flash.read();
+        let state = BootState {
+            pending: 0,
+            attempts: 0,
+            ..state.unwrap()
+        };
+        flash.pages[next] = Some(state.next());
+
+        for _ in 0..=MAX_BOOT_ATTEMPTS {
+            assert_eq!(flash.boot(images), Some(BootSlot::B));
+        }
+    }
+
+    #[test]
+    fn select_enforces_epoch_floor() {
+        let mut flash = Flash {
+            pages: [
+                Some(
+                    BootState {
+                        preferred: BootSlot::B as u32,
+                        ..state(1)
+                    }
+                    .next(),
+                ),
+                None,
+            ],
+        };
+
+        // Booting a confirmed image raises the floor to its epoch...
+        assert_eq!(flash.boot([image(1, 1), image(2, 2)]), Some(BootSlot::B));
+        assert_eq!(flash.read().0.unwrap().min_epoch, 2);
+
+        // ...so the older image can't be booted, even if B goes bad.
+        assert_eq!(flash.boot([image(1, 1), None]), None);
+
+        // A pending image from before the floor is passed over too.
+        let state = BootState {
+            min_epoch: 2,
+            ..pending(BootSlot::A)
+        };
+        let choice = select_slot(Some(state), [image(3, 1), image(2, 2)]);
+        assert_eq!(choice.slot, Some(BootSlot::B));
+    }
+
+    #[test]
+    fn select_ignores_torn_copy() {
+        let images = [image(1, 0), image(2, 0)];
+        let mut flash = Flash {
+            pages: [Some(pending(BootSlot::B)), None],
+        };
+        assert_eq!(flash.boot(images), Some(BootSlot::B));
+
+        // A reset tears the next write, which went to page 0; page 1 still
+        // says one attempt.
+        let mut torn = flash.pages[1].unwrap().next();
+        torn.attempts += 1;
+        flash.pages[0] = Some(torn);
+        assert_eq!(flash.read().0.unwrap().attempts, 1);
+
+        // The next boot counts from the intact copy, and overwrites the torn
+        // one.
+        assert_eq!(flash.boot(images), Some(BootSlot::B));
+        assert_eq!(flash.read().0.unwrap().attempts, 2);
+        assert!(flash.pages[0].unwrap().is_valid());
+    }
+
+    #[test]
+    fn select_across_sequence_wrap() {
+        let images = [image(1, 0), image(2, 0)];
+        let mut flash = Flash {
+            pages: [
+                Some(BootState {
+                    attempts: MAX_BOOT_ATTEMPTS - 1,
+                    ..pending(BootSlot::B)
+                }),
+                None,
+            ],
+        };
+        flash.pages[0] = Some(
+            BootState {
+                sequence: u32::MAX - 1,
+                ..flash.pages[0].unwrap()
+            }
+            .next(),
+        );
+        flash.pages[1] = Some(
+            BootState {
+                sequence: u32::MAX - 2,
+                ..flash.pages[0].unwrap()
+            }
+            .next(),
+        );
+
+        // This write wraps the sequence to zero, which must still count as
+        // newest.
+        assert_eq!(flash.boot(images), Some(BootSlot::B));
+        let (state, _) = flash.read();
+        assert_eq!(state.unwrap().sequence, 0);
+        assert_eq!(state.unwrap().attempts, MAX_BOOT_ATTEMPTS);
+
+        assert_eq!(flash.boot(images), Some(BootSlot::A));
+        assert_eq!(flash.read().0.unwrap().sequence, 1);
+    }
+
+    /// Stands in for SHA3-256: any function of the contents will do here.
+    fn fake_hash(bytes: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        for (i, &b) in bytes.iter().enumerate() {
+            out[i % 32] = out[i % 32].rotate_left(3) ^ b;
+        }
+        out[31] ^= bytes.len() as u8;
+        out
+    }
+
+    const BASE: u32 = 0x1_0000;
+
+    /// Builds an image with two components and a manifest after them,
+    /// returning it and its header.
+    fn manifest_image() -> (Vec<u8>, ImageHeader) {
+        let mut image = vec![0; 0x40];
+        image.extend((0..0x40).map(|i| i as u8));
+        image.extend((0..0x20).map(|i| !i as u8));
+
+        let header = ImageHeader {
+            magic: HEADER_MAGIC,
+            version: 3,
+            epoch: 2,
+            manifest: image.len() as u32,
+            total_image_len: image.len() as u32 + ManifestHeader::len(2),
+            ..Default::default()
+        };
+        let manifest = ManifestHeader {
+            magic: MANIFEST_MAGIC,
+            format: MANIFEST_FORMAT,
+            version: 3,
+            epoch: 2,
+            component_count: 2,
+            ..Default::default()
+        };
+        let components = [(0x40, 0x40), (0x80, 0x20)].map(|(start, len)| {
+            ManifestComponent {
+                base: BASE + start,
+                len,
+                hash: fake_hash(&image[start as usize..][..len as usize]),
+            }
+        });
+        image.extend_from_slice(manifest.as_bytes());
+        for c in &components {
+            image.extend_from_slice(c.as_bytes());
+        }
+        assert_eq!(image.len() as u32, header.total_image_len);
+        (image, header)
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let (image, header) = manifest_image();
+        let manifest = check_manifest(&image, BASE, &header, fake_hash);
+        assert_eq!(manifest, Some(&image[header.manifest as usize..]));
+    }
+
+    #[test]
+    fn manifest_rejects_tampered_component() {
+        let (mut image, header) = manifest_image();
+        image[0x90] ^= 1;
+        assert_eq!(check_manifest(&image, BASE, &header, fake_hash), None);
+
+        // Outside any component, a change isn't noticed.
+        let (mut image, header) = manifest_image();
+        image[0x10] ^= 1;
+        assert!(check_manifest(&image, BASE, &header, fake_hash).is_some());
+    }
+
+    #[test]
+    fn manifest_must_match_header() {
+        let (image, mut header) = manifest_image();
+        header.version = 4;
+        assert_eq!(check_manifest(&image, BASE, &header, fake_hash), None);
+
+        let (image, mut header) = manifest_image();
+        header.epoch = 1;
+        assert_eq!(check_manifest(&image, BASE, &header, fake_hash), None);
+    }
+
+    #[test]
+    fn manifest_components_stay_in_image() {
+        let (mut image, header) = manifest_image();
+        // Point the first component before the image.
+        let at =
+            header.manifest as usize + core::mem::size_of::<ManifestHeader>();
+        image[at..at + 4].copy_from_slice(&(BASE - 4).to_le_bytes());
+        assert_eq!(check_manifest(&image, BASE, &header, fake_hash), None);
+
+        // And a truncated image loses the end of the manifest.
+        let (image, header) = manifest_image();
+        let short = &image[..image.len() - 1];
+        assert_eq!(check_manifest(short, BASE, &header, fake_hash), None);
+    }
+}
@@ -11,7 +11,10 @@ use std::path::PathBuf;
 use serde::Deserialize;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    build_util::expose_m_profile();
+    // The hosted simulation target has no M-profile to speak of.
+    if !env::var("TARGET").unwrap().starts_with("x86_64") {
+        build_util::expose_m_profile();
+    }
 
     generate_consts()?;
     generate_statics()?;
@@ -57,11 +60,26 @@ fn generate_consts() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 fn generate_statics() -> Result<(), Box<dyn std::error::Error>> {
-    let image_id: u64 = env::var("HUBRIS_IMAGE_ID")?.parse()?;
+    // Host builds of the kernel (for unit tests, mostly) aren't driven by
+    // xtask, so we let them get away with an empty configuration.
+    let hosted = env::var("TARGET").unwrap().starts_with("x86_64");
+
+    let image_id: u64 = match env::var("HUBRIS_IMAGE_ID") {
+        Ok(id) => id.parse()?,
+        Err(_) if hosted => 0,
+        Err(e) => return Err(e.into()),
+    };
     println!("cargo:rerun-if-env-changed=HUBRIS_IMAGE_ID");
 
-    let kconfig: KernelConfig =
-        ron::de::from_str(&env::var("HUBRIS_KCONFIG")?)?;
+    let kconfig: KernelConfig = match env::var("HUBRIS_KCONFIG") {
+        Ok(kconfig) => ron::de::from_str(&kconfig)?,
+        // Give tests an event log to exercise.
+        Err(_) if hosted => KernelConfig {
+            event_log: 8,
+            ..KernelConfig::default()
+        },
+        Err(e) => return Err(e.into()),
+    };
     println!("cargo:rerun-if-env-changed=HUBRIS_KCONFIG");
 
     let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
@@ -77,6 +95,38 @@ fn generate_statics() -> Result<(), Box<dyn std::error::Error>> {
         kconfig.tasks.len()
     )?;
 
+    // Every task gets room for as many timers as the most demanding task in
+    // the image. Host builds without a configuration get a few, for tests.
+    let timers_per_task = match kconfig.tasks.iter().map(|t| t.timers).max() {
+        Some(n) => n.max(1),
+        None => 4,
+    };
+    writeln!(
+        file,
+        "pub(crate) const HUBRIS_TIMERS_PER_TASK: usize = {};",
+        timers_per_task
+    )?;
+
+    // Likewise for async send slots, except that most images don't use them,
+    // and so need no room at all. Host builds get a couple, for tests.
+    let async_sends_per_task = kconfig
+        .tasks
+        .iter()
+        .map(|t| t.async_sends)
+        .max()
+        .unwrap_or(2);
+    writeln!(
+        file,
+        "pub(crate) const HUBRIS_ASYNC_SENDS_PER_TASK: usize = {};",
+        async_sends_per_task
+    )?;
+
+    writeln!(
+        file,
+        "pub(crate) const HUBRIS_EVENT_LOG_ENTRIES: usize = {};",
+        kconfig.event_log
+    )?;
+
     writeln!(
         file,
         "static HUBRIS_TASK_DESCS: [abi::TaskDesc; HUBRIS_TASK_COUNT] = ["
@@ -91,6 +141,8 @@ fn generate_statics() -> Result<(), Box<dyn std::error::Error>> {
         writeln!(file, "        entry_point: {:#010x},", task.entry_point)?;
         writeln!(file, "        initial_stack: {:#010x},", task.initial_stack)?;
         writeln!(file, "        priority: {},", task.priority)?;
+        writeln!(file, "        timers: {},", task.timers)?;
+        writeln!(file, "        async_sends: {},", task.async_sends)?;
         writeln!(file, "        index: {},", task.index)?;
         writeln!(
             file,
@@ -102,6 +154,39 @@ fn generate_statics() -> Result<(), Box<dyn std::error::Error>> {
     }
     writeln!(file, "];")?;
 
+    // IPC access control lists, by task index. Tasks with no list (including
+    // any beyond the end of `kconfig.acls`) accept messages from anyone.
+    writeln!(
+        file,
+        "pub(crate) static HUBRIS_IPC_ACLS: \
+        [Option<&[crate::task::IpcGrant]>; HUBRIS_TASK_COUNT] = ["
+    )?;
+    for i in 0..kconfig.tasks.len() {
+        match kconfig.acls.get(i).and_then(Option::as_ref) {
+            None => writeln!(file, "    None,")?,
+            Some(grants) => {
+                writeln!(file, "    Some(&[")?;
+                for grant in grants {
+                    writeln!(file, "        crate::task::IpcGrant {{")?;
+                    writeln!(file, "            caller: {},", grant.caller)?;
+                    match &grant.operations {
+                        None => {
+                            writeln!(file, "            operations: None,")?
+                        }
+                        Some(ops) => writeln!(
+                            file,
+                            "            operations: Some(&{:?}),",
+                            ops
+                        )?,
+                    }
+                    writeln!(file, "        }},")?;
+                }
+                writeln!(file, "    ]),")?;
+            }
+        }
+    }
+    writeln!(file, "];")?;
+
     writeln!(
         file,
         "static mut HUBRIS_TASK_TABLE_SPACE: \
@@ -225,6 +310,7 @@ pub const HUBRIS_TASK_IRQ_LOOKUP: SortedList::<abi::InterruptOwner, &'static [ab
     } else if target.starts_with("thumbv7m")
         || target.starts_with("thumbv7em")
         || target.starts_with("thumbv8m")
+        || target.starts_with("x86_64")
     {
         let nested_import = if let Ok(task_irq_map) =
             phash_gen::OwnedPerfectHashMap::build(task_irq_map.clone())
@@ -336,9 +422,20 @@ pub const HUBRIS_IRQ_TASK_LOOKUP: NestedPerfectHashMap::<abi::InterruptNum, abi:
     Ok(())
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 struct KernelConfig {
     tasks: Vec<abi::TaskDesc>,
     regions: Vec<abi::RegionDesc>,
     irqs: Vec<abi::Interrupt>,
+    #[serde(default)]
+    acls: Vec<Option<Vec<IpcGrant>>>,
+    #[serde(default)]
+    event_log: usize,
+}
+
+/// Mirrors `kern::task::IpcGrant`, as serialized by xtask.
+#[derive(Deserialize)]
+struct IpcGrant {
+    caller: u16,
+    operations: Option<Vec<u16>>,
 }
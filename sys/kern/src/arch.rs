@@ -19,8 +19,19 @@ cfg_if::cfg_if! {
     // Note: cfg_if! is slightly touchy about ordering and expression
     // complexity; this chain seems to be the best compromise.
 
-    if #[cfg(not(target_pointer_width = "32"))] {
-        compile_error!("non-32-bit targets not supported (even for simulation)");
+    if #[cfg(test)] {
+        // Unit tests drive the kernel by hand, with no real tasks.
+        #[macro_use]
+        pub mod fake;
+        pub use fake::*;
+    } else if #[cfg(all(target_arch = "x86_64", target_os = "linux"))] {
+        // Hosted simulation is the one exception to the 32-bit rule; it keeps
+        // task memory below 4 GiB so that addresses still fit in a u32.
+        #[macro_use]
+        pub mod hosted;
+        pub use hosted::*;
+    } else if #[cfg(not(target_pointer_width = "32"))] {
+        compile_error!("non-32-bit targets not supported (except hosted simulation)");
     } else if #[cfg(target_arch = "arm")] {
         #[macro_use]
         pub mod arm_m;
@@ -13,6 +13,10 @@
 //! interrupts to maintain `TICKS`, but has the upside that we don't need
 //! special SoC support for timing.
 //!
+//! When the idle task sleeps using the `IDLE` syscall, we stretch the SysTick
+//! period to cover the time until the next kernel deadline, and add the ticks
+//! we skipped to `TICKS` when we wake. See `sleep` for details.
+//!
 //! # Notes on ARM-M interrupts
 //!
 //! For performance and (believe it or not) simplicity, this implementation uses
@@ -282,29 +286,9 @@ pub fn reinitialize(task: &mut task::Task) {
     // Ok. Generate a uslice for the task's starting stack frame.
     let mut frame_uslice: USlice<ExtendedExceptionFrame> =
         USlice::from_raw(initial_stack as usize - frame_size, 1).unwrap_lite();
-    // Before we set our frame, find the region that contains our initial stack
-    // pointer, and zap the region from the base to the stack pointer with a
-    // distinct (and storied) pattern.
-    for region in task.region_table().iter() {
-        if initial_stack < region.base {
-            continue;
-        }
-
-        if initial_stack > region.base + region.size {
-            continue;
-        }
-
-        let mut uslice: USlice<u32> = USlice::from_raw(
-            region.base as usize,
-            (initial_stack as usize - frame_size - region.base as usize) >> 2,
-        )
-        .unwrap_lite();
-
-        let zap = task.try_write(&mut uslice).unwrap_lite();
-        for word in zap.iter_mut() {
-            *word = 0xbaddcafe;
-        }
-    }
+    // Before we set our frame, paint the stack so we can later tell how much
+    // of it the task has used.
+    task.paint_stack();
 
     let descriptor = task.descriptor();
     let frame = &mut task.try_write(&mut frame_uslice).unwrap_lite()[0];
@@ -901,6 +885,10 @@ pub unsafe extern "C" fn SVCall() {
 /// pointer while you have access to `task`, and as long as the `task` being
 /// stored is actually in the task table, you'll be okay.
 pub unsafe fn set_current_task(task: &mut task::Task) {
+    let prev = CURRENT_TASK_PTR.load(Ordering::Relaxed);
+    if prev != task as *mut _ {
+        task.note_switched_in();
+    }
     CURRENT_TASK_PTR.store(task, Ordering::Relaxed);
     crate::profiling::event_context_switch(task as *mut _ as usize);
 }
@@ -935,7 +923,18 @@ static TICKS: [AtomicU32; 2] = {
 #[no_mangle]
 pub unsafe extern "C" fn SysTick() {
     crate::profiling::event_timer_isr_enter();
+
+    let current = CURRENT_TASK_PTR.load(Ordering::Relaxed);
+    uassert!(!current.is_null()); // tick before kernel started?
+
+    // Safety: we're dereferencing the current task pointer, which we're
+    // trusting the rest of this module to maintain correctly.
+    let current = usize::from(unsafe { (*current).descriptor().index });
+
     with_task_table(|tasks| {
+        // Whoever we interrupted gets charged for this tick.
+        tasks[current].charge_tick();
+
         // Load the time before this tick event.
         let t0 = TICKS[0].load(Ordering::Relaxed);
         let t1 = TICKS[1].load(Ordering::Relaxed);
@@ -969,6 +968,143 @@ pub unsafe extern "C" fn SysTick() {
     crate::profiling::event_timer_isr_exit();
 }
 
+/// Largest value that fits in the SysTick reload register.
+const SYST_MAX_RELOAD: u32 = 0x00ff_ffff;
+
+/// SysTick control and status register bit that enables the counter.
+const SYST_CSR_ENABLE: u32 = 1 << 0;
+
+/// System control register bit that makes interrupts becoming pending count
+/// as `WFE` wakeup events, even if they can't preempt.
+const SCB_SCR_SEVONPEND: u32 = 1 << 4;
+
+/// Interrupt control and state register bits that indicate a pending
+/// interrupt: `ISRPENDING` for the NVIC, and `PENDSTSET` for SysTick.
+const SCB_ICSR_ISRPENDING: u32 = 1 << 22;
+const SCB_ICSR_PENDSTSET: u32 = 1 << 26;
+
+/// Sleeps until an interrupt arrives, or until at most `ticks` kernel ticks
+/// have passed, and then brings `TICKS` up to date.
+///
+/// This is called from the `IDLE` syscall when nothing is runnable. If `deep`
+/// names a sleep hook and state, we stop SysTick and let the hook handle it.
+/// Otherwise, we stretch the current SysTick period to cover up to `ticks`
+/// ticks (as far as the 24-bit counter allows) and wait.
+pub fn sleep(
+    ticks: Option<u64>,
+    deep: Option<(&'static crate::sleep::SleepHooks, u32)>,
+) {
+    // Safety: we're manufacturing authority to interact with SysTick and the
+    // SCB, as in `start_first_task`. We can't be preempted by anything else in
+    // the kernel that uses them.
+    let (syst, scb) = unsafe {
+        (
+            &*cortex_m::peripheral::SYST::PTR,
+            &*cortex_m::peripheral::SCB::PTR,
+        )
+    };
+    let divisor = CLOCK_FREQ_KHZ.load(Ordering::Relaxed);
+
+    // If something is already waiting for us, don't bother.
+    let pending = SCB_ICSR_ISRPENDING | SCB_ICSR_PENDSTSET;
+    if scb.icsr.read() & pending != 0 {
+        return;
+    }
+
+    if let Some((hooks, state)) = deep {
+        let limit = ticks.map(|t| t.min(u64::from(u32::MAX)) as u32);
+        // Safety: stopping and restarting the counter has no effect on memory
+        // safety.
+        unsafe {
+            syst.csr.modify(|v| v & !SYST_CSR_ENABLE);
+        }
+        let slept = (hooks.sleep)(state, limit);
+        skip_ticks(u64::from(slept));
+        // We've lost track of where we were within the current tick, so
+        // start a fresh one.
+        unsafe {
+            syst.cvr.write(0);
+            syst.csr.modify(|v| v | SYST_CSR_ENABLE);
+        }
+        return;
+    }
+
+    // Tick boundaries fall wherever the counter passes a multiple of
+    // `divisor`. Reload the counter so that the next SysTick arrives `stretch`
+    // boundaries from now rather than one.
+    let max_stretch = (SYST_MAX_RELOAD + 1) / divisor;
+    let stretch =
+        ticks.map_or(max_stretch, |t| t.min(u64::from(max_stretch)) as u32);
+    if stretch > 1 {
+        let remaining = syst.cvr.read();
+        load_systick(syst, remaining + (stretch - 1) * divisor, divisor);
+    }
+
+    // We're in the SVCall handler, which runs at the same priority as SysTick
+    // and every interrupt. None of them can preempt us, so `WFI` would sleep
+    // through them. Instead, have newly pending interrupts generate events
+    // and wait for one with `WFE`. The first `WFE` clears any stale event,
+    // such as the one left by entering this handler; we then check for an
+    // interrupt that became pending before the events were enabled.
+    //
+    // Safety: this only changes what wakes `WFE`.
+    unsafe {
+        scb.scr.modify(|v| v | SCB_SCR_SEVONPEND);
+    }
+    cortex_m::asm::dsb();
+    cortex_m::asm::sev();
+    cortex_m::asm::wfe();
+    if scb.icsr.read() & pending == 0 {
+        cortex_m::asm::wfe();
+    }
+
+    if stretch > 1 {
+        if scb.icsr.read() & SCB_ICSR_PENDSTSET != 0 {
+            // We slept the whole way. The counter has already reloaded with
+            // the normal period, and the pending SysTick will account for the
+            // final tick.
+            skip_ticks(u64::from(stretch - 1));
+        } else {
+            // Something woke us early. Count the boundaries we passed, and
+            // line the counter back up with the next one. If the counter
+            // wraps while we're doing this, the SysTick it pends accounts
+            // for the boundary, which we then don't count.
+            let count = syst.cvr.read();
+            skip_ticks(u64::from(stretch - 1 - count / divisor));
+            // A reload value of 0 would stop the counter, so never ask for
+            // fewer than 2 cycles; drifting by one is harmless.
+            load_systick(syst, (count % divisor).max(2), divisor);
+        }
+    }
+}
+
+/// Arranges for the next SysTick to arrive `cycles` cycles from now, and
+/// for it to resume its normal period of `divisor` cycles after that.
+fn load_systick(
+    syst: &cortex_m::peripheral::syst::RegisterBlock,
+    cycles: u32,
+    divisor: u32,
+) {
+    // Safety: reprogramming the counter has no effect on memory safety.
+    unsafe {
+        syst.rvr.write(cycles - 1);
+        // Writing any value clears the counter, which then loads the reload
+        // value on the next cycle.
+        syst.cvr.write(0);
+        while syst.cvr.read() == 0 {}
+        // The reload value is only used when the counter wraps, so we can put
+        // the normal one back now.
+        syst.rvr.write(divisor - 1);
+    }
+}
+
+/// Advances `TICKS` by `ticks`, for ticks that passed without a SysTick.
+fn skip_ticks(ticks: u64) {
+    let now = u64::from(now()) + ticks;
+    TICKS[0].store(now as u32, Ordering::Relaxed);
+    TICKS[1].store((now >> 32) as u32, Ordering::Relaxed);
+}
+
 fn pend_context_switch_from_isr() {
     // This sets the bit to pend a PendSV interrupt. PendSV will happen after
     // the current ISR (and any chained ISRs) returns, and perform the context
@@ -1434,6 +1570,16 @@ pub fn reset() -> ! {
     cortex_m::peripheral::SCB::sys_reset()
 }
 
+/// Reads the program counter that `task` will resume at, from the exception
+/// frame on its stack. This returns `None` if the stack pointer doesn't point
+/// at memory the task can read -- after a stack overflow, for instance.
+pub fn saved_pc(task: &task::Task) -> Option<u32> {
+    let frame: USlice<BaseExceptionFrame> =
+        USlice::from_raw(task.save().psp as usize, 1).ok()?;
+    let frame = task.try_read(&frame).ok()?;
+    Some(frame[0].pc)
+}
+
 /// Common implementation of fault handling.
 ///
 /// # Safety
@@ -0,0 +1,225 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Fake architecture support for unit-testing the kernel on the build host.
+//!
+//! This is selected whenever the kernel is compiled with `cfg(test)`. It
+//! doesn't run any tasks; instead, it gives tests direct control over each
+//! task's "registers," so that the portable parts of the kernel (scheduling,
+//! IPC, timers, faults) can be driven one syscall at a time and their effects
+//! inspected.
+//!
+//! Because task memory is described by 32-bit addresses, tests that need to
+//! move data between tasks should obtain it from `task_memory`, which hands
+//! out memory below 2 GiB.
+
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::atomic::AtomicExt;
+use crate::task;
+use crate::time::Timestamp;
+use abi::{RegionAttributes, RegionDesc, TaskDesc, TaskFlags};
+
+macro_rules! uassert {
+    ($cond : expr) => {
+        if !$cond {
+            panic!("Assertion failed!");
+        }
+    };
+}
+
+thread_local! {
+    /// Kernel time as seen by `now`. This is per-thread so that tests can run
+    /// in parallel.
+    static NOW: Cell<u64> = Cell::new(0);
+}
+
+/// Fake "registers" for a task. Tests write syscall arguments into `args` and
+/// `descriptor` and read results back out of `rets`.
+#[derive(Debug, Default)]
+pub struct SavedState {
+    pub args: [u32; 7],
+    pub descriptor: u32,
+    pub rets: [u32; 6],
+}
+
+impl task::ArchState for SavedState {
+    fn stack_pointer(&self) -> u32 {
+        0
+    }
+
+    fn arg0(&self) -> u32 {
+        self.args[0]
+    }
+    fn arg1(&self) -> u32 {
+        self.args[1]
+    }
+    fn arg2(&self) -> u32 {
+        self.args[2]
+    }
+    fn arg3(&self) -> u32 {
+        self.args[3]
+    }
+    fn arg4(&self) -> u32 {
+        self.args[4]
+    }
+    fn arg5(&self) -> u32 {
+        self.args[5]
+    }
+    fn arg6(&self) -> u32 {
+        self.args[6]
+    }
+
+    fn syscall_descriptor(&self) -> u32 {
+        self.descriptor
+    }
+
+    fn ret0(&mut self, x: u32) {
+        self.rets[0] = x
+    }
+    fn ret1(&mut self, x: u32) {
+        self.rets[1] = x
+    }
+    fn ret2(&mut self, x: u32) {
+        self.rets[2] = x
+    }
+    fn ret3(&mut self, x: u32) {
+        self.rets[3] = x
+    }
+    fn ret4(&mut self, x: u32) {
+        self.rets[4] = x
+    }
+    fn ret5(&mut self, x: u32) {
+        self.rets[5] = x
+    }
+}
+
+/// Does nothing, since there's no clock to speak of.
+///
+/// # Safety
+///
+/// This is only unsafe to match the other architectures.
+pub unsafe fn set_clock_freq(_tick_divisor: u32) {}
+
+pub fn reinitialize(task: &mut task::Task) {
+    *task.save_mut() = SavedState::default();
+    task.paint_stack();
+}
+
+pub fn apply_memory_protection(_task: &task::Task) {}
+
+pub fn start_first_task(_tick_divisor: u32, _task: &mut task::Task) -> ! {
+    panic!("tasks can't be started under the fake arch");
+}
+
+/// Does nothing, since there are no real tasks to switch between.
+///
+/// # Safety
+///
+/// This is only unsafe to match the other architectures.
+pub unsafe fn set_current_task(_task: &mut task::Task) {}
+
+/// Reads the fake kernel clock, as set by `set_now`.
+pub fn now() -> Timestamp {
+    Timestamp::from(NOW.with(Cell::get))
+}
+
+/// Sets the fake kernel clock for the calling thread.
+pub fn set_now(now: Timestamp) {
+    NOW.with(|n| n.set(u64::from(now)));
+}
+
+/// Simulates sleeping for `ticks` by advancing the fake kernel clock that far,
+/// as if no interrupt arrived to wake us early. Sleeping indefinitely leaves
+/// the clock alone.
+pub fn sleep(
+    ticks: Option<u64>,
+    _deep: Option<(&'static crate::sleep::SleepHooks, u32)>,
+) {
+    if let Some(ticks) = ticks {
+        set_now(Timestamp::from(u64::from(now()) + ticks));
+    }
+}
+
+pub fn disable_irq(_n: u32) {}
+
+pub fn enable_irq(_n: u32) {}
+
+pub fn reset() -> ! {
+    panic!("kernel reset requested");
+}
+
+/// Fake tasks don't run, so they have no program counter.
+pub fn saved_pc(_task: &task::Task) -> Option<u32> {
+    None
+}
+
+impl AtomicExt for AtomicBool {
+    type Primitive = bool;
+
+    #[inline(always)]
+    fn swap_polyfill(
+        &self,
+        value: Self::Primitive,
+        ordering: Ordering,
+    ) -> Self::Primitive {
+        self.swap(value, ordering)
+    }
+}
+
+/// Allocates `size` bytes of zeroed memory below 2 GiB, where its address can
+/// be expressed in a task's 32-bit registers, and returns a region describing
+/// it with the given attributes. The memory is leaked.
+pub fn task_memory(size: u32, attributes: RegionAttributes) -> RegionDesc {
+    // Safety: we're asking for fresh anonymous memory and not overlaying
+    // anything.
+    let p = unsafe {
+        libc::mmap(
+            core::ptr::null_mut(),
+            size as usize,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_32BIT,
+            -1,
+            0,
+        )
+    };
+    assert_ne!(p, libc::MAP_FAILED);
+    RegionDesc {
+        base: p as usize as u32,
+        size,
+        attributes,
+    }
+}
+
+/// Builds a task table with one task per entry in `priorities`, all runnable,
+/// each of which can access every region in `regions`.
+///
+/// The descriptors and region tables are leaked, since `Task` wants `'static`
+/// references to them.
+pub fn task_table(
+    priorities: &[u8],
+    regions: &[RegionDesc],
+) -> Vec<task::Task> {
+    let regions: &'static [RegionDesc] = Vec::leak(regions.to_vec());
+    let region_table: &'static [&'static RegionDesc] =
+        Vec::leak(regions.iter().collect());
+    priorities
+        .iter()
+        .enumerate()
+        .map(|(index, &priority)| {
+            let desc = Box::leak(Box::new(TaskDesc {
+                regions: [0; abi::REGIONS_PER_TASK],
+                entry_point: 0,
+                initial_stack: 0,
+                priority,
+                timers: 2,
+                async_sends: 2,
+                flags: TaskFlags::START_AT_BOOT,
+                index: index as u16,
+            }));
+            task::Task::from_descriptor(desc, region_table)
+        })
+        .collect()
+}
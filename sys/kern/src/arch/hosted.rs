@@ -0,0 +1,687 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Architecture support for running the kernel as a hosted process on x86_64
+//! Linux.
+//!
+//! This is a simulation target. It exists so that the portable parts of the
+//! kernel -- scheduling, IPC, kipc, timers -- can be booted and exercised in
+//! CI without real silicon. It makes no attempt to run the ARM binaries
+//! produced by a normal build; instead, the simulation harness supplies the
+//! body of each task as a host function (see `configure_task_entries`).
+//!
+//! # Tasks as threads
+//!
+//! Each task runs on its own host thread, but only one thread -- either the
+//! kernel's or that of the current task -- is ever allowed to make progress at
+//! a time. The kernel runs on the thread that called `start_kernel`, in a loop
+//! that receives *events* from a channel:
+//!
+//! - A task thread traps into the kernel by depositing its syscall arguments
+//!   in an `Event::Syscall` and then blocking until it is resumed. This is our
+//!   `SVC`.
+//! - A task thread that has nothing to do (i.e. the idle task) can offer up
+//!   the CPU with `wait_for_interrupt`. This is our `WFI`. The `IDLE` syscall
+//!   has the same effect; ticks keep arriving while we "sleep", so there's no
+//!   time to correct afterwards.
+//! - A dedicated thread produces `Event::Tick` at a fixed period. This is our
+//!   `SysTick`.
+//! - The harness can inject hardware interrupts with `raise_irq`.
+//!
+//! Resuming a task means handing its thread the contents of its return
+//! registers, which it then returns from its syscall. Because the saved state
+//! of a task lives in the task table like on any other architecture, the rest
+//! of the kernel can't tell the difference.
+//!
+//! When a task is reinitialized, its old thread is abandoned. The thread is
+//! woken with an error the next time it would have been resumed and unwinds
+//! quietly, and a fresh thread is spawned when the task is next scheduled.
+//!
+//! # Memory
+//!
+//! The kernel accesses task memory by address, and the addresses in the kernel
+//! configuration are 32-bit. We satisfy this by mapping anonymous memory at
+//! the (low) addresses named by each task's non-device regions when the task
+//! is initialized. Peripheral regions are not mapped, and there is no MPU, so
+//! nothing stops a task thread from scribbling on memory it doesn't own --
+//! but the kernel's own checks on syscall arguments still apply.
+//!
+//! # Limitations
+//!
+//! We can't interrupt a host thread in the middle of whatever it's doing, so
+//! preemption is deferred: if a timer or interrupt makes a more important task
+//! runnable while a task is running, the switch happens at the running task's
+//! next kernel entry. Tasks that spin without making syscalls will hog the
+//! simulated CPU.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::atomic::AtomicExt;
+use crate::startup::with_task_table;
+use crate::task;
+use crate::time::Timestamp;
+use abi::RegionAttributes;
+
+macro_rules! uassert {
+    ($cond : expr) => {
+        if !$cond {
+            panic!("Assertion failed!");
+        }
+    };
+}
+
+/// Like ARM-M, we record the current task pointer in a global.
+static CURRENT_TASK_PTR: AtomicPtr<task::Task> =
+    AtomicPtr::new(core::ptr::null_mut());
+
+/// Host microseconds per kernel tick, as passed to `start_kernel`.
+static TICK_MICROS: AtomicU32 = AtomicU32::new(0);
+
+/// Task bodies supplied by the harness, indexed by task index.
+static TASK_ENTRIES: Mutex<&'static [fn()]> = Mutex::new(&[]);
+
+/// Sending side of the kernel's event channel. This is `None` until the kernel
+/// has started.
+static KERNEL_EVENTS: Mutex<Option<Sender<Event>>> = Mutex::new(None);
+
+/// Pages we've mapped on behalf of task memory regions, kept sorted.
+static MAPPED_PAGES: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// Number of interrupts we simulate. This matches the number of interrupt
+/// enable bits in the ARMv7-M NVIC.
+const IRQ_COUNT: usize = 512;
+
+/// Simulated interrupt enable bits, one per IRQ.
+static IRQ_ENABLED: [AtomicU32; IRQ_COUNT / 32] = {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicU32 = AtomicU32::new(0);
+    [ZERO; IRQ_COUNT / 32]
+};
+
+/// Simulated interrupt pending bits. An interrupt raised while disabled is
+/// latched here and delivered when it's next enabled, as on the NVIC.
+static IRQ_PENDING: [AtomicU32; IRQ_COUNT / 32] = {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicU32 = AtomicU32::new(0);
+    [ZERO; IRQ_COUNT / 32]
+};
+
+/// Set when a tick or interrupt has made a task runnable while another task
+/// was running; processed at the next kernel entry. This is our `PendSV`.
+static SWITCH_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Set by `sleep` when a task asks to sleep through the `IDLE` syscall, so that
+/// the event loop leaves it waiting instead of resuming it.
+static SLEEP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Kernel global for tracking the current timestamp, measured in ticks. This
+/// is kept in the same split form as on ARM-M for parity.
+///
+/// `TICKS[0]` is the least significant part, `TICKS[1]` the most significant.
+static TICKS: [AtomicU32; 2] = {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicU32 = AtomicU32::new(0);
+    [ZERO; 2]
+};
+
+/// Hosted "registers" that must be saved across context switches.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct SavedState {
+    /// Syscall arguments, deposited by the task thread on kernel entry.
+    args: [u32; 7],
+    /// Syscall number.
+    descriptor: u32,
+    /// Syscall results, handed back to the task thread when it resumes.
+    rets: [u32; 6],
+    /// Nominal stack pointer. Task threads run on host stacks, so this only
+    /// records the initial stack from the task's descriptor.
+    sp: u32,
+    /// Incarnation counter, advanced on every `reinitialize`, so that we can
+    /// tell a restarted task's thread from its abandoned predecessor.
+    incarnation: u32,
+}
+
+/// Map the saved state to (architecture-independent) syscall argument and
+/// return slots.
+impl task::ArchState for SavedState {
+    fn stack_pointer(&self) -> u32 {
+        self.sp
+    }
+
+    fn arg0(&self) -> u32 {
+        self.args[0]
+    }
+    fn arg1(&self) -> u32 {
+        self.args[1]
+    }
+    fn arg2(&self) -> u32 {
+        self.args[2]
+    }
+    fn arg3(&self) -> u32 {
+        self.args[3]
+    }
+    fn arg4(&self) -> u32 {
+        self.args[4]
+    }
+    fn arg5(&self) -> u32 {
+        self.args[5]
+    }
+    fn arg6(&self) -> u32 {
+        self.args[6]
+    }
+
+    fn syscall_descriptor(&self) -> u32 {
+        self.descriptor
+    }
+
+    fn ret0(&mut self, x: u32) {
+        self.rets[0] = x
+    }
+    fn ret1(&mut self, x: u32) {
+        self.rets[1] = x
+    }
+    fn ret2(&mut self, x: u32) {
+        self.rets[2] = x
+    }
+    fn ret3(&mut self, x: u32) {
+        self.rets[3] = x
+    }
+    fn ret4(&mut self, x: u32) {
+        self.rets[4] = x
+    }
+    fn ret5(&mut self, x: u32) {
+        self.rets[5] = x
+    }
+}
+
+/// Things that can bring the kernel thread out of its wait.
+enum Event {
+    /// The current task has trapped into the kernel to make a syscall.
+    Syscall {
+        index: usize,
+        nr: u32,
+        args: [u32; 7],
+    },
+    /// The current task has nothing to do until the next interrupt.
+    Idle { index: usize },
+    /// A kernel tick has elapsed.
+    Tick,
+    /// A hardware interrupt has been raised.
+    Irq(u32),
+}
+
+/// Payload used to unwind the thread of a task that has been reinitialized.
+struct Defunct;
+
+/// Per-thread state for task threads.
+struct TaskContext {
+    /// Index of the task this thread is running.
+    index: usize,
+    /// Where the kernel sends our return registers when it resumes us.
+    resume: Receiver<[u32; 6]>,
+}
+
+thread_local! {
+    static TASK_CONTEXT: RefCell<Option<TaskContext>> =
+        const { RefCell::new(None) };
+    static DEFUNCT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Supplies the bodies of tasks, indexed by task index. This must be called
+/// before `start_kernel`.
+///
+/// The `entry_point` recorded in each `TaskDesc` is meaningless on this target
+/// and is ignored. A task body that returns is treated as a panic.
+pub fn configure_task_entries(entries: &'static [fn()]) {
+    *TASK_ENTRIES.lock().unwrap() = entries;
+}
+
+/// Performs syscall `nr` with `args` on behalf of the calling task thread,
+/// returning the contents of the return registers. This is what the syscall
+/// stubs bottom out in on this target.
+///
+/// # Panics
+///
+/// If called from a thread that isn't running a task.
+pub fn syscall(nr: u32, args: [u32; 7]) -> [u32; 6] {
+    let index = current_task_thread_index();
+    send_event(Event::Syscall { index, nr, args });
+    wait_for_resume()
+}
+
+/// Offers up the CPU until the next interrupt or tick, like `WFI`. This is
+/// intended for use by the idle task.
+pub fn wait_for_interrupt() {
+    let index = current_task_thread_index();
+    send_event(Event::Idle { index });
+    wait_for_resume();
+}
+
+/// Raises hardware interrupt `n`, as if a peripheral had asserted it. This can
+/// be called from any thread.
+pub fn raise_irq(n: u32) {
+    send_event(Event::Irq(n));
+}
+
+fn current_task_thread_index() -> usize {
+    if DEFUNCT.with(Cell::get) {
+        // This thread's task has been reinitialized and we're unwinding, but
+        // something on the way out wants to make a syscall. It can't be
+        // allowed to reach the kernel, so, stop here.
+        loop {
+            std::thread::park();
+        }
+    }
+    TASK_CONTEXT.with(|c| {
+        c.borrow()
+            .as_ref()
+            .map(|c| c.index)
+            .expect("kernel entry from non-task thread")
+    })
+}
+
+fn send_event(event: Event) {
+    let events = KERNEL_EVENTS.lock().unwrap().clone();
+    events
+        .expect("kernel not started")
+        .send(event)
+        .expect("kernel thread is gone");
+}
+
+/// Blocks the calling task thread until the kernel resumes it, returning its
+/// return registers. If the task has been reinitialized in the meantime, this
+/// unwinds the thread instead.
+fn wait_for_resume() -> [u32; 6] {
+    let rets = TASK_CONTEXT.with(|c| {
+        c.borrow()
+            .as_ref()
+            .expect("kernel entry from non-task thread")
+            .resume
+            .recv()
+    });
+    match rets {
+        Ok(rets) => rets,
+        Err(_) => {
+            DEFUNCT.with(|d| d.set(true));
+            std::panic::resume_unwind(Box::new(Defunct))
+        }
+    }
+}
+
+/// Records the tick divisor, which is interpreted as host microseconds per
+/// kernel tick. We keep it for symmetry with ARM-M, where debuggers go looking
+/// for it.
+///
+/// # Safety
+///
+/// This is only `unsafe` to match the other architectures; there are no
+/// requirements on the caller here.
+pub unsafe fn set_clock_freq(tick_divisor: u32) {
+    TICK_MICROS.store(tick_divisor, Ordering::Relaxed);
+}
+
+pub fn reinitialize(task: &mut task::Task) {
+    let incarnation = task.save().incarnation.wrapping_add(1);
+    *task.save_mut() = SavedState {
+        incarnation,
+        sp: task.descriptor().initial_stack,
+        ..SavedState::default()
+    };
+
+    // Make sure the memory the kernel may be asked to access on the task's
+    // behalf actually exists.
+    for region in task.region_table().iter() {
+        map_region(region);
+    }
+
+    // Tasks here run on their threads' native stacks, so this won't show any
+    // usage, but it keeps the task's memory looking like it would on hardware.
+    task.paint_stack();
+}
+
+/// Maps anonymous memory covering `region`, if it's normal memory and we
+/// haven't done so already.
+fn map_region(region: &abi::RegionDesc) {
+    const PAGE: usize = 4096;
+
+    if region.attributes.contains(RegionAttributes::DEVICE)
+        || !region.attributes.intersects(
+            RegionAttributes::READ
+                | RegionAttributes::WRITE
+                | RegionAttributes::EXECUTE,
+        )
+    {
+        return;
+    }
+
+    let start = region.base as usize & !(PAGE - 1);
+    let end =
+        (region.base as usize + region.size as usize + PAGE - 1) & !(PAGE - 1);
+
+    let mut mapped = MAPPED_PAGES.lock().unwrap();
+    for page in (start..end).step_by(PAGE) {
+        if let Err(i) = mapped.binary_search(&page) {
+            // Safety: MAP_FIXED_NOREPLACE refuses to replace an existing
+            // mapping, so this can't alias any memory the process is already
+            // using.
+            let p = unsafe {
+                libc::mmap(
+                    page as *mut libc::c_void,
+                    PAGE,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE
+                        | libc::MAP_ANONYMOUS
+                        | libc::MAP_FIXED_NOREPLACE,
+                    -1,
+                    0,
+                )
+            };
+            if p as usize != page {
+                panic!("unable to map task memory at {:#x}", page);
+            }
+            mapped.insert(i, page);
+        }
+    }
+}
+
+/// There's no MPU on this target.
+pub fn apply_memory_protection(_task: &task::Task) {}
+
+pub fn start_first_task(tick_divisor: u32, task: &mut task::Task) -> ! {
+    let (tx, events) = mpsc::channel();
+    *KERNEL_EVENTS.lock().unwrap() = Some(tx.clone());
+
+    let period = Duration::from_micros(u64::from(tick_divisor));
+    std::thread::Builder::new()
+        .name("systick".to_string())
+        .spawn(move || {
+            while tx.send(Event::Tick).is_ok() {
+                std::thread::sleep(period);
+            }
+        })
+        .unwrap();
+
+    CURRENT_TASK_PTR.store(task, Ordering::Relaxed);
+
+    // Threads we've spawned for tasks, by task index, along with the
+    // incarnation they belong to.
+    let mut threads: HashMap<usize, (u32, Sender<[u32; 6]>)> = HashMap::new();
+    // Whether the current task is waiting for an interrupt.
+    let mut idle = false;
+
+    resume_current(&mut threads);
+
+    for event in events {
+        let from_task =
+            matches!(event, Event::Syscall { .. } | Event::Idle { .. });
+        match event {
+            Event::Syscall { index, nr, args } => {
+                let current = CURRENT_TASK_PTR.load(Ordering::Relaxed);
+                // Safety: we're dereferencing the current task pointer, which
+                // we maintain, and no reference to the task table is live.
+                unsafe {
+                    let t = &mut *current;
+                    uassert!(usize::from(t.descriptor().index) == index);
+                    t.save_mut().args = args;
+                    t.save_mut().descriptor = nr;
+                }
+                // Safety: the current task's state has been saved, and we're
+                // not being called reentrantly.
+                unsafe {
+                    crate::syscalls::syscall_entry(nr, current);
+                }
+                if SLEEP_REQUESTED.swap(false, Ordering::Relaxed) {
+                    idle = true;
+                    continue;
+                }
+                if SWITCH_PENDING.swap(false, Ordering::Relaxed) {
+                    reschedule();
+                }
+                resume_current(&mut threads);
+            }
+            Event::Idle { index } => {
+                let current = CURRENT_TASK_PTR.load(Ordering::Relaxed);
+                // Safety: as above.
+                let current =
+                    unsafe { usize::from((*current).descriptor().index) };
+                uassert!(current == index);
+                idle = true;
+            }
+            Event::Tick => {
+                crate::profiling::event_timer_isr_enter();
+                if tick() {
+                    SWITCH_PENDING.store(true, Ordering::Relaxed);
+                }
+                crate::profiling::event_timer_isr_exit();
+            }
+            Event::Irq(n) => {
+                crate::profiling::event_isr_enter();
+                if irq(n) {
+                    SWITCH_PENDING.store(true, Ordering::Relaxed);
+                }
+                crate::profiling::event_isr_exit();
+            }
+        }
+
+        // Like a processor in WFI, an idle task wakes on any event, whether
+        // or not it caused a context switch.
+        if idle && !from_task {
+            idle = false;
+            if SWITCH_PENDING.swap(false, Ordering::Relaxed) {
+                reschedule();
+            }
+            resume_current(&mut threads);
+        }
+    }
+
+    panic!("kernel event channel closed");
+}
+
+/// Hands the current task's return registers to its thread, spawning a new
+/// thread first if the task has been (re)initialized since it last ran.
+fn resume_current(threads: &mut HashMap<usize, (u32, Sender<[u32; 6]>)>) {
+    let current = CURRENT_TASK_PTR.load(Ordering::Relaxed);
+    uassert!(!current.is_null());
+    // Safety: we're dereferencing the current task pointer, which we
+    // maintain, and no reference to the task table is live.
+    let (index, incarnation, rets) = unsafe {
+        let t = &*current;
+        (
+            usize::from(t.descriptor().index),
+            t.save().incarnation,
+            t.save().rets,
+        )
+    };
+
+    let resume = match threads.get(&index) {
+        Some((i, tx)) if *i == incarnation => tx.clone(),
+        _ => {
+            // Replacing the old entry drops the abandoned thread's sender,
+            // which causes it to unwind if it's ever woken.
+            let tx = spawn_task(index);
+            threads.insert(index, (incarnation, tx.clone()));
+            tx
+        }
+    };
+    resume.send(rets).expect("task thread is gone");
+}
+
+/// Spawns a thread to run task `index`. The thread waits to be resumed before
+/// calling into the task body.
+fn spawn_task(index: usize) -> Sender<[u32; 6]> {
+    let entry =
+        *TASK_ENTRIES.lock().unwrap().get(index).unwrap_or_else(|| {
+            panic!("no entry configured for task {}", index)
+        });
+
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name(format!("task{}", index))
+        .spawn(move || {
+            TASK_CONTEXT.with(|c| {
+                *c.borrow_mut() = Some(TaskContext { index, resume: rx })
+            });
+            wait_for_resume();
+            match std::panic::catch_unwind(entry) {
+                Err(e) if e.is::<Defunct>() => return,
+                _ => (),
+            }
+            // The task either panicked or returned from its body, which
+            // would be a fault on real hardware. Either way, tell the kernel.
+            syscall(abi::Sysnum::Panic as u32, [0; 7]);
+        })
+        .unwrap();
+    tx
+}
+
+/// Picks a new task to run, as `PendSV` does on ARM-M.
+fn reschedule() {
+    crate::profiling::event_secondary_syscall_enter();
+
+    let current = CURRENT_TASK_PTR.load(Ordering::Relaxed);
+    uassert!(!current.is_null());
+    // Safety: we're dereferencing the current task pointer, which we're
+    // trusting the rest of this module to maintain correctly.
+    let current = usize::from(unsafe { (*current).descriptor().index });
+
+    with_task_table(|tasks| {
+        let next = task::select(current, tasks);
+        let next = &mut tasks[next];
+        apply_memory_protection(next);
+        // Safety: next comes from the task table and we don't use it again
+        // until next kernel entry, so we meet set_current_task's requirements.
+        unsafe {
+            set_current_task(next);
+        }
+    });
+    crate::profiling::event_secondary_syscall_exit();
+}
+
+/// Advances the kernel's notion of time by one tick and processes timers.
+/// Returns `true` if a context switch is needed.
+fn tick() -> bool {
+    let current = CURRENT_TASK_PTR.load(Ordering::Relaxed);
+    uassert!(!current.is_null());
+    // Safety: we're dereferencing the current task pointer, which we're
+    // trusting the rest of this module to maintain correctly.
+    let current = usize::from(unsafe { (*current).descriptor().index });
+
+    with_task_table(|tasks| {
+        // Whoever was running gets charged for this tick.
+        tasks[current].charge_tick();
+
+        let t0 = TICKS[0].load(Ordering::Relaxed);
+        let t1 = TICKS[1].load(Ordering::Relaxed);
+
+        let (t0, t1) = if let Some(t0p) = t0.checked_add(1) {
+            TICKS[0].store(t0p, Ordering::Relaxed);
+            (t0p, t1)
+        } else {
+            TICKS[0].store(0, Ordering::Relaxed);
+            TICKS[1].store(t1 + 1, Ordering::Relaxed);
+            (0, t1 + 1)
+        };
+
+        let now = Timestamp::from([t0, t1]);
+        task::process_timers(tasks, now) != task::NextTask::Same
+    })
+}
+
+/// Delivers hardware interrupt `n` to its owner, or latches it if it's
+/// disabled. Returns `true` if a context switch is needed.
+fn irq(n: u32) -> bool {
+    let (reg, bit) = ((n / 32) as usize, 1 << (n % 32));
+    if IRQ_ENABLED[reg].load(Ordering::Relaxed) & bit == 0 {
+        IRQ_PENDING[reg].fetch_or(bit, Ordering::Relaxed);
+        return false;
+    }
+
+    let owner = crate::startup::HUBRIS_IRQ_TASK_LOOKUP
+        .get(abi::InterruptNum(n))
+        .unwrap_or_else(|| panic!("unhandled IRQ {}", n));
+
+    with_task_table(|tasks| {
+        disable_irq(n);
+        let n = task::NotificationSet(owner.notification);
+        tasks[owner.task as usize].post(n)
+    })
+}
+
+/// Records the address of `task` as the current user task.
+///
+/// # Safety
+///
+/// This records a pointer that aliases `task`. As long as you don't read that
+/// pointer while you have access to `task`, and as long as the `task` being
+/// stored is actually in the task table, you'll be okay.
+pub unsafe fn set_current_task(task: &mut task::Task) {
+    let prev = CURRENT_TASK_PTR.load(Ordering::Relaxed);
+    if !core::ptr::eq(prev, task) {
+        task.note_switched_in();
+    }
+    CURRENT_TASK_PTR.store(task, Ordering::Relaxed);
+    crate::profiling::event_context_switch(task as *mut _ as usize);
+}
+
+/// Puts the current task to sleep until the next event, as though it had called
+/// `wait_for_interrupt`. The simulated tick keeps running, so `ticks` doesn't
+/// matter, and we have no deeper sleep states to offer.
+pub fn sleep(
+    _ticks: Option<u64>,
+    _deep: Option<(&'static crate::sleep::SleepHooks, u32)>,
+) {
+    SLEEP_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Reads the tick counter.
+pub fn now() -> Timestamp {
+    Timestamp::from([
+        TICKS[0].load(Ordering::Relaxed),
+        TICKS[1].load(Ordering::Relaxed),
+    ])
+}
+
+pub fn disable_irq(n: u32) {
+    let (reg, bit) = ((n / 32) as usize, 1 << (n % 32));
+    IRQ_ENABLED[reg].fetch_and(!bit, Ordering::Relaxed);
+}
+
+pub fn enable_irq(n: u32) {
+    let (reg, bit) = ((n / 32) as usize, 1 << (n % 32));
+    IRQ_ENABLED[reg].fetch_or(bit, Ordering::Relaxed);
+    if IRQ_PENDING[reg].fetch_and(!bit, Ordering::Relaxed) & bit != 0 {
+        // It fired while we weren't looking; deliver it now.
+        send_event(Event::Irq(n));
+    }
+}
+
+/// Resetting the simulated system ends the process. We exit successfully, so
+/// that a supervisor-requested reset doesn't look like a crash.
+pub fn reset() -> ! {
+    std::process::exit(0)
+}
+
+/// Simulated tasks are host threads, whose program counters we can't see.
+pub fn saved_pc(_task: &task::Task) -> Option<u32> {
+    None
+}
+
+impl AtomicExt for AtomicBool {
+    type Primitive = bool;
+
+    #[inline(always)]
+    fn swap_polyfill(
+        &self,
+        value: Self::Primitive,
+        ordering: Ordering,
+    ) -> Self::Primitive {
+        self.swap(value, ordering)
+    }
+}
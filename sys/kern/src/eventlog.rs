@@ -0,0 +1,250 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Kernel event log.
+//!
+//! The kernel keeps a small circular log of the supervisory events that are
+//! most useful after the fact: task faults, restarts, priority changes, and
+//! resets. Tasks read it back, one `abi::KernelEvent` at a time, with the
+//! `ReadEventLog` kipc.
+//!
+//! The log lives in the kernel's `.uninit` section, which startup code doesn't
+//! touch, so it survives warm resets -- including the one requested through
+//! the `Reset` kipc, and those caused by watchdogs. At boot we check the log's
+//! header, and only start a fresh log if the old one is missing or was written
+//! by a different image. Events are stored in their `ssmarshal` encoding and
+//! checked when read back, so a partially corrupted log costs us some events
+//! but can't confuse the kernel.
+//!
+//! The number of entries is set by the `event-log` key in the `[kernel]`
+//! section of `app.toml`. It defaults to zero, which disables the log, because
+//! some images can't spare the RAM.
+
+use abi::{KernelEvent, KernelEventKind};
+
+use crate::startup::{HUBRIS_EVENT_LOG_ENTRIES, HUBRIS_IMAGE_ID};
+
+/// Space reserved for each encoded event. This must be enough for the largest
+/// `KernelEvent`, which is checked by a test below.
+const EVENT_BYTES: usize = 32;
+
+/// Marks a log header as having been written by the kernel.
+const EVENT_LOG_MAGIC: u32 = 0x4556_4c47;
+
+#[repr(C)]
+struct EventLog {
+    /// `EVENT_LOG_MAGIC` if the log has been initialized.
+    magic: u32,
+    /// Sequence number that the next event will receive. The entry for event
+    /// number `n` is `entries[n % HUBRIS_EVENT_LOG_ENTRIES]`.
+    next_seq: u32,
+    /// Image that created the log. Another image might use a different layout
+    /// or encoding.
+    image_id: u64,
+    entries: [[u8; EVENT_BYTES]; HUBRIS_EVENT_LOG_ENTRIES],
+}
+
+impl EventLog {
+    /// Starts a new, empty log. This leaves the old entries in place, since
+    /// they're only read once they've been overwritten with new events.
+    fn clear(&mut self) {
+        self.magic = EVENT_LOG_MAGIC;
+        self.next_seq = 0;
+        self.image_id = image_id();
+    }
+}
+
+/// Reads the image ID. Like the `ReadImageId` kipc, we use a volatile read to
+/// keep the compiler from assuming it knows the value.
+fn image_id() -> u64 {
+    // Safety: this is a valid, aligned reference to a static.
+    unsafe { core::ptr::read_volatile(&HUBRIS_IMAGE_ID) }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(test)] {
+        use core::cell::RefCell;
+
+        thread_local! {
+            /// Unit tests run in parallel, so each gets its own log.
+            static EVENT_LOG: RefCell<EventLog> = RefCell::new(EventLog {
+                magic: 0,
+                next_seq: 0,
+                image_id: 0,
+                entries: [[0; EVENT_BYTES]; HUBRIS_EVENT_LOG_ENTRIES],
+            });
+        }
+
+        fn with_log<R>(body: impl FnOnce(&mut EventLog) -> R) -> R {
+            EVENT_LOG.with(|log| body(&mut log.borrow_mut()))
+        }
+    } else {
+        use core::mem::MaybeUninit;
+
+        /// The log itself. This is `no_mangle` so that debuggers can find it.
+        ///
+        /// Kernel entry points don't preempt one another, so every access to
+        /// this happens from one non-reentrant context at a time.
+        #[no_mangle]
+        #[cfg_attr(
+            target_os = "none",
+            link_section = ".uninit.hubris_event_log"
+        )]
+        static mut HUBRIS_EVENT_LOG: MaybeUninit<EventLog> =
+            MaybeUninit::uninit();
+
+        fn with_log<R>(body: impl FnOnce(&mut EventLog) -> R) -> R {
+            // Safety: see the comment on `HUBRIS_EVENT_LOG` for why this
+            // reference is unique. `EventLog` consists entirely of integers,
+            // so whatever the last boot left in RAM is a valid value, if not
+            // necessarily a meaningful one -- which `init` checks.
+            let log = unsafe {
+                (*core::ptr::addr_of_mut!(HUBRIS_EVENT_LOG)).assume_init_mut()
+            };
+            body(log)
+        }
+    }
+}
+
+/// Checks the log left by the previous boot, if any, starting a new one if
+/// it's unusable, and records a `Boot` event. This must be called once at
+/// startup, before anything else in this module.
+pub(crate) fn init() {
+    with_log(|log| {
+        if log.magic != EVENT_LOG_MAGIC || log.image_id != image_id() {
+            log.clear();
+        }
+    });
+    record(KernelEventKind::Boot);
+}
+
+/// Appends an event to the log, overwriting the oldest event if it's full.
+pub(crate) fn record(kind: KernelEventKind) {
+    if HUBRIS_EVENT_LOG_ENTRIES == 0 {
+        return;
+    }
+    let timestamp = u64::from(crate::arch::now());
+    with_log(|log| {
+        let event = KernelEvent {
+            seq: log.next_seq,
+            timestamp,
+            kind,
+        };
+        let entry = &mut log.entries[slot(log.next_seq)];
+        // This can only fail if EVENT_BYTES is too small, which the tests
+        // guard against; there's nothing better to do than drop the event.
+        if ssmarshal::serialize(entry, &event).is_ok() {
+            log.next_seq = log.next_seq.wrapping_add(1);
+        }
+    })
+}
+
+/// Maps sequence number `seq` to its slot in the log.
+fn slot(seq: u32) -> usize {
+    // The divisor is clamped so that images with the log turned off still
+    // compile: a constant modulus of zero is a hard error, even in code that
+    // the `HUBRIS_EVENT_LOG_ENTRIES == 0` checks make unreachable.
+    seq as usize % HUBRIS_EVENT_LOG_ENTRIES.max(1)
+}
+
+/// Returns the oldest event still in the log whose sequence number is at least
+/// `seq`, if any.
+pub(crate) fn read(seq: u32) -> Option<KernelEvent> {
+    if HUBRIS_EVENT_LOG_ENTRIES == 0 {
+        return None;
+    }
+    with_log(|log| {
+        let oldest =
+            log.next_seq.saturating_sub(HUBRIS_EVENT_LOG_ENTRIES as u32);
+        (seq.max(oldest)..log.next_seq).find_map(|n| {
+            let entry = &log.entries[slot(n)];
+            match ssmarshal::deserialize::<KernelEvent>(entry) {
+                Ok((event, _)) if event.seq == n => Some(event),
+                // Skip entries that have been damaged somehow.
+                _ => None,
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::{FaultInfo, FaultSource, TaskId};
+
+    /// Discards whatever log this thread has, and starts a new one.
+    fn fresh_log() {
+        with_log(|log| log.magic = 0);
+        init();
+    }
+
+    #[test]
+    fn largest_event_fits() {
+        let event = KernelEvent {
+            seq: !0,
+            timestamp: !0,
+            kind: KernelEventKind::Fault {
+                task: TaskId(!0),
+                fault: FaultInfo::MemoryAccess {
+                    address: Some(!0),
+                    source: FaultSource::Kernel,
+                },
+                pc: Some(!0),
+            },
+        };
+        let mut buf = [0; EVENT_BYTES];
+        assert!(ssmarshal::serialize(&mut buf, &event).is_ok());
+    }
+
+    #[test]
+    fn log_wraps_keeping_newest_events() {
+        fresh_log();
+        for i in 0..HUBRIS_EVENT_LOG_ENTRIES as u8 + 2 {
+            record(KernelEventKind::SetPriority {
+                task: TaskId(1),
+                priority: i,
+            });
+        }
+
+        // The boot event and the first two priority changes have been
+        // overwritten, so asking from the start gets the oldest survivor.
+        let first = read(0).unwrap();
+        assert_eq!(first.seq, 3);
+        assert_eq!(
+            first.kind,
+            KernelEventKind::SetPriority {
+                task: TaskId(1),
+                priority: 2,
+            }
+        );
+
+        let last = HUBRIS_EVENT_LOG_ENTRIES as u32 + 2;
+        assert_eq!(read(last).unwrap().seq, last);
+        assert_eq!(read(last + 1), None);
+    }
+
+    #[test]
+    fn damaged_entries_are_skipped() {
+        fresh_log();
+        record(KernelEventKind::Reset { by: TaskId(0) });
+        with_log(|log| log.entries[0] = [0xFF; EVENT_BYTES]);
+
+        assert_eq!(read(0).unwrap().seq, 1);
+    }
+
+    #[test]
+    fn init_keeps_log_from_same_image() {
+        fresh_log();
+        record(KernelEventKind::Reset { by: TaskId(0) });
+        init();
+        assert_eq!(read(0).unwrap().kind, KernelEventKind::Boot);
+        assert_eq!(read(2).unwrap().kind, KernelEventKind::Boot);
+
+        with_log(|log| log.image_id = !image_id());
+        init();
+        let first = read(0).unwrap();
+        assert_eq!((first.seq, first.kind), (0, KernelEventKind::Boot));
+        assert_eq!(read(1), None);
+    }
+}
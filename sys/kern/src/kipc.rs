@@ -4,12 +4,15 @@
 
 //! Implementation of IPC operations on the virtual kernel task.
 
-use abi::{FaultInfo, Kipcnum, SchedState, TaskState, UsageError};
+use abi::{
+    FaultInfo, KernelEventKind, Kipcnum, Priority, SchedState, TaskState,
+    UsageError,
+};
 
 use crate::arch;
-use crate::err::UserError;
+use crate::err::{InteractFault, UserError};
 use crate::task::{current_id, ArchState, NextTask, Task};
-use crate::umem::USlice;
+use crate::umem::{safe_copy, USlice};
 use core::convert::TryFrom;
 
 /// Message dispatcher.
@@ -30,6 +33,24 @@ pub fn handle_kernel_message(
             read_image_id(tasks, caller, args.response?)
         }
         Ok(Kipcnum::Reset) => reset(tasks, caller, args.message?),
+        Ok(Kipcnum::ReadTaskPriority) => {
+            read_task_priority(tasks, caller, args.message?, args.response?)
+        }
+        Ok(Kipcnum::SetTaskPriority) => {
+            set_task_priority(tasks, caller, args.message?)
+        }
+        Ok(Kipcnum::ReadTaskRuntime) => {
+            read_task_runtime(tasks, caller, args.message?, args.response?)
+        }
+        Ok(Kipcnum::ReadTaskStackUsage) => {
+            read_task_stack_usage(tasks, caller, args.message?, args.response?)
+        }
+        Ok(Kipcnum::ReadEventLog) => {
+            read_event_log(tasks, caller, args.message?, args.response?)
+        }
+        Ok(Kipcnum::ReadPanicMessage) => {
+            read_panic_message(tasks, caller, args.message?, args.response?)
+        }
         _ => {
             // Task has sent an unknown message to the kernel. That's bad.
             Err(UserError::Unrecoverable(FaultInfo::SyscallUsage(
@@ -38,7 +59,10 @@ pub fn handle_kernel_message(
         }
     }
 }
-fn reset(_tasks: &mut [Task], _caller: usize, _message: USlice<u8>) -> ! {
+fn reset(tasks: &mut [Task], caller: usize, _message: USlice<u8>) -> ! {
+    crate::eventlog::record(KernelEventKind::Reset {
+        by: current_id(tasks, caller),
+    });
     arch::reset()
 }
 
@@ -112,6 +136,10 @@ fn restart_task(
     }
     let old_id = current_id(tasks, index);
     tasks[index].reinitialize();
+    crate::eventlog::record(KernelEventKind::Restart {
+        task: current_id(tasks, index),
+        by: current_id(tasks, caller),
+    });
     if start {
         tasks[index].set_healthy_state(SchedState::Runnable);
     }
@@ -146,6 +174,11 @@ fn restart_task(
                 }
                 _ => (),
             }
+
+            // Async sends to the defunct task get the same news, by way of
+            // their notifications.
+            let code = abi::dead_response_code(old_id.generation());
+            let _ = task.abort_async_sends_to(old_id, code);
         }
     }
 
@@ -211,3 +244,390 @@ fn read_image_id(
         .set_send_response_and_length(0, response_len);
     Ok(NextTask::Same)
 }
+
+/// Checks that `caller` is the supervisor, for operations that are reserved to
+/// it.
+fn require_supervisor(caller: usize) -> Result<(), UserError> {
+    if caller != 0 {
+        return Err(UserError::Unrecoverable(FaultInfo::SyscallUsage(
+            UsageError::NotSupervisor,
+        )));
+    }
+    Ok(())
+}
+
+fn read_task_priority(
+    tasks: &mut [Task],
+    caller: usize,
+    message: USlice<u8>,
+    response: USlice<u8>,
+) -> Result<NextTask, UserError> {
+    require_supervisor(caller)?;
+
+    let index: u32 = deserialize_message(&tasks[caller], message)?;
+    let index = index as usize;
+    if index >= tasks.len() {
+        return Err(UserError::Unrecoverable(FaultInfo::SyscallUsage(
+            UsageError::TaskOutOfRange,
+        )));
+    }
+    let priority = tasks[index].priority().0;
+
+    let response_len =
+        serialize_response(&mut tasks[caller], response, &priority)?;
+    tasks[caller]
+        .save_mut()
+        .set_send_response_and_length(0, response_len);
+    Ok(NextTask::Same)
+}
+
+///
+/// Changes the priority of a task until it is next restarted. This is intended
+/// to let the supervisor demote a misbehaving task rather than kill it; the
+/// task's original priority from the image is restored by `reinitialize`.
+///
+fn set_task_priority(
+    tasks: &mut [Task],
+    caller: usize,
+    message: USlice<u8>,
+) -> Result<NextTask, UserError> {
+    require_supervisor(caller)?;
+
+    let (index, priority): (u32, u8) =
+        deserialize_message(&tasks[caller], message)?;
+    let index = index as usize;
+    if index >= tasks.len() {
+        return Err(UserError::Unrecoverable(FaultInfo::SyscallUsage(
+            UsageError::TaskOutOfRange,
+        )));
+    }
+
+    tasks[index].set_priority(Priority(priority));
+    crate::eventlog::record(KernelEventKind::SetPriority {
+        task: current_id(tasks, index),
+        priority,
+    });
+    tasks[caller].save_mut().set_send_response_and_length(0, 0);
+
+    // If the caller is no longer the most important runnable task -- because
+    // it has promoted someone above itself, or demoted itself -- we need to
+    // reschedule.
+    let caller_priority = tasks[caller].priority();
+    let preempted = tasks.iter().any(|t| {
+        t.is_runnable() && t.priority().is_more_important_than(caller_priority)
+    });
+    if preempted {
+        Ok(NextTask::Other)
+    } else {
+        Ok(NextTask::Same)
+    }
+}
+
+fn read_task_runtime(
+    tasks: &mut [Task],
+    caller: usize,
+    message: USlice<u8>,
+    response: USlice<u8>,
+) -> Result<NextTask, UserError> {
+    let index: u32 = deserialize_message(&tasks[caller], message)?;
+    if index as usize >= tasks.len() {
+        return Err(UserError::Unrecoverable(FaultInfo::SyscallUsage(
+            UsageError::TaskOutOfRange,
+        )));
+    }
+    let runtime = *tasks[index as usize].runtime();
+
+    let response_len =
+        serialize_response(&mut tasks[caller], response, &runtime)?;
+    tasks[caller]
+        .save_mut()
+        .set_send_response_and_length(0, response_len);
+    Ok(NextTask::Same)
+}
+
+fn read_task_stack_usage(
+    tasks: &mut [Task],
+    caller: usize,
+    message: USlice<u8>,
+    response: USlice<u8>,
+) -> Result<NextTask, UserError> {
+    let index: u32 = deserialize_message(&tasks[caller], message)?;
+    if index as usize >= tasks.len() {
+        return Err(UserError::Unrecoverable(FaultInfo::SyscallUsage(
+            UsageError::TaskOutOfRange,
+        )));
+    }
+    let usage = tasks[index as usize].stack_usage();
+
+    let response_len =
+        serialize_response(&mut tasks[caller], response, &usage)?;
+    tasks[caller]
+        .save_mut()
+        .set_send_response_and_length(0, response_len);
+    Ok(NextTask::Same)
+}
+
+/// Reads the oldest entry in the kernel event log whose sequence number is at
+/// least the one given, responding with `None` if there isn't one yet.
+fn read_event_log(
+    tasks: &mut [Task],
+    caller: usize,
+    message: USlice<u8>,
+    response: USlice<u8>,
+) -> Result<NextTask, UserError> {
+    let seq: u32 = deserialize_message(&tasks[caller], message)?;
+    let event = crate::eventlog::read(seq);
+
+    let response_len =
+        serialize_response(&mut tasks[caller], response, &event)?;
+    tasks[caller]
+        .save_mut()
+        .set_send_response_and_length(0, response_len);
+    Ok(NextTask::Same)
+}
+
+/// Copies the message that a task passed to the `PANIC` syscall into the
+/// response buffer, truncating it if necessary. This only works until the task
+/// is restarted; for any task that isn't currently faulted by a panic, the
+/// response is empty.
+fn read_panic_message(
+    tasks: &mut [Task],
+    caller: usize,
+    message: USlice<u8>,
+    response: USlice<u8>,
+) -> Result<NextTask, UserError> {
+    require_supervisor(caller)?;
+
+    let index: u32 = deserialize_message(&tasks[caller], message)?;
+    let index = index as usize;
+    if index >= tasks.len() {
+        return Err(UserError::Unrecoverable(FaultInfo::SyscallUsage(
+            UsageError::TaskOutOfRange,
+        )));
+    }
+
+    let panicked = matches!(
+        tasks[index].state(),
+        TaskState::Faulted {
+            fault: FaultInfo::Panic,
+            ..
+        }
+    );
+    let panic_message = tasks[index].save().as_panic_args().message;
+    let response_len = match panic_message {
+        Ok(panic_message) if panicked && index != caller => {
+            match safe_copy(tasks, index, panic_message, caller, response) {
+                Ok(n) => n,
+                // The caller gave us a bad response buffer, which is its
+                // problem.
+                Err(InteractFault { dst: Some(f), .. }) => return Err(f.into()),
+                // The panicking task gave the kernel a bad message, which
+                // isn't the caller's problem; it just doesn't get one.
+                Err(_) => 0,
+            }
+        }
+        _ => 0,
+    };
+    tasks[caller]
+        .save_mut()
+        .set_send_response_and_length(0, response_len);
+    Ok(NextTask::Same)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arch::{task_memory, task_table};
+    use abi::{RegionAttributes, RegionDesc, TaskId};
+
+    fn setup() -> (Vec<Task>, RegionDesc) {
+        let mem =
+            task_memory(4096, RegionAttributes::READ | RegionAttributes::WRITE);
+        let tasks = task_table(&[0, 1, 2], core::slice::from_ref(&mem));
+        (tasks, mem)
+    }
+
+    /// Has `caller` send `message` to the kernel as operation `op`, with a
+    /// response buffer in `mem`. Returns the scheduling hint and the response.
+    fn kipc<T: serde::Serialize>(
+        tasks: &mut [Task],
+        mem: &RegionDesc,
+        caller: usize,
+        op: Kipcnum,
+        message: &T,
+    ) -> Result<(NextTask, Vec<u8>), UserError> {
+        let msg = mem.base;
+        let resp = mem.base + 0x100;
+        // Safety: `mem` comes from `task_memory` and is ours to scribble on.
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(msg as usize as *mut u8, 0x100)
+        };
+        let len = ssmarshal::serialize(buf, message).unwrap();
+
+        tasks[caller].save_mut().args = [
+            u32::from(TaskId::KERNEL.0) << 16 | op as u32,
+            msg,
+            len as u32,
+            resp,
+            0x100,
+            4,
+            0,
+        ];
+        let hint = handle_kernel_message(tasks, caller)?;
+        let rets = tasks[caller].save().rets;
+        assert_eq!(rets[0], 0);
+        // Safety: as above.
+        let response = unsafe {
+            core::slice::from_raw_parts(
+                resp as usize as *const u8,
+                rets[1] as usize,
+            )
+        };
+        Ok((hint, response.to_vec()))
+    }
+
+    #[test]
+    fn priority_change_lasts_until_restart() {
+        let (mut tasks, mem) = setup();
+
+        let (hint, _) =
+            kipc(&mut tasks, &mem, 0, Kipcnum::SetTaskPriority, &(2u32, 5u8))
+                .unwrap();
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[2].priority(), Priority(5));
+
+        let (_, resp) =
+            kipc(&mut tasks, &mem, 0, Kipcnum::ReadTaskPriority, &2u32)
+                .unwrap();
+        assert_eq!(resp, [5]);
+
+        tasks[2].reinitialize();
+        assert_eq!(tasks[2].priority(), Priority(2));
+    }
+
+    #[test]
+    fn priority_change_can_preempt_supervisor() {
+        let (mut tasks, mem) = setup();
+        // Demoting ourselves below a runnable task must cause a switch.
+        let (hint, _) =
+            kipc(&mut tasks, &mem, 0, Kipcnum::SetTaskPriority, &(0u32, 3u8))
+                .unwrap();
+        assert_eq!(hint, NextTask::Other);
+    }
+
+    #[test]
+    fn priority_kipcs_are_supervisor_only() {
+        let (mut tasks, mem) = setup();
+        for op in [Kipcnum::ReadTaskPriority, Kipcnum::SetTaskPriority] {
+            match kipc(&mut tasks, &mem, 1, op, &(2u32, 0u8)) {
+                Err(UserError::Unrecoverable(FaultInfo::SyscallUsage(
+                    UsageError::NotSupervisor,
+                ))) => (),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+        assert_eq!(tasks[2].priority(), Priority(2));
+    }
+
+    #[test]
+    fn priority_kipcs_check_task_index() {
+        let (mut tasks, mem) = setup();
+        match kipc(&mut tasks, &mem, 0, Kipcnum::SetTaskPriority, &(3u32, 0u8))
+        {
+            Err(UserError::Unrecoverable(FaultInfo::SyscallUsage(
+                UsageError::TaskOutOfRange,
+            ))) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn event_log_records_fault_and_restart() {
+        let (mut tasks, mem) = setup();
+        let faulted = current_id(&tasks, 2);
+        let _ = crate::task::force_fault(&mut tasks, 2, FaultInfo::Panic);
+        let _ = kipc(&mut tasks, &mem, 0, Kipcnum::RestartTask, &(2u32, true))
+            .unwrap();
+
+        // Walk the log to its end, as a supervisor would.
+        let mut seq = 0u32;
+        let mut events = vec![];
+        loop {
+            let (hint, resp) =
+                kipc(&mut tasks, &mem, 1, Kipcnum::ReadEventLog, &seq).unwrap();
+            assert_eq!(hint, NextTask::Same);
+            let (event, _): (Option<abi::KernelEvent>, _) =
+                ssmarshal::deserialize(&resp).unwrap();
+            match event {
+                Some(e) => {
+                    seq = e.seq + 1;
+                    events.push(e.kind);
+                }
+                None => break,
+            }
+        }
+
+        assert_eq!(
+            events[events.len() - 2..],
+            [
+                KernelEventKind::Fault {
+                    task: faulted,
+                    fault: FaultInfo::Panic,
+                    pc: None,
+                },
+                KernelEventKind::Restart {
+                    task: faulted.next_generation(),
+                    by: current_id(&tasks, 0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn panic_message_readable_until_restart() {
+        let (mut tasks, mem) = setup();
+        let msg = mem.base + 0x200;
+        // Safety: `mem` comes from `task_memory` and is ours to scribble on.
+        unsafe {
+            core::slice::from_raw_parts_mut(msg as usize as *mut u8, 5)
+                .copy_from_slice(b"oh no");
+        }
+        tasks[2].save_mut().args[..2].copy_from_slice(&[msg, 5]);
+        let _ = crate::task::force_fault(&mut tasks, 2, FaultInfo::Panic);
+
+        let (hint, resp) =
+            kipc(&mut tasks, &mem, 0, Kipcnum::ReadPanicMessage, &2u32)
+                .unwrap();
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(resp, b"oh no");
+
+        tasks[2].reinitialize();
+        let (_, resp) =
+            kipc(&mut tasks, &mem, 0, Kipcnum::ReadPanicMessage, &2u32)
+                .unwrap();
+        assert_eq!(resp, b"");
+    }
+
+    #[test]
+    fn read_task_runtime_reports_counters() {
+        let (mut tasks, mem) = setup();
+        tasks[2].charge_tick();
+        tasks[2].charge_tick();
+        tasks[2].note_switched_in();
+        // Counters survive restarts.
+        tasks[2].reinitialize();
+
+        let (hint, resp) =
+            kipc(&mut tasks, &mem, 1, Kipcnum::ReadTaskRuntime, &2u32).unwrap();
+        assert_eq!(hint, NextTask::Same);
+        let (runtime, _): (abi::TaskRuntime, _) =
+            ssmarshal::deserialize(&resp).unwrap();
+        assert_eq!(
+            runtime,
+            abi::TaskRuntime {
+                ticks: 2,
+                context_switches: 1,
+            }
+        );
+    }
+}
@@ -39,8 +39,10 @@ pub mod arch;
 
 pub mod atomic;
 pub mod err;
+pub mod eventlog;
 pub mod kipc;
 pub mod profiling;
+pub mod sleep;
 pub mod startup;
 pub mod syscalls;
 pub mod task;
@@ -21,6 +21,11 @@
 //! are of course possible, but be careful of probe effect and keep the handler
 //! functions fast.
 //!
+//! For targets without spare GPIOs or a logic analyzer at hand, the `trace`
+//! submodule provides a portable implementation that records events into a
+//! circular buffer in kernel RAM; see `trace::configure_trace`. The buffer can
+//! be dumped with a debugger and decoded with `cargo xtask trace`.
+//!
 //! # Interpreting task numbers
 //!
 //! To impose minimum overhead on the kernel itself, the kernel gives the
@@ -43,9 +48,15 @@
 //! 2. Determine the base address of the task array (`HUBRIS_TASK_TABLE_SPACE`).
 //! 3. Compute the code corresponding to each task index as
 //!    `(HUBRIS_TASK_TABLE_SPACE + index * size) >> 4 & PINS_EXPOSED`.
+//!
+//! The `trace` backend uses the same encoding, but also records the size of
+//! `Task` and the address of the task table in the buffer's header, so that
+//! `cargo xtask trace` can do this decoding for you.
 
 use core::sync::atomic::{AtomicPtr, Ordering};
 
+pub mod trace;
+
 /// Hooks that must be provided by the board setup code if it wants to enable
 /// kernel profiling.
 ///
@@ -129,12 +140,16 @@ pub(crate) fn event_syscall_exit() {
     }
 }
 
+// Several of these hooks are only called from the real architecture backends,
+// not the fake one that unit tests use, so they're left out of test builds.
+#[cfg(not(test))]
 pub(crate) fn event_secondary_syscall_enter() {
     if let Some(t) = table() {
         (t.secondary_syscall_enter)()
     }
 }
 
+#[cfg(not(test))]
 pub(crate) fn event_secondary_syscall_exit() {
     if let Some(t) = table() {
         (t.secondary_syscall_exit)()
@@ -157,18 +172,21 @@ pub fn event_isr_exit() {
     }
 }
 
+#[cfg(not(test))]
 pub(crate) fn event_timer_isr_enter() {
     if let Some(t) = table() {
         (t.timer_isr_enter)()
     }
 }
 
+#[cfg(not(test))]
 pub(crate) fn event_timer_isr_exit() {
     if let Some(t) = table() {
         (t.timer_isr_exit)()
     }
 }
 
+#[cfg(not(test))]
 pub(crate) fn event_context_switch(tcb: usize) {
     if let Some(t) = table() {
         (t.context_switch)(tcb)
@@ -0,0 +1,112 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Portable profiling backend that records events into RAM.
+//!
+//! This implements every hook in `EventsTable` by appending a timestamped
+//! `abi::TraceRecord` to a fixed-size circular buffer, the
+//! `HUBRIS_KERNEL_TRACE` symbol. The buffer begins with an `abi::TraceHeader`
+//! describing the task table, so that a dump of the buffer (taken with a
+//! debugger, say) is self-contained and can be turned into a timeline with
+//! `cargo xtask trace`.
+//!
+//! To use it, call `configure_trace` from your startup routine before calling
+//! `start_kernel`. The buffer costs about 1.5 KiB of kernel RAM, which is only
+//! linked into images that call `configure_trace`.
+
+use super::EventsTable;
+use abi::{TraceEvent, TraceHeader, TraceRecord, TRACE_MAGIC};
+
+/// Number of records in the trace buffer. This must be a power of two, so that
+/// the record index stays consistent when the count wraps.
+const TRACE_CAPACITY: usize = 128;
+
+#[repr(C)]
+struct TraceBuffer {
+    header: TraceHeader,
+    records: [TraceRecord; TRACE_CAPACITY],
+}
+
+const EMPTY_RECORD: TraceRecord = TraceRecord {
+    timestamp: 0,
+    event: 0,
+    arg: 0,
+};
+
+/// The trace buffer itself. This is `no_mangle` so that tools can find it.
+///
+/// Kernel entry points don't preempt one another -- the kernel configures
+/// interrupt priorities to ensure this -- so every access to this happens
+/// from one non-reentrant context at a time.
+#[no_mangle]
+static mut HUBRIS_KERNEL_TRACE: TraceBuffer = TraceBuffer {
+    header: TraceHeader {
+        magic: 0,
+        task_table: 0,
+        task_size: 0,
+        capacity: 0,
+        count: 0,
+    },
+    records: [EMPTY_RECORD; TRACE_CAPACITY],
+};
+
+/// Clock used to timestamp records. Written only by `configure_trace`.
+static mut TRACE_CLOCK: fn() -> u32 = kernel_ticks;
+
+static TRACE_EVENTS: EventsTable = EventsTable {
+    syscall_enter: |nr| record(TraceEvent::SyscallEnter, nr),
+    syscall_exit: || record(TraceEvent::SyscallExit, 0),
+    secondary_syscall_enter: || record(TraceEvent::SecondarySyscallEnter, 0),
+    secondary_syscall_exit: || record(TraceEvent::SecondarySyscallExit, 0),
+    isr_enter: || record(TraceEvent::IsrEnter, 0),
+    isr_exit: || record(TraceEvent::IsrExit, 0),
+    timer_isr_enter: || record(TraceEvent::TimerIsrEnter, 0),
+    timer_isr_exit: || record(TraceEvent::TimerIsrExit, 0),
+    context_switch: |tcb| record(TraceEvent::ContextSwitch, (tcb >> 4) as u32),
+};
+
+/// Enables the RAM trace backend, timestamping records using `clock`.
+///
+/// `clock` is called on every event, so it should be cheap; a free-running
+/// cycle counter is ideal. If the target doesn't have one, `kernel_ticks` will
+/// do, at the cost of resolution.
+///
+/// This replaces any previously configured `EventsTable`, and must be called
+/// before `start_kernel`.
+pub fn configure_trace(clock: fn() -> u32) {
+    // Safety: we're called before the kernel starts, so nothing can be
+    // recording events concurrently.
+    unsafe {
+        TRACE_CLOCK = clock;
+        HUBRIS_KERNEL_TRACE.header = TraceHeader {
+            magic: TRACE_MAGIC,
+            task_table: crate::startup::task_table_addr() as u32,
+            task_size: core::mem::size_of::<crate::task::Task>() as u32,
+            capacity: TRACE_CAPACITY as u32,
+            count: 0,
+        };
+    }
+    super::configure_events_table(&TRACE_EVENTS);
+}
+
+/// Returns the low 32 bits of the kernel's tick count, for use as a trace
+/// clock.
+pub fn kernel_ticks() -> u32 {
+    u64::from(crate::arch::now()) as u32
+}
+
+fn record(event: TraceEvent, arg: u32) {
+    // Safety: see the comment on `HUBRIS_KERNEL_TRACE` -- we can't be
+    // preempted by another kernel entry point that might also be recording.
+    unsafe {
+        let trace = &mut *core::ptr::addr_of_mut!(HUBRIS_KERNEL_TRACE);
+        let i = trace.header.count as usize % TRACE_CAPACITY;
+        trace.records[i] = TraceRecord {
+            timestamp: TRACE_CLOCK(),
+            event: event as u32,
+            arg,
+        };
+        trace.header.count = trace.header.count.wrapping_add(1);
+    }
+}
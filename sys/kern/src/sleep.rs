@@ -0,0 +1,61 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Support for sleeping through idle periods.
+//!
+//! When the idle task has nothing better to do, it can make the `IDLE`
+//! syscall instead of simply waiting for an interrupt. If no other task is
+//! runnable, the kernel works out how long it can afford to sleep -- until the
+//! earliest armed timer or IPC deadline -- and stops taking periodic ticks for
+//! that long. When the processor wakes, for whatever reason, the kernel
+//! corrects its timestamp to account for the ticks it skipped.
+//!
+//! By itself this only avoids waking up for ticks that wouldn't have done
+//! anything. Deeper sleep states, in which the tick timer may stop, are
+//! chip-specific. The idle task requests one by passing a nonzero _sleep
+//! state_ to `IDLE`, and the board setup code provides a `SleepHooks` struct
+//! that knows how to enter it, by calling `configure_sleep_hooks` from its
+//! startup routine. Without hooks, every sleep state is treated like state 0.
+
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Hooks that can be provided by the board setup code to support chip-specific
+/// low-power states.
+pub struct SleepHooks {
+    /// Called, with the kernel's tick timer stopped, to enter sleep state
+    /// `state` (which is never 0). The hook must arrange to wake no more than
+    /// `ticks` kernel ticks later -- or whenever an interrupt arrives, if
+    /// `ticks` is `None` -- and return the number of whole ticks that elapsed
+    /// while asleep, which the kernel adds to its timestamp.
+    ///
+    /// The hook is also responsible for restoring any clocks it changes before
+    /// returning.
+    pub sleep: fn(state: u32, ticks: Option<u32>) -> u32,
+}
+
+/// Supplies the kernel with sleep hooks.
+pub fn configure_sleep_hooks(hooks: &'static SleepHooks) {
+    SLEEP_HOOKS.store(hooks as *const _ as *mut _, Ordering::Relaxed);
+}
+
+/// Internal pointer written by `configure_sleep_hooks` and read by `hooks`. If
+/// this is null, no hooks have been provided.
+///
+/// As with the profiling events table, any non-null pointed-to struct has
+/// static scope, and we use `Relaxed` ordering because this is written once at
+/// startup.
+static SLEEP_HOOKS: AtomicPtr<SleepHooks> =
+    AtomicPtr::new(core::ptr::null_mut());
+
+/// Grabs a reference to the configured hooks, if any.
+pub(crate) fn hooks() -> Option<&'static SleepHooks> {
+    let p = SLEEP_HOOKS.load(Ordering::Relaxed);
+    if p.is_null() {
+        None
+    } else {
+        // We only write this pointer from a valid `&'static`, and we're handing
+        // out a shared reference, so this should be ok...
+        unsafe { Some(&*p) }
+    }
+}
@@ -46,6 +46,10 @@ pub unsafe fn start_kernel(tick_divisor: u32) -> ! {
         crate::arch::set_clock_freq(tick_divisor);
     }
 
+    // Pick up the event log left by the last boot, if any, and note that
+    // we're starting up.
+    crate::eventlog::init();
+
     // Grab references to all our statics.
     let task_descs = &HUBRIS_TASK_DESCS;
     let region_descs = &HUBRIS_REGION_DESCS;
@@ -145,4 +149,12 @@ pub(crate) fn with_task_table<R>(body: impl FnOnce(&mut [Task]) -> R) -> R {
     r
 }
 
+/// Returns the address of the task table, for tools that need to map a `Task`
+/// pointer back to its index.
+pub(crate) fn task_table_addr() -> usize {
+    // Safety: we're only taking the address, not producing a reference, so
+    // this can't alias anything.
+    unsafe { core::ptr::addr_of!(HUBRIS_TASK_TABLE_SPACE) as usize }
+}
+
 include!(concat!(env!("OUT_DIR"), "/kconfig.rs"));
@@ -100,18 +100,30 @@ fn safe_syscall_entry(nr: u32, current: usize, tasks: &mut [Task]) -> NextTask {
         Ok(Sysnum::Send) => send(tasks, current),
         Ok(Sysnum::Recv) => recv(tasks, current).map_err(UserError::from),
         Ok(Sysnum::Reply) => reply(tasks, current).map_err(UserError::from),
-        Ok(Sysnum::SetTimer) => Ok(set_timer(&mut tasks[current], arch::now())),
+        Ok(Sysnum::SetTimer) => set_timer(&mut tasks[current], arch::now()),
         Ok(Sysnum::BorrowRead) => borrow_read(tasks, current),
         Ok(Sysnum::BorrowWrite) => borrow_write(tasks, current),
         Ok(Sysnum::BorrowInfo) => borrow_info(tasks, current),
         Ok(Sysnum::IrqControl) => irq_control(tasks, current),
         Ok(Sysnum::Panic) => explicit_panic(tasks, current),
-        Ok(Sysnum::GetTimer) => Ok(get_timer(&mut tasks[current], arch::now())),
+        Ok(Sysnum::GetTimer) => get_timer(&mut tasks[current], arch::now()),
         Ok(Sysnum::RefreshTaskId) => refresh_task_id(tasks, current),
         Ok(Sysnum::Post) => post(tasks, current),
         Ok(Sysnum::ReplyFault) => {
             reply_fault(tasks, current).map_err(UserError::from)
         }
+        Ok(Sysnum::SetSendDeadline) => {
+            Ok(set_send_deadline(&mut tasks[current]))
+        }
+        Ok(Sysnum::Idle) => Ok(idle(tasks, current)),
+        Ok(Sysnum::BorrowReadv) => {
+            borrow_vectored(tasks, current, BorrowDir::Read)
+        }
+        Ok(Sysnum::BorrowWritev) => {
+            borrow_vectored(tasks, current, BorrowDir::Write)
+        }
+        Ok(Sysnum::SetSendAsync) => Ok(set_send_async(&mut tasks[current])),
+        Ok(Sysnum::CollectAsync) => collect_async(&mut tasks[current]),
         Err(_) => {
             // Bogus syscall number! That's a fault.
             Err(FaultInfo::SyscallUsage(UsageError::BadSyscallNumber).into())
@@ -140,9 +152,11 @@ fn send(tasks: &mut [Task], caller: usize) -> Result<NextTask, UserError> {
     // Extract callee.
     let callee_id = tasks[caller].save().as_send_args().callee;
 
-    // Check IPC filter - TODO
-    // Open question: should out-of-range task IDs be handled by faulting below,
-    // or by failing the IPC filter? Either condition will fault...
+    // Any deadline armed with SET_SEND_DEADLINE applies to this send alone,
+    // as does any async send slot armed with SET_SEND_ASYNC.
+    let deadline = tasks[caller].take_send_deadline();
+    tasks[caller].set_ipc_deadline(deadline);
+    let async_slot = tasks[caller].take_send_async();
 
     // Route kernel messages.
     if callee_id == TaskId::KERNEL {
@@ -152,6 +166,27 @@ fn send(tasks: &mut [Task], caller: usize) -> Result<NextTask, UserError> {
     // Verify the given callee ID, converting it into a table index on success.
     let callee = task::check_task_id_against_table(tasks, callee_id)?;
 
+    // Check that the application config lets us send this message.
+    let operation = tasks[caller].save().as_send_args().operation;
+    if !task::ipc_permitted(
+        &crate::startup::HUBRIS_IPC_ACLS,
+        caller,
+        callee,
+        operation,
+    ) {
+        return Err(FaultInfo::SyscallUsage(UsageError::IpcNotPermitted).into());
+    }
+
+    // If we already have an async send outstanding with the callee, it would
+    // have no way to tell the two apart when replying.
+    if tasks[caller].has_async_send_with(callee_id) {
+        return Err(FaultInfo::SyscallUsage(UsageError::SendInProgress).into());
+    }
+
+    if let Some((slot, notification)) = async_slot {
+        return send_async(tasks, caller, callee, slot, notification);
+    }
+
     // Check for ready peer.
     let mut next_task = NextTask::Same;
     let caller_id = current_id(tasks, caller);
@@ -179,13 +214,78 @@ fn send(tasks: &mut [Task], caller: usize) -> Result<NextTask, UserError> {
     }
 
     // Caller needs to block sending, callee is either busy or
-    // faulted.
+    // faulted...unless its deadline has already passed.
+    if deadline_passed(deadline) {
+        return Err(UserError::Recoverable(abi::TIMED_OUT, next_task));
+    }
     tasks[caller].set_healthy_state(SchedState::InSend(callee_id));
     // We may not know what task to run next, but we're pretty sure it isn't the
     // caller.
     Ok(NextTask::Other.combine(next_task))
 }
 
+/// Implementation of asynchronous SEND, for when the caller has armed an async
+/// send slot with SET_SEND_ASYNC.
+///
+/// Rather than blocking the caller, this records the message in the slot and
+/// returns immediately. The message is delivered to `callee` as if the caller
+/// were blocked in SEND, and when the callee replies, the kernel records the
+/// result in the slot and posts the notification to the caller.
+fn send_async(
+    tasks: &mut [Task],
+    caller: usize,
+    callee: usize,
+    slot: usize,
+    notification: task::NotificationSet,
+) -> Result<NextTask, UserError> {
+    let args = tasks[caller].save().as_send_args();
+
+    // We won't have the caller's registers around to check later, so insist
+    // on valid slices now.
+    if let (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) =
+        (&args.message, &args.response, &args.lease_table)
+    {
+        return Err(FaultInfo::SyscallUsage(*e).into());
+    }
+    tasks[caller]
+        .start_async_send(slot, notification, args)
+        .map_err(FaultInfo::SyscallUsage)?;
+
+    // Deadlines don't apply to async sends, and the caller learns right away
+    // that its message has been queued.
+    tasks[caller].set_ipc_deadline(None);
+    tasks[caller].save_mut().set_send_response_and_length(0, 0);
+
+    let mut next_task = NextTask::Same;
+    let caller_id = current_id(tasks, caller);
+    if tasks[callee].state().can_accept_message_from(caller_id) {
+        match deliver(tasks, caller, callee) {
+            Ok(_) => {
+                // The caller is still runnable, so only switch to the callee
+                // if it's more important.
+                let caller_p = tasks[caller].priority();
+                if tasks[callee].priority().is_more_important_than(caller_p) {
+                    next_task = NextTask::Specific(callee);
+                }
+            }
+            Err(interact) => {
+                // Delivery failed because of fault events in one or both
+                // tasks. If the callee is to blame, its message stays queued
+                // until it's restarted.
+                next_task = interact.apply_to_dst(tasks, callee)?;
+            }
+        }
+    }
+    Ok(next_task)
+}
+
+/// Checks whether `sender` has a message waiting for `callee`, either blocked
+/// in SEND or queued in an async send slot.
+fn has_message_for(sender: &Task, callee: TaskId) -> bool {
+    sender.state().is_sending_to(callee)
+        || sender.queued_async_send_to(callee).is_some()
+}
+
 /// Implementation of the RECV IPC primitive.
 ///
 /// `caller` is a valid task index (i.e. not directly from user code).
@@ -194,6 +294,9 @@ fn send(tasks: &mut [Task], caller: usize) -> Result<NextTask, UserError> {
 ///
 /// If `caller` is out of range for `tasks`.
 fn recv(tasks: &mut [Task], caller: usize) -> Result<NextTask, UserError> {
+    let args = tasks[caller].save().as_recv_args();
+    tasks[caller].set_ipc_deadline(args.deadline);
+
     // We allow tasks to atomically replace their notification mask at each
     // receive. We simultaneously find out if there are notifications pending.
     if let Some(firing) = tasks[caller].take_notifications() {
@@ -210,7 +313,7 @@ fn recv(tasks: &mut [Task], caller: usize) -> Result<NextTask, UserError> {
 
     let caller_id = current_id(tasks, caller);
 
-    let specific_sender = tasks[caller].save().as_recv_args().specific_sender;
+    let specific_sender = args.specific_sender;
 
     let mut next_task = NextTask::Same; // update if we wake tasks
 
@@ -227,7 +330,7 @@ fn recv(tasks: &mut [Task], caller: usize) -> Result<NextTask, UserError> {
         // First possibility: that task you're asking about is DEAD.
         let sender_idx = task::check_task_id_against_table(tasks, sender_id)?;
         // Second possibility: task has a message for us.
-        if tasks[sender_idx].state().is_sending_to(caller_id) {
+        if has_message_for(&tasks[sender_idx], caller_id) {
             // Oh hello sender!
             match deliver(tasks, sender_idx, caller) {
                 Ok(_) => {
@@ -263,9 +366,9 @@ fn recv(tasks: &mut [Task], caller: usize) -> Result<NextTask, UserError> {
         let mut last = caller; // keep track of scan position.
 
         // Is anyone blocked waiting to send to us?
-        while let Some(sender) = task::priority_scan(last, tasks, |t| {
-            t.state().is_sending_to(caller_id)
-        }) {
+        while let Some(sender) =
+            task::priority_scan(last, tasks, |t| has_message_for(t, caller_id))
+        {
             // Oh hello sender!
             match deliver(tasks, sender, caller) {
                 Ok(_) => {
@@ -288,7 +391,11 @@ fn recv(tasks: &mut [Task], caller: usize) -> Result<NextTask, UserError> {
         }
     }
 
-    // No notifications, nobody waiting to send -- block the caller.
+    // No notifications, nobody waiting to send -- block the caller, unless
+    // it's already out of time.
+    if deadline_passed(args.deadline) {
+        return Err(UserError::Recoverable(abi::TIMED_OUT, next_task));
+    }
     tasks[caller].set_healthy_state(SchedState::InRecv(specific_sender));
     // We may not know what task should run next, but we're pretty sure it's not
     // the one we just blocked.
@@ -317,14 +424,19 @@ fn reply(tasks: &mut [Task], caller: usize) -> Result<NextTask, FaultInfo> {
         Ok(x) => x,
     };
 
-    if tasks[callee].state()
-        != &TaskState::Healthy(SchedState::InReply(caller_id))
-    {
-        // Huh. The target task is off doing something else. This can happen if
-        // application-specific supervisory logic unblocks it before we've had a
-        // chance to reply (e.g. to implement timeouts).
-        return Ok(NextTask::Same);
-    }
+    // Work out whether the target is blocked waiting for us, or has an async
+    // send awaiting our reply.
+    let slot = match waiting_for_reply(&tasks[callee], caller_id) {
+        Some(slot) => slot,
+        None => {
+            // Huh. The target task is off doing something else. This can
+            // happen if application-specific supervisory logic unblocks it
+            // before we've had a chance to reply, if its SEND timed out, or
+            // if it cancelled an async send.
+            tasks[callee].take_abandoned_reply(caller_id);
+            return Ok(NextTask::Same);
+        }
+    };
 
     // Deliver the reply. Note that we can't use `deliver`, which is
     // specific to a pair of tasks that are sending and receiving,
@@ -343,7 +455,10 @@ fn reply(tasks: &mut [Task], caller: usize) -> Result<NextTask, FaultInfo> {
 
     // Collect information about the callee's reply buffer. This, too, is
     // somewhere we can read infallibly.
-    let send_args = tasks[callee].save().as_send_args();
+    let send_args = match slot {
+        Some(slot) => tasks[callee].async_send_args(slot),
+        None => tasks[callee].save().as_send_args(),
+    };
     let dest_slice = match send_args.response {
         Ok(buffer) => buffer,
         Err(e) => {
@@ -376,6 +491,22 @@ fn reply(tasks: &mut [Task], caller: usize) -> Result<NextTask, FaultInfo> {
         }
     };
 
+    if let Some(slot) = slot {
+        // The sender wasn't blocked, so it finds out by notification. We're
+        // not assuming anything about its priority this time, since it may
+        // well have been sending to several servers.
+        let woke = tasks[callee].complete_async_send(
+            slot,
+            reply_args.response_code,
+            amount_copied,
+        );
+        let caller_p = tasks[caller].priority();
+        if woke && tasks[callee].priority().is_more_important_than(caller_p) {
+            return Ok(NextTask::Specific(callee));
+        }
+        return Ok(NextTask::Same);
+    }
+
     tasks[callee]
         .save_mut()
         .set_send_response_and_length(reply_args.response_code, amount_copied);
@@ -388,30 +519,106 @@ fn reply(tasks: &mut [Task], caller: usize) -> Result<NextTask, FaultInfo> {
 }
 
 /// Implementation of the `SET_TIMER` syscall.
-fn set_timer(task: &mut Task, now: Timestamp) -> NextTask {
+fn set_timer(task: &mut Task, now: Timestamp) -> Result<NextTask, UserError> {
     let args = task.save().as_set_timer_args();
+    if args.timer >= task.timer_count() {
+        return Err(FaultInfo::SyscallUsage(UsageError::TimerOutOfRange).into());
+    }
     if let Some(deadline) = args.deadline {
         // timer is being enabled
         if deadline <= now {
             // timer is already expired
-            task.set_timer(None, args.notification);
+            task.set_timer(args.timer, None, args.notification);
             // We don't care if we woke the task, because it's already running!
             let _ = task.post(args.notification);
-            return NextTask::Same;
+            return Ok(NextTask::Same);
         }
     }
-    task.set_timer(args.deadline, args.notification);
+    task.set_timer(args.timer, args.deadline, args.notification);
+    Ok(NextTask::Same)
+}
+
+/// Implementation of the `SET_SEND_DEADLINE` syscall.
+fn set_send_deadline(task: &mut Task) -> NextTask {
+    let args = task.save().as_set_send_deadline_args();
+    task.set_send_deadline(args.deadline);
     NextTask::Same
 }
 
+/// Implementation of the `SET_SEND_ASYNC` syscall.
+///
+/// The slot isn't checked until it's used, by the next SEND.
+fn set_send_async(task: &mut Task) -> NextTask {
+    let args = task.save().as_set_send_async_args();
+    task.set_send_async(args.slot, args.notification);
+    NextTask::Same
+}
+
+/// Implementation of the `COLLECT_ASYNC` syscall.
+fn collect_async(task: &mut Task) -> Result<NextTask, UserError> {
+    let args = task.save().as_collect_async_args();
+    let (status, code, len) = task
+        .collect_async_send(args.slot, args.cancel)
+        .map_err(FaultInfo::SyscallUsage)?;
+    task.save_mut().set_collect_async_result(status, code, len);
+    Ok(NextTask::Same)
+}
+
+/// Implementation of the `IDLE` syscall.
+///
+/// This only sleeps if no other task is runnable, so it's harmless (if
+/// pointless) for tasks other than the idle task to call it.
+fn idle(tasks: &mut [Task], caller: usize) -> NextTask {
+    let args = tasks[caller].save().as_idle_args();
+    let others_runnable = tasks
+        .iter()
+        .enumerate()
+        .any(|(i, task)| i != caller && task.is_runnable());
+    if others_runnable {
+        return NextTask::Other;
+    }
+
+    let start = arch::now();
+    let ticks = task::next_deadline(tasks)
+        .map(|d| u64::from(d).saturating_sub(u64::from(start)));
+    if ticks != Some(0) {
+        // State 0 is ordinary sleep, which the architecture handles itself.
+        // Anything deeper needs the board's help, if it's offered any.
+        let deep = match crate::sleep::hooks() {
+            Some(hooks) if args.state != 0 => Some((hooks, args.state)),
+            _ => None,
+        };
+        arch::sleep(ticks, deep);
+    }
+
+    // The ticks we slept through weren't charged to anyone by the tick
+    // handler, so charge them to the task that asked to sleep. Anything that
+    // came due in the meantime needs processing now, since those ticks won't
+    // be replayed.
+    let now = arch::now();
+    tasks[caller].charge_ticks(u64::from(now) - u64::from(start));
+    task::process_timers(tasks, now)
+}
+
+/// Checks whether an IPC deadline, if there is one, has already passed.
+fn deadline_passed(deadline: Option<Timestamp>) -> bool {
+    match deadline {
+        Some(deadline) => deadline <= arch::now(),
+        None => false,
+    }
+}
+
 /// Implementation of the `GET_TIMER` syscall.
-fn get_timer(task: &mut Task, now: Timestamp) -> NextTask {
-    // This syscall takes no arguments.
+fn get_timer(task: &mut Task, now: Timestamp) -> Result<NextTask, UserError> {
+    let args = task.save().as_get_timer_args();
+    if args.timer >= task.timer_count() {
+        return Err(FaultInfo::SyscallUsage(UsageError::TimerOutOfRange).into());
+    }
 
-    let (dl, n) = task.timer();
+    let (dl, n) = task.timer(args.timer);
 
     task.save_mut().set_time_result(now, dl, n);
-    NextTask::Same
+    Ok(NextTask::Same)
 }
 
 fn borrow_read(
@@ -424,37 +631,19 @@ fn borrow_read(
 
     let lender = task::check_task_id_against_table(tasks, args.lender)?;
 
-    let lease =
-        borrow_lease(tasks, caller, lender, args.lease_number, args.offset)?;
-
-    // Does the lease grant us the ability to read from the memory?
-    if !lease.attributes.contains(LeaseAttributes::READ) {
-        // Lease is not readable. Defecting lender.
-        return Err(UserError::Recoverable(abi::DEFECT, NextTask::Same));
-    }
-
-    let leased_area = USlice::from(&lease);
-
-    // Note: we do not explicitly check that the lender has access to
-    // `leased_area` because `safe_copy` will do it.
-
-    // Okay, goodness! We're finally getting close!
-    let copy_result = safe_copy(tasks, lender, leased_area, caller, buffer);
-
-    match copy_result {
-        Ok(n) => {
-            // Copy succeeded!
-            tasks[caller]
-                .save_mut()
-                .set_borrow_response_and_length(0, n);
-            Ok(NextTask::Same)
-        }
-        Err(interact) => {
-            let wake_hint = interact.apply_to_src(tasks, lender)?;
-            // Copy failed but not our side, report defecting lender.
-            Err(UserError::Recoverable(abi::DEFECT, wake_hint))
-        }
-    }
+    let n = borrow_copy(
+        tasks,
+        caller,
+        lender,
+        args.lease_number,
+        args.offset,
+        buffer,
+        BorrowDir::Read,
+    )?;
+    tasks[caller]
+        .save_mut()
+        .set_borrow_response_and_length(0, n);
+    Ok(NextTask::Same)
 }
 
 fn borrow_write(
@@ -467,12 +656,94 @@ fn borrow_write(
 
     let lender = task::check_task_id_against_table(tasks, args.lender)?;
 
-    let lease =
-        borrow_lease(tasks, caller, lender, args.lease_number, args.offset)?;
+    let n = borrow_copy(
+        tasks,
+        caller,
+        lender,
+        args.lease_number,
+        args.offset,
+        buffer,
+        BorrowDir::Write,
+    )?;
+    tasks[caller]
+        .save_mut()
+        .set_borrow_response_and_length(0, n);
+    Ok(NextTask::Same)
+}
+
+/// Implementation of both `BORROW_READV` and `BORROW_WRITEV`, which work
+/// through a list of `BorrowSegment`s in order, as though each were a separate
+/// `BORROW_READ` or `BORROW_WRITE`.
+///
+/// If a segment fails, the borrower sees the same outcome as it would for a
+/// single borrow -- but any segments before it have already been copied.
+fn borrow_vectored(
+    tasks: &mut [Task],
+    caller: usize,
+    dir: BorrowDir,
+) -> Result<NextTask, UserError> {
+    // Collect parameters from caller.
+    let args = tasks[caller].save().as_borrowv_args();
+    let segments = args.segments?;
+
+    let lender = task::check_task_id_against_table(tasks, args.lender)?;
+
+    let mut total = 0;
+    for i in 0..segments.len() {
+        // Re-read the segment table each time around, since we need our
+        // borrow of `tasks` back to do the copy. It's in the caller's memory,
+        // so if it's not readable that's on them.
+        let segment = tasks[caller].try_read(&segments)?[i];
+        let buffer = USlice::from_raw(
+            segment.base_address as usize,
+            segment.length as usize,
+        )?;
+        total += borrow_copy(
+            tasks,
+            caller,
+            lender,
+            segment.lease_number as usize,
+            segment.offset as usize,
+            buffer,
+            dir,
+        )?;
+    }
+
+    tasks[caller]
+        .save_mut()
+        .set_borrow_response_and_length(0, total);
+    Ok(NextTask::Same)
+}
+
+/// Which way a borrow copies data, from the borrower's perspective.
+#[derive(Copy, Clone, Debug)]
+enum BorrowDir {
+    /// Copy out of the lease into the borrower.
+    Read,
+    /// Copy from the borrower into the lease.
+    Write,
+}
 
-    // Does the lease grant us the ability to write to the memory?
-    if !lease.attributes.contains(LeaseAttributes::WRITE) {
-        // Lease is not readable. Defecting lender.
+/// Copies between `buffer` in the caller and lease `lease_number` (starting
+/// at `offset`) of `lender`, returning the number of bytes copied.
+fn borrow_copy(
+    tasks: &mut [Task],
+    caller: usize,
+    lender: usize,
+    lease_number: usize,
+    offset: usize,
+    buffer: USlice<u8>,
+    dir: BorrowDir,
+) -> Result<usize, UserError> {
+    let lease = borrow_lease(tasks, caller, lender, lease_number, offset)?;
+
+    // Does the lease grant us the ability to access the memory this way?
+    let needed = match dir {
+        BorrowDir::Read => LeaseAttributes::READ,
+        BorrowDir::Write => LeaseAttributes::WRITE,
+    };
+    if !lease.attributes.contains(needed) {
+        // Lease doesn't allow it. Defecting lender.
         return Err(UserError::Recoverable(abi::DEFECT, NextTask::Same));
     }
 
@@ -482,22 +753,23 @@ fn borrow_write(
     // `leased_area` because `safe_copy` will do it.
 
     // Okay, goodness! We're finally getting close!
-    let copy_result = safe_copy(tasks, caller, buffer, lender, leased_area);
-
-    match copy_result {
-        Ok(n) => {
-            // Copy succeeded!
-            tasks[caller]
-                .save_mut()
-                .set_borrow_response_and_length(0, n);
-            Ok(NextTask::Same)
+    let copy_result = match dir {
+        BorrowDir::Read => {
+            safe_copy(tasks, lender, leased_area, caller, buffer)
         }
-        Err(interact) => {
-            let wake_hint = interact.apply_to_dst(tasks, lender)?;
-            // Copy failed but not our side, report defecting lender.
-            Err(UserError::Recoverable(abi::DEFECT, wake_hint))
+        BorrowDir::Write => {
+            safe_copy(tasks, caller, buffer, lender, leased_area)
         }
-    }
+    };
+
+    copy_result.or_else(|interact| {
+        let wake_hint = match dir {
+            BorrowDir::Read => interact.apply_to_src(tasks, lender)?,
+            BorrowDir::Write => interact.apply_to_dst(tasks, lender)?,
+        };
+        // Copy failed but not our side, report defecting lender.
+        Err(UserError::Recoverable(abi::DEFECT, wake_hint))
+    })
 }
 
 fn borrow_info(
@@ -527,15 +799,15 @@ fn borrow_lease(
     let caller_id = current_id(tasks, caller);
 
     // Check state of lender and range of lease table.
-    if tasks[lender].state()
-        != &TaskState::Healthy(SchedState::InReply(caller_id))
-    {
-        // The alleged lender isn't lending anything at all.
-        // Let's assume this is a defecting lender.
-        return Err(UserError::Recoverable(abi::DEFECT, NextTask::Same));
-    }
-
-    let largs = tasks[lender].save().as_send_args();
+    let largs = match waiting_for_reply(&tasks[lender], caller_id) {
+        Some(Some(slot)) => tasks[lender].async_send_args(slot),
+        Some(None) => tasks[lender].save().as_send_args(),
+        None => {
+            // The alleged lender isn't lending anything at all.
+            // Let's assume this is a defecting lender.
+            return Err(UserError::Recoverable(abi::DEFECT, NextTask::Same));
+        }
+    };
     let leases = match largs.lease_table {
         Ok(t) => t,
         Err(e) => {
@@ -588,6 +860,23 @@ fn borrow_lease(
     }
 }
 
+/// Checks whether `task` is waiting for a reply from `server`. If so, returns
+/// `Some(None)` if it's blocked in REPLY, or `Some(Some(slot))` if it's waiting
+/// in an async send slot.
+///
+/// While `server` still owes a reply to a message that `task` gave up on when
+/// its SEND timed out, `task` isn't waiting for anything from `server`, even if
+/// it has since sent it another message.
+fn waiting_for_reply(task: &Task, server: TaskId) -> Option<Option<usize>> {
+    if task.owes_abandoned_reply(server) {
+        None
+    } else if task.state() == &TaskState::Healthy(SchedState::InReply(server)) {
+        Some(None)
+    } else {
+        task.async_send_in_reply_to(server).map(Some)
+    }
+}
+
 /// Performs the architecture-specific bookkeeping to activate `task` on next
 /// return to user. This should be done "on our way out" to user code, toward
 /// the end of the syscall routine.
@@ -629,8 +918,9 @@ unsafe fn switch_to(task: &mut Task) {
 ///
 /// Preconditions:
 ///
-/// - Caller is sending -- either blocked in state `InSend`, or in the
-///   process of transitioning from `Runnable` to `InReply`.
+/// - Caller is sending -- either blocked in state `InSend`, in the process of
+///   transitioning from `Runnable` to `InReply`, or with an async send queued
+///   for callee.
 /// - Callee is receiving -- either blocked in `InRecv` or in `Runnable`
 ///   executing a receive system call.
 ///
@@ -651,11 +941,18 @@ fn deliver(
     callee: usize,
 ) -> Result<(), InteractFault> {
     let caller_id = task::current_id(tasks, caller);
+    let callee_id = current_id(tasks, callee);
 
     // Collect information on the send from the caller. This information is all
     // stored in infallibly-readable areas, but our accesses can fail if the
-    // caller handed us bogus slices.
-    let send_args = tasks[caller].save().as_send_args();
+    // caller handed us bogus slices. It comes from an async send slot if the
+    // caller has one queued for callee -- `send` makes sure it can't also be
+    // blocked sending to callee -- and from its registers otherwise.
+    let slot = tasks[caller].queued_async_send_to(callee_id);
+    let send_args = match slot {
+        Some(slot) => tasks[caller].async_send_args(slot),
+        None => tasks[caller].save().as_send_args(),
+    };
     let src_slice = send_args.message.map_err(InteractFault::in_src)?;
     let response_capacity =
         send_args.response.map_err(InteractFault::in_src)?.len();
@@ -678,8 +975,10 @@ fn deliver(
         lease_count,
     );
 
-    let callee_id = current_id(tasks, callee);
-    tasks[caller].set_healthy_state(SchedState::InReply(callee_id));
+    match slot {
+        Some(slot) => tasks[caller].set_async_send_delivered(slot),
+        None => tasks[caller].set_healthy_state(SchedState::InReply(callee_id)),
+    }
     tasks[callee].set_healthy_state(SchedState::Runnable);
     // We don't have an opinion about the newly runnable task, nor do we
     // have enough information to insist that a switch must happen.
@@ -797,12 +1096,11 @@ fn reply_fault(
         Ok(x) => x,
     };
 
-    if tasks[callee].state()
-        != &TaskState::Healthy(SchedState::InReply(caller_id))
-    {
+    if waiting_for_reply(&tasks[callee], caller_id).is_none() {
         // Huh. The target task is off doing something else. This can happen if
         // application-specific supervisory logic unblocks it before we've had a
-        // chance to reply (e.g. to implement timeouts).
+        // chance to reply, or if its SEND timed out.
+        tasks[callee].take_abandoned_reply(caller_id);
         return Ok(NextTask::Same);
     }
 
@@ -819,3 +1117,1220 @@ fn reply_fault(
     // the task using it faults.
     Ok(NextTask::Same)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arch::{task_memory, task_table};
+    use abi::{
+        BorrowSegment, Generation, RegionAttributes, RegionDesc,
+        ReplyFaultReason,
+    };
+
+    /// Index of the supervisor in test task tables.
+    const SUPER: usize = 0;
+    /// Index of the server in test task tables.
+    const SERVER: usize = 1;
+    /// Indices of two clients in test task tables.
+    const CLIENT_A: usize = 2;
+    const CLIENT_B: usize = 3;
+
+    /// An empty slice, for syscall arguments we don't care about. Like the
+    /// slices userlib produces for `&[]`, this has a non-null, aligned base.
+    const EMPTY: (u32, u32) = (4, 0);
+
+    /// Builds a table of a supervisor, a server, and two clients, all of which
+    /// share a single region of read-write memory, which is also returned.
+    fn setup() -> (Vec<Task>, RegionDesc) {
+        let mem =
+            task_memory(4096, RegionAttributes::READ | RegionAttributes::WRITE);
+        let mut tasks = task_table(&[0, 1, 3, 2], core::slice::from_ref(&mem));
+        // Have the supervisor wait for faults, like Jefe does.
+        tasks[SUPER].save_mut().args[2] =
+            crate::startup::HUBRIS_FAULT_NOTIFICATION;
+        tasks[SUPER].set_healthy_state(SchedState::InRecv(None));
+        (tasks, mem)
+    }
+
+    fn syscall(
+        tasks: &mut [Task],
+        caller: usize,
+        nr: Sysnum,
+        args: [u32; 7],
+    ) -> NextTask {
+        let nr = nr as u32;
+        let save = tasks[caller].save_mut();
+        save.args = args;
+        save.descriptor = nr;
+        safe_syscall_entry(nr, caller, tasks)
+    }
+
+    fn id(tasks: &[Task], index: usize) -> TaskId {
+        current_id(tasks, index)
+    }
+
+    fn send_args(
+        callee: TaskId,
+        op: u16,
+        msg: (u32, u32),
+        resp: (u32, u32),
+        leases: (u32, u32),
+    ) -> [u32; 7] {
+        [
+            u32::from(callee.0) << 16 | u32::from(op),
+            msg.0,
+            msg.1,
+            resp.0,
+            resp.1,
+            leases.0,
+            leases.1,
+        ]
+    }
+
+    fn recv_args(buf: (u32, u32), mask: u32, from: Option<TaskId>) -> [u32; 7] {
+        let from = from.map(|t| 1 << 31 | u32::from(t.0)).unwrap_or(0);
+        [buf.0, buf.1, mask, from, 0, 0, 0]
+    }
+
+    fn poke(addr: u32, bytes: &[u8]) {
+        // Safety: tests only poke at memory from `task_memory`.
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                addr as usize as *mut u8,
+                bytes.len(),
+            )
+        }
+    }
+
+    fn peek(addr: u32, len: usize) -> Vec<u8> {
+        // Safety: tests only peek at memory from `task_memory`.
+        unsafe {
+            core::slice::from_raw_parts(addr as usize as *const u8, len)
+                .to_vec()
+        }
+    }
+
+    fn fault_of(task: &Task) -> Option<FaultInfo> {
+        match task.state() {
+            TaskState::Faulted { fault, .. } => Some(*fault),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn send_to_waiting_server_delivers() {
+        let (mut tasks, mem) = setup();
+        let buf = (mem.base, 16);
+        let msg = mem.base + 0x100;
+        let resp = mem.base + 0x200;
+        poke(msg, b"hello");
+
+        let hint =
+            syscall(&mut tasks, SERVER, Sysnum::Recv, recv_args(buf, 0, None));
+        assert_eq!(hint, NextTask::Other);
+        assert_eq!(
+            tasks[SERVER].state(),
+            &TaskState::Healthy(SchedState::InRecv(None))
+        );
+
+        let server = id(&tasks, SERVER);
+        let hint = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 7, (msg, 5), (resp, 8), EMPTY),
+        );
+        assert_eq!(hint, NextTask::Specific(SERVER));
+        assert!(tasks[SERVER].is_runnable());
+        assert_eq!(
+            tasks[SERVER].save().rets,
+            [0, u32::from(id(&tasks, CLIENT_A).0), 7, 5, 8, 0]
+        );
+        assert_eq!(peek(mem.base, 5), b"hello");
+        assert_eq!(
+            tasks[CLIENT_A].state(),
+            &TaskState::Healthy(SchedState::InReply(server))
+        );
+
+        // Now reply, which should unblock the client.
+        poke(mem.base + 0x300, b"goodbye!!");
+        let client = id(&tasks, CLIENT_A);
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Reply,
+            [u32::from(client.0), 0x1234, mem.base + 0x300, 9, 0, 0, 0],
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert!(tasks[CLIENT_A].is_runnable());
+        // The reply is truncated to fit the response buffer.
+        assert_eq!(tasks[CLIENT_A].save().rets[..2], [0x1234, 8]);
+        assert_eq!(peek(resp, 8), b"goodbye!");
+    }
+
+    #[test]
+    fn send_to_busy_server_blocks() {
+        let (mut tasks, _mem) = setup();
+        let server = id(&tasks, SERVER);
+        let hint = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 0, EMPTY, EMPTY, EMPTY),
+        );
+        assert_eq!(hint, NextTask::Other);
+        assert!(tasks[CLIENT_A].state().is_sending_to(server));
+    }
+
+    #[test]
+    fn open_recv_takes_most_important_sender() {
+        let (mut tasks, mem) = setup();
+        let server = id(&tasks, SERVER);
+        for client in [CLIENT_A, CLIENT_B] {
+            let _ = syscall(
+                &mut tasks,
+                client,
+                Sysnum::Send,
+                send_args(server, client as u16, EMPTY, EMPTY, EMPTY),
+            );
+        }
+
+        // CLIENT_B is more important, despite being later in the table.
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 0), 0, None),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[2], CLIENT_B as u32);
+        assert!(tasks[CLIENT_A].state().is_sending_to(server));
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 0), 0, None),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[2], CLIENT_A as u32);
+    }
+
+    #[test]
+    fn closed_recv_ignores_other_senders() {
+        let (mut tasks, mem) = setup();
+        let server = id(&tasks, SERVER);
+        let client_a = id(&tasks, CLIENT_A);
+        let client_b = id(&tasks, CLIENT_B);
+        let _ = syscall(
+            &mut tasks,
+            CLIENT_B,
+            Sysnum::Send,
+            send_args(server, 0, EMPTY, EMPTY, EMPTY),
+        );
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 0), 0, Some(client_a)),
+        );
+        assert_eq!(hint, NextTask::Other);
+        assert_eq!(
+            tasks[SERVER].state(),
+            &TaskState::Healthy(SchedState::InRecv(Some(client_a)))
+        );
+        assert!(tasks[CLIENT_B].state().is_sending_to(server));
+
+        // The other client still can't get through...
+        assert!(!tasks[SERVER].state().can_accept_message_from(client_b));
+
+        // ...but the one we're waiting for can.
+        let hint = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 0, EMPTY, EMPTY, EMPTY),
+        );
+        assert_eq!(hint, NextTask::Specific(SERVER));
+        assert_eq!(tasks[SERVER].save().rets[1], u32::from(client_a.0));
+    }
+
+    #[test]
+    fn closed_recv_from_dead_task_fails() {
+        let (mut tasks, mem) = setup();
+        let stale = id(&tasks, CLIENT_A);
+        tasks[CLIENT_A].reinitialize();
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 0), 0, Some(stale)),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert!(tasks[SERVER].is_runnable());
+        assert_eq!(
+            tasks[SERVER].save().rets[0],
+            abi::dead_response_code(tasks[CLIENT_A].generation())
+        );
+    }
+
+    #[test]
+    fn recv_delivers_pending_notifications_first() {
+        let (mut tasks, mem) = setup();
+        let server = id(&tasks, SERVER);
+        let _ = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 0, EMPTY, EMPTY, EMPTY),
+        );
+        assert!(!tasks[SERVER].post(task::NotificationSet(0b110)));
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 0), 0b100, None),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(
+            tasks[SERVER].save().rets,
+            [0, u32::from(TaskId::KERNEL.0), 0b100, 0, 0, 0]
+        );
+        assert!(tasks[CLIENT_A].state().is_sending_to(server));
+    }
+
+    #[test]
+    fn send_with_stale_generation_fails() {
+        let (mut tasks, _mem) = setup();
+        let stale = id(&tasks, SERVER);
+        tasks[SERVER].reinitialize();
+        tasks[SERVER].set_healthy_state(SchedState::InRecv(None));
+
+        let hint = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(stale, 0, EMPTY, EMPTY, EMPTY),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert!(tasks[CLIENT_A].is_runnable());
+        assert_eq!(
+            tasks[CLIENT_A].save().rets[0],
+            abi::dead_response_code(Generation::from(1))
+        );
+    }
+
+    #[test]
+    fn send_out_of_range_faults_caller() {
+        let (mut tasks, _mem) = setup();
+        let hint = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(TaskId(99), 0, EMPTY, EMPTY, EMPTY),
+        );
+        assert_eq!(hint, NextTask::Specific(SUPER));
+        assert_eq!(
+            fault_of(&tasks[CLIENT_A]),
+            Some(FaultInfo::SyscallUsage(UsageError::TaskOutOfRange))
+        );
+    }
+
+    #[test]
+    fn bad_message_faults_sender_not_receiver() {
+        let (mut tasks, mem) = setup();
+        let server = id(&tasks, SERVER);
+        let _ = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 16), 0, None),
+        );
+        // Message lies outside of any region the client can access.
+        let hint = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 0, (mem.base + mem.size, 4), EMPTY, EMPTY),
+        );
+        assert_eq!(hint, NextTask::Specific(SUPER));
+        assert!(matches!(
+            fault_of(&tasks[CLIENT_A]),
+            Some(FaultInfo::MemoryAccess { .. })
+        ));
+        assert_eq!(
+            tasks[SERVER].state(),
+            &TaskState::Healthy(SchedState::InRecv(None))
+        );
+    }
+
+    #[test]
+    fn reply_to_task_not_awaiting_reply_is_ignored() {
+        let (mut tasks, _mem) = setup();
+        let client = id(&tasks, CLIENT_A);
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Reply,
+            [u32::from(client.0), 0, 0, 0, 0, 0, 0],
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert!(tasks[CLIENT_A].is_runnable());
+        assert_eq!(tasks[CLIENT_A].save().rets, [0; 6]);
+    }
+
+    #[test]
+    fn reply_fault_faults_client() {
+        let (mut tasks, mem) = setup();
+        let server = id(&tasks, SERVER);
+        let client = id(&tasks, CLIENT_A);
+        let _ = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 0), 0, None),
+        );
+        let _ = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 0, EMPTY, EMPTY, EMPTY),
+        );
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::ReplyFault,
+            [
+                u32::from(client.0),
+                ReplyFaultReason::AccessViolation as u32,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(
+            fault_of(&tasks[CLIENT_A]),
+            Some(FaultInfo::FromServer(
+                server,
+                ReplyFaultReason::AccessViolation
+            ))
+        );
+    }
+
+    /// Has `CLIENT_A` send to `SERVER` with a single lease over 8 bytes of
+    /// memory with the given attributes, and has the server receive it.
+    /// Returns the address of the leased memory.
+    fn lend(
+        tasks: &mut [Task],
+        mem: &RegionDesc,
+        attributes: LeaseAttributes,
+    ) -> u32 {
+        let leased = mem.base + 0x400;
+        let table = mem.base + 0x500;
+        poke(leased, b"abcdefgh");
+        let lease = ULease {
+            attributes,
+            base_address: leased,
+            length: 8,
+        };
+        // Safety: the lease table is in test memory, and suitably aligned.
+        unsafe {
+            core::ptr::write(table as usize as *mut ULease, lease);
+        }
+
+        let server = id(tasks, SERVER);
+        let _ = syscall(
+            tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 0), 0, None),
+        );
+        let _ = syscall(
+            tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 0, EMPTY, EMPTY, (table, 1)),
+        );
+        assert_eq!(tasks[SERVER].save().rets[5], 1);
+        leased
+    }
+
+    fn borrow_args(
+        lender: TaskId,
+        lease: u32,
+        offset: u32,
+        buf: (u32, u32),
+    ) -> [u32; 7] {
+        [u32::from(lender.0), lease, offset, buf.0, buf.1, 0, 0]
+    }
+
+    #[test]
+    fn borrow_read_honors_offset() {
+        let (mut tasks, mem) = setup();
+        let _ = lend(&mut tasks, &mem, LeaseAttributes::READ);
+        let client = id(&tasks, CLIENT_A);
+
+        let buf = mem.base + 0x600;
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::BorrowRead,
+            borrow_args(client, 0, 2, (buf, 16)),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[..2], [0, 6]);
+        assert_eq!(peek(buf, 6), b"cdefgh");
+
+        // An offset right at the end of the lease is fine, and empty.
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::BorrowRead,
+            borrow_args(client, 0, 8, (buf, 16)),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[..2], [0, 0]);
+    }
+
+    #[test]
+    fn borrow_write_copies_into_lender() {
+        let (mut tasks, mem) = setup();
+        let leased = lend(&mut tasks, &mem, LeaseAttributes::WRITE);
+        let client = id(&tasks, CLIENT_A);
+
+        let buf = mem.base + 0x600;
+        poke(buf, b"XY");
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::BorrowWrite,
+            borrow_args(client, 0, 6, (buf, 2)),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[..2], [0, 2]);
+        assert_eq!(peek(leased, 8), b"abcdefXY");
+    }
+
+    /// Writes a `BorrowSegment` table to `table` and returns arguments for a
+    /// vectored borrow from `lender` using it.
+    fn borrowv_args(
+        lender: TaskId,
+        table: u32,
+        segments: &[(u32, u32, (u32, u32))],
+    ) -> [u32; 7] {
+        for (i, &(lease, offset, buf)) in segments.iter().enumerate() {
+            let segment = BorrowSegment {
+                lease_number: lease,
+                offset,
+                base_address: buf.0,
+                length: buf.1,
+            };
+            // Safety: the table is in test memory, and suitably aligned.
+            unsafe {
+                core::ptr::write(
+                    (table as usize as *mut BorrowSegment).add(i),
+                    segment,
+                );
+            }
+        }
+        [
+            u32::from(lender.0),
+            table,
+            segments.len() as u32,
+            0,
+            0,
+            0,
+            0,
+        ]
+    }
+
+    #[test]
+    fn borrow_readv_gathers_into_buffers() {
+        let (mut tasks, mem) = setup();
+        let _ = lend(&mut tasks, &mem, LeaseAttributes::READ);
+        let client = id(&tasks, CLIENT_A);
+
+        let (a, b) = (mem.base + 0x600, mem.base + 0x640);
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::BorrowReadv,
+            borrowv_args(
+                client,
+                mem.base + 0x700,
+                &[(0, 0, (a, 3)), (0, 5, (b, 16))],
+            ),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[..2], [0, 6]);
+        assert_eq!(peek(a, 3), b"abc");
+        assert_eq!(peek(b, 3), b"fgh");
+    }
+
+    #[test]
+    fn borrow_writev_scatters_into_lender() {
+        let (mut tasks, mem) = setup();
+        let leased = lend(&mut tasks, &mem, LeaseAttributes::WRITE);
+        let client = id(&tasks, CLIENT_A);
+
+        let (a, b) = (mem.base + 0x600, mem.base + 0x640);
+        poke(a, b"XY");
+        poke(b, b"Z");
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::BorrowWritev,
+            borrowv_args(
+                client,
+                mem.base + 0x700,
+                &[(0, 1, (a, 2)), (0, 7, (b, 1))],
+            ),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[..2], [0, 3]);
+        assert_eq!(peek(leased, 8), b"aXYdefgZ");
+    }
+
+    #[test]
+    fn borrowv_stops_at_bad_segment() {
+        let (mut tasks, mem) = setup();
+        let _ = lend(&mut tasks, &mem, LeaseAttributes::READ);
+        let client = id(&tasks, CLIENT_A);
+
+        let buf = mem.base + 0x600;
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::BorrowReadv,
+            borrowv_args(
+                client,
+                mem.base + 0x700,
+                &[(0, 0, (buf, 2)), (1, 0, (buf + 2, 2))],
+            ),
+        );
+        assert_eq!(hint, NextTask::Specific(SUPER));
+        assert_eq!(
+            fault_of(&tasks[SERVER]),
+            Some(FaultInfo::SyscallUsage(UsageError::LeaseOutOfRange))
+        );
+        // The first segment was copied before the second was found wanting.
+        assert_eq!(peek(buf, 2), b"ab");
+    }
+
+    #[test]
+    fn borrow_against_lease_attributes_is_defect() {
+        let (mut tasks, mem) = setup();
+        let _ = lend(&mut tasks, &mem, LeaseAttributes::READ);
+        let client = id(&tasks, CLIENT_A);
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::BorrowWrite,
+            borrow_args(client, 0, 0, (mem.base + 0x600, 8)),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[0], abi::DEFECT);
+        assert!(fault_of(&tasks[SERVER]).is_none());
+    }
+
+    #[test]
+    fn borrow_info_reports_lease() {
+        let (mut tasks, mem) = setup();
+        let _ = lend(
+            &mut tasks,
+            &mem,
+            LeaseAttributes::READ | LeaseAttributes::WRITE,
+        );
+        let client = id(&tasks, CLIENT_A);
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::BorrowInfo,
+            borrow_args(client, 0, 0, EMPTY),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[..3], [0, 0b11, 8]);
+    }
+
+    #[test]
+    fn borrow_out_of_bounds_faults_borrower() {
+        for (lease, offset, fault) in [
+            (1, 0, UsageError::LeaseOutOfRange),
+            (0, 9, UsageError::OffsetOutOfRange),
+        ] {
+            let (mut tasks, mem) = setup();
+            let _ = lend(&mut tasks, &mem, LeaseAttributes::READ);
+            let client = id(&tasks, CLIENT_A);
+
+            let hint = syscall(
+                &mut tasks,
+                SERVER,
+                Sysnum::BorrowRead,
+                borrow_args(client, lease, offset, (mem.base + 0x600, 8)),
+            );
+            assert_eq!(hint, NextTask::Specific(SUPER));
+            assert_eq!(
+                fault_of(&tasks[SERVER]),
+                Some(FaultInfo::SyscallUsage(fault))
+            );
+        }
+    }
+
+    #[test]
+    fn borrow_from_task_not_lending_is_defect() {
+        let (mut tasks, mem) = setup();
+        let client = id(&tasks, CLIENT_A);
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::BorrowRead,
+            borrow_args(client, 0, 0, (mem.base, 8)),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[0], abi::DEFECT);
+    }
+
+    #[test]
+    fn borrow_from_lender_with_bad_lease_table_faults_lender() {
+        let (mut tasks, mem) = setup();
+        let server = id(&tasks, SERVER);
+        let client = id(&tasks, CLIENT_A);
+        let _ = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 0), 0, None),
+        );
+        // The lease table is outside the client's memory. Delivery doesn't
+        // look at the table, only its length, so this isn't caught until
+        // someone tries to borrow.
+        let _ = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 0, EMPTY, EMPTY, (mem.base + mem.size, 1)),
+        );
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::BorrowRead,
+            borrow_args(client, 0, 0, (mem.base, 8)),
+        );
+        assert_eq!(hint, NextTask::Specific(SUPER));
+        assert_eq!(tasks[SERVER].save().rets[0], abi::DEFECT);
+        assert!(matches!(
+            fault_of(&tasks[CLIENT_A]),
+            Some(FaultInfo::MemoryAccess { .. })
+        ));
+    }
+
+    #[test]
+    fn set_timer_in_past_posts_immediately() {
+        let (mut tasks, _mem) = setup();
+        crate::arch::set_now(Timestamp::from(100));
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::SetTimer,
+            [1, 50, 0, 1, 0, 0, 0],
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].timer(0).0, None);
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::SetTimer,
+            [1, 150, 0, 1, 0, 0, 0],
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].timer(0).0, Some(Timestamp::from(150)));
+
+        // The bit posted by the first call is waiting for the next RECV.
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args(EMPTY, 1, None),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[..3], [0, 0xffff, 1]);
+    }
+
+    #[test]
+    fn timers_are_selected_by_index() {
+        let (mut tasks, _mem) = setup();
+        crate::arch::set_now(Timestamp::from(100));
+
+        let _ = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::SetTimer,
+            [1, 150, 0, 1, 1, 0, 0],
+        );
+        assert_eq!(tasks[SERVER].timer(0).0, None);
+        assert_eq!(tasks[SERVER].timer(1).0, Some(Timestamp::from(150)));
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::GetTimer,
+            [1, 0, 0, 0, 0, 0, 0],
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets, [100, 0, 1, 150, 0, 1]);
+    }
+
+    #[test]
+    fn timer_index_out_of_range_faults() {
+        let (mut tasks, _mem) = setup();
+        let _ = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::SetTimer,
+            [1, 150, 0, 1, 2, 0, 0],
+        );
+        assert_eq!(
+            fault_of(&tasks[SERVER]),
+            Some(FaultInfo::SyscallUsage(UsageError::TimerOutOfRange))
+        );
+    }
+
+    /// Adds a deadline to arguments produced by `recv_args`.
+    fn with_deadline(mut args: [u32; 7], deadline: u64) -> [u32; 7] {
+        args[3] |= 1 << 30;
+        args[4] = deadline as u32;
+        args[5] = (deadline >> 32) as u32;
+        args
+    }
+
+    #[test]
+    fn idle_sleeps_until_next_deadline() {
+        let (mut tasks, _mem) = setup();
+        crate::arch::set_now(Timestamp::from(100));
+        tasks[SERVER].save_mut().args[2] = !0;
+        tasks[SERVER].set_healthy_state(SchedState::InRecv(None));
+        tasks[SERVER].set_timer(
+            0,
+            Some(Timestamp::from(130)),
+            task::NotificationSet(1),
+        );
+        tasks[CLIENT_B].set_healthy_state(SchedState::InRecv(None));
+
+        let hint = syscall(&mut tasks, CLIENT_A, Sysnum::Idle, [0; 7]);
+        assert_eq!(hint, NextTask::Specific(SERVER));
+        assert_eq!(crate::arch::now(), Timestamp::from(130));
+        assert!(tasks[SERVER].is_runnable());
+        assert_eq!(tasks[CLIENT_A].runtime().ticks, 30);
+    }
+
+    #[test]
+    fn idle_does_not_sleep_while_others_are_runnable() {
+        let (mut tasks, _mem) = setup();
+        crate::arch::set_now(Timestamp::from(100));
+        tasks[SERVER].set_timer(
+            0,
+            Some(Timestamp::from(130)),
+            task::NotificationSet(1),
+        );
+
+        let hint = syscall(&mut tasks, CLIENT_A, Sysnum::Idle, [0; 7]);
+        assert_eq!(hint, NextTask::Other);
+        assert_eq!(crate::arch::now(), Timestamp::from(100));
+    }
+
+    #[test]
+    fn recv_times_out_at_deadline() {
+        let (mut tasks, _mem) = setup();
+        crate::arch::set_now(Timestamp::from(100));
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            with_deadline(recv_args(EMPTY, 0, None), 150),
+        );
+        assert_eq!(hint, NextTask::Other);
+
+        let hint = task::process_timers(&mut tasks, Timestamp::from(149));
+        assert_eq!(hint, NextTask::Same);
+        assert!(!tasks[SERVER].is_runnable());
+
+        let hint = task::process_timers(&mut tasks, Timestamp::from(150));
+        assert_eq!(hint, NextTask::Specific(SERVER));
+        assert!(tasks[SERVER].is_runnable());
+        assert_eq!(tasks[SERVER].save().rets[0], abi::TIMED_OUT);
+    }
+
+    #[test]
+    fn recv_past_deadline_returns_immediately() {
+        let (mut tasks, _mem) = setup();
+        crate::arch::set_now(Timestamp::from(100));
+
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            with_deadline(recv_args(EMPTY, 0, None), 100),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert!(tasks[SERVER].is_runnable());
+        assert_eq!(tasks[SERVER].save().rets[0], abi::TIMED_OUT);
+    }
+
+    #[test]
+    fn send_deadline_covers_reply_and_applies_once() {
+        let (mut tasks, _mem) = setup();
+        crate::arch::set_now(Timestamp::from(100));
+        let server = id(&tasks, SERVER);
+
+        let _ = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args(EMPTY, 0, None),
+        );
+        let hint = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::SetSendDeadline,
+            [1, 150, 0, 0, 0, 0, 0],
+        );
+        assert_eq!(hint, NextTask::Same);
+        let hint = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 0, EMPTY, EMPTY, EMPTY),
+        );
+        assert_eq!(hint, NextTask::Specific(SERVER));
+
+        // The server sits on the message past the deadline.
+        let hint = task::process_timers(&mut tasks, Timestamp::from(150));
+        assert_eq!(hint, NextTask::Specific(CLIENT_A));
+        assert_eq!(tasks[CLIENT_A].save().rets[..2], [abi::TIMED_OUT, 0]);
+
+        // Its eventual reply goes nowhere.
+        let client = id(&tasks, CLIENT_A);
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Reply,
+            [u32::from(client.0), 0, EMPTY.0, EMPTY.1, 0, 0, 0],
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[CLIENT_A].save().rets[0], abi::TIMED_OUT);
+
+        // The deadline was used up by the first send, so this one waits for
+        // as long as it takes.
+        let _ = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 0, EMPTY, EMPTY, EMPTY),
+        );
+        let hint = task::process_timers(&mut tasks, Timestamp::from(1000));
+        assert_eq!(hint, NextTask::Same);
+        assert!(tasks[CLIENT_A].state().is_sending_to(server));
+    }
+
+    #[test]
+    fn late_reply_is_not_taken_for_the_next_one() {
+        let (mut tasks, mem) = setup();
+        crate::arch::set_now(Timestamp::from(100));
+        let server = id(&tasks, SERVER);
+        let client = id(&tasks, CLIENT_A);
+        let resp = mem.base + 0x200;
+        let reply = |tasks: &mut [Task], code: u32| {
+            syscall(
+                tasks,
+                SERVER,
+                Sysnum::Reply,
+                [u32::from(client.0), code, EMPTY.0, EMPTY.1, 0, 0, 0],
+            )
+        };
+
+        // The server takes the first message and sits on it until the
+        // client gives up.
+        let _ = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args(EMPTY, 0, None),
+        );
+        let _ = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::SetSendDeadline,
+            [1, 150, 0, 0, 0, 0, 0],
+        );
+        let _ = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 1, EMPTY, (resp, 8), EMPTY),
+        );
+        let _ = task::process_timers(&mut tasks, Timestamp::from(150));
+        assert_eq!(tasks[CLIENT_A].save().rets[0], abi::TIMED_OUT);
+
+        // The client tries again, and the server takes that message too
+        // before getting around to answering the first.
+        let _ = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args(EMPTY, 0, None),
+        );
+        let _ = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 2, EMPTY, (resp, 8), EMPTY),
+        );
+        assert_eq!(tasks[SERVER].save().rets[..3], [0, u32::from(client.0), 2]);
+
+        // The late reply to the first message is dropped...
+        let hint = reply(&mut tasks, 0x1111);
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(
+            tasks[CLIENT_A].state(),
+            &TaskState::Healthy(SchedState::InReply(server))
+        );
+
+        // ...and the reply to the second one gets through.
+        let _ = reply(&mut tasks, 0x2222);
+        assert!(tasks[CLIENT_A].is_runnable());
+        assert_eq!(tasks[CLIENT_A].save().rets[0], 0x2222);
+    }
+
+    /// Has `CLIENT_A` start an async send to `SERVER` in slot 0, with a
+    /// 5-byte message and 8-byte response buffer, and the given leases.
+    fn send_async(
+        tasks: &mut [Task],
+        mem: &RegionDesc,
+        leases: (u32, u32),
+    ) -> NextTask {
+        let server = id(tasks, SERVER);
+        poke(mem.base + 0x100, b"hello");
+        let _ = syscall(
+            tasks,
+            CLIENT_A,
+            Sysnum::SetSendAsync,
+            [0, 0b100, 0, 0, 0, 0, 0],
+        );
+        syscall(
+            tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(
+                server,
+                7,
+                (mem.base + 0x100, 5),
+                (mem.base + 0x200, 8),
+                leases,
+            ),
+        )
+    }
+
+    fn collect_async(tasks: &mut [Task], slot: u32, cancel: bool) -> [u32; 3] {
+        let hint = syscall(
+            tasks,
+            CLIENT_A,
+            Sysnum::CollectAsync,
+            [slot, cancel as u32, 0, 0, 0, 0, 0],
+        );
+        assert_eq!(hint, NextTask::Same);
+        let rets = tasks[CLIENT_A].save().rets;
+        [rets[0], rets[1], rets[2]]
+    }
+
+    #[test]
+    fn async_send_completes_by_notification() {
+        let (mut tasks, mem) = setup();
+        let hint = send_async(&mut tasks, &mem, EMPTY);
+        // The client keeps running.
+        assert_eq!(hint, NextTask::Same);
+        assert!(tasks[CLIENT_A].is_runnable());
+        assert_eq!(tasks[CLIENT_A].save().rets[..2], [0, 0]);
+        assert_eq!(collect_async(&mut tasks, 0, false), [1, 0, 0]);
+
+        // The server picks the message up as if the client were blocked.
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 16), 0, None),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(
+            tasks[SERVER].save().rets,
+            [0, u32::from(id(&tasks, CLIENT_A).0), 7, 5, 8, 0]
+        );
+        assert_eq!(peek(mem.base, 5), b"hello");
+
+        poke(mem.base + 0x300, b"goodbye!!");
+        let client = id(&tasks, CLIENT_A);
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Reply,
+            [u32::from(client.0), 0x1234, mem.base + 0x300, 9, 0, 0, 0],
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(peek(mem.base + 0x200, 8), b"goodbye!");
+
+        // The client hears about it by notification, and collects the result
+        // once.
+        let _ = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Recv,
+            recv_args(EMPTY, 0b100, None),
+        );
+        assert_eq!(tasks[CLIENT_A].save().rets[2], 0b100);
+        assert_eq!(collect_async(&mut tasks, 0, false), [2, 0x1234, 8]);
+        assert_eq!(collect_async(&mut tasks, 0, false), [0, 0, 0]);
+    }
+
+    #[test]
+    fn async_send_to_waiting_server_lends_memory() {
+        let (mut tasks, mem) = setup();
+        let _ = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 16), 0, None),
+        );
+
+        let leased = mem.base + 0x400;
+        let table = mem.base + 0x500;
+        poke(leased, b"abcdefgh");
+        let lease = ULease {
+            attributes: LeaseAttributes::READ,
+            base_address: leased,
+            length: 8,
+        };
+        // Safety: the lease table is in test memory, and suitably aligned.
+        unsafe {
+            core::ptr::write(table as usize as *mut ULease, lease);
+        }
+
+        // The server is more important, so it gets to run right away.
+        let hint = send_async(&mut tasks, &mem, (table, 1));
+        assert_eq!(hint, NextTask::Specific(SERVER));
+        assert!(tasks[CLIENT_A].is_runnable());
+
+        let client = id(&tasks, CLIENT_A);
+        let buf = mem.base + 0x600;
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::BorrowRead,
+            borrow_args(client, 0, 0, (buf, 8)),
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(tasks[SERVER].save().rets[..2], [0, 8]);
+        assert_eq!(peek(buf, 8), b"abcdefgh");
+
+        // A second conversation with the same server would be ambiguous.
+        let server = id(&tasks, SERVER);
+        let hint = syscall(
+            &mut tasks,
+            CLIENT_A,
+            Sysnum::Send,
+            send_args(server, 0, EMPTY, EMPTY, EMPTY),
+        );
+        assert_eq!(hint, NextTask::Specific(SUPER));
+        assert_eq!(
+            fault_of(&tasks[CLIENT_A]),
+            Some(FaultInfo::SyscallUsage(UsageError::SendInProgress))
+        );
+    }
+
+    #[test]
+    fn cancelled_async_send_ignores_reply() {
+        let (mut tasks, mem) = setup();
+        let _ = send_async(&mut tasks, &mem, EMPTY);
+        let _ = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Recv,
+            recv_args((mem.base, 16), 0, None),
+        );
+        assert_eq!(collect_async(&mut tasks, 0, true), [1, 0, 0]);
+
+        poke(mem.base + 0x300, b"goodbye!");
+        let client = id(&tasks, CLIENT_A);
+        let hint = syscall(
+            &mut tasks,
+            SERVER,
+            Sysnum::Reply,
+            [u32::from(client.0), 0, mem.base + 0x300, 8, 0, 0, 0],
+        );
+        assert_eq!(hint, NextTask::Same);
+        assert_eq!(peek(mem.base + 0x200, 8), [0; 8]);
+        assert_eq!(collect_async(&mut tasks, 0, false), [0, 0, 0]);
+    }
+
+    #[test]
+    fn aborted_async_send_reports_dead_code() {
+        let (mut tasks, mem) = setup();
+        let _ = send_async(&mut tasks, &mem, EMPTY);
+        let server = id(&tasks, SERVER);
+        let code = abi::dead_response_code(server.generation());
+        assert!(!tasks[CLIENT_A].abort_async_sends_to(server, code));
+        assert_eq!(collect_async(&mut tasks, 0, false), [2, code, 0]);
+    }
+
+    #[test]
+    fn async_slot_misuse_faults() {
+        for (slot, fault) in [
+            (0, UsageError::AsyncSlotBusy),
+            (2, UsageError::AsyncSlotOutOfRange),
+        ] {
+            let (mut tasks, mem) = setup();
+            let _ = send_async(&mut tasks, &mem, EMPTY);
+
+            let other = id(&tasks, CLIENT_B);
+            let _ = syscall(
+                &mut tasks,
+                CLIENT_A,
+                Sysnum::SetSendAsync,
+                [slot, 0, 0, 0, 0, 0, 0],
+            );
+            let hint = syscall(
+                &mut tasks,
+                CLIENT_A,
+                Sysnum::Send,
+                send_args(other, 0, EMPTY, EMPTY, EMPTY),
+            );
+            assert_eq!(hint, NextTask::Specific(SUPER));
+            assert_eq!(
+                fault_of(&tasks[CLIENT_A]),
+                Some(FaultInfo::SyscallUsage(fault))
+            );
+        }
+    }
+
+    #[test]
+    fn bad_syscall_number_faults() {
+        let (mut tasks, _mem) = setup();
+        let hint = safe_syscall_entry(0xffff, CLIENT_A, &mut tasks);
+        assert_eq!(hint, NextTask::Specific(SUPER));
+        assert_eq!(
+            fault_of(&tasks[CLIENT_A]),
+            Some(FaultInfo::SyscallUsage(UsageError::BadSyscallNumber))
+        );
+    }
+}
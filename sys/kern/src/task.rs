@@ -7,9 +7,10 @@
 use core::convert::TryFrom;
 
 use abi::{
-    FaultInfo, FaultSource, Generation, Priority, RegionAttributes, RegionDesc,
-    ReplyFaultReason, SchedState, TaskDesc, TaskFlags, TaskId, TaskState,
-    ULease, UsageError,
+    AsyncSendStatus, BorrowSegment, FaultInfo, FaultSource, Generation,
+    KernelEventKind, Priority, RegionAttributes, RegionDesc, ReplyFaultReason,
+    SchedState, TaskDesc, TaskFlags, TaskId, TaskRuntime, TaskStackUsage,
+    TaskState, ULease, UsageError,
 };
 use zerocopy::FromBytes;
 
@@ -18,6 +19,10 @@ use crate::startup::HUBRIS_FAULT_NOTIFICATION;
 use crate::time::Timestamp;
 use crate::umem::USlice;
 
+/// Pattern written over every word of a task's stack when the task is
+/// (re)initialized, so that we can later tell how much of it has been used.
+const STACK_PAINT: u32 = 0xbaddcafe;
+
 /// Internal representation of a task.
 ///
 /// The fields of this struct are private to this module so that we can maintain
@@ -34,8 +39,15 @@ pub struct Task {
     priority: Priority,
     /// State used to make status and scheduling decisions.
     state: TaskState,
-    /// State for tracking the task's timer.
+    /// State for tracking the task's timers.
     timer: TimerState,
+    /// State for tracking the task's asynchronous sends.
+    async_sends: AsyncSends,
+    /// Server that took a message from this task, but hadn't replied by the
+    /// time the `SEND` timed out, along with how many such messages it holds.
+    /// Its replies to those messages are discarded as they arrive, so that
+    /// they can't be mistaken for replies to later messages.
+    abandoned_replies: Option<(TaskId, u16)>,
     /// Restart count for this task. We increment this whenever we reinitialize
     /// the task. The low bits of this become the task's generation number.
     generation: u32,
@@ -46,6 +58,10 @@ pub struct Task {
     /// Notification status.
     notifications: u32,
 
+    /// CPU usage statistics. Unlike most of the fields above, these are not
+    /// reset when the task is reinitialized.
+    runtime: TaskRuntime,
+
     /// Pointer to the ROM descriptor used to create this task, so it can be
     /// restarted.
     descriptor: &'static TaskDesc,
@@ -71,8 +87,11 @@ impl Task {
 
             generation: 0,
             notifications: 0,
+            runtime: TaskRuntime::default(),
             save: crate::arch::SavedState::default(),
             timer: crate::task::TimerState::default(),
+            async_sends: AsyncSends::default(),
+            abandoned_replies: None,
         }
     }
 
@@ -245,26 +264,266 @@ impl Task {
         self.state == TaskState::Healthy(SchedState::Runnable)
     }
 
-    /// Configures this task's timer.
+    /// Returns the number of timers this task can use, as configured in its
+    /// descriptor.
+    pub fn timer_count(&self) -> usize {
+        usize::from(self.descriptor.timers)
+            .clamp(1, crate::startup::HUBRIS_TIMERS_PER_TASK)
+    }
+
+    /// Configures one of this task's timers.
+    ///
+    /// `index` selects the timer, and must be less than `timer_count`.
     ///
     /// `deadline` specifies the moment when the timer should fire, in kernel
     /// time. If `None`, the timer will never fire.
     ///
     /// `notifications` is the set of notification bits to be set when the timer
     /// fires.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of range.
     pub fn set_timer(
         &mut self,
+        index: usize,
         deadline: Option<Timestamp>,
         notifications: NotificationSet,
     ) {
-        self.timer.deadline = deadline;
-        self.timer.to_post = notifications;
+        uassert!(index < self.timer_count());
+        let timer = &mut self.timer.timers[index];
+        timer.deadline = deadline;
+        timer.to_post = notifications;
     }
 
-    /// Reads out the state of this task's timer, as previously set by
+    /// Reads out the state of one of this task's timers, as previously set by
     /// `set_timer`.
-    pub fn timer(&self) -> (Option<Timestamp>, NotificationSet) {
-        (self.timer.deadline, self.timer.to_post)
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of range.
+    pub fn timer(&self, index: usize) -> (Option<Timestamp>, NotificationSet) {
+        uassert!(index < self.timer_count());
+        let timer = &self.timer.timers[index];
+        (timer.deadline, timer.to_post)
+    }
+
+    /// Sets the deadline for the SEND or RECV this task is starting, replacing
+    /// any left over from a previous operation.
+    ///
+    /// If the task is still blocked in that operation when the deadline
+    /// passes, `process_timers` will resume it with `abi::TIMED_OUT`.
+    pub fn set_ipc_deadline(&mut self, deadline: Option<Timestamp>) {
+        self.timer.ipc_deadline = deadline;
+    }
+
+    /// Arms a deadline that will apply to this task's next SEND.
+    pub fn set_send_deadline(&mut self, deadline: Option<Timestamp>) {
+        self.timer.send_deadline = deadline;
+    }
+
+    /// Takes the deadline armed for this task's next SEND, if any, disarming
+    /// it.
+    pub fn take_send_deadline(&mut self) -> Option<Timestamp> {
+        self.timer.send_deadline.take()
+    }
+
+    /// Notes that this task has given up waiting for `server` to reply to a
+    /// message it has already taken.
+    ///
+    /// Only one server is tracked at a time. Giving up on a second server
+    /// forgets about any messages abandoned with the first.
+    fn abandon_reply(&mut self, server: TaskId) {
+        self.abandoned_replies = match self.abandoned_replies {
+            Some((s, n)) if s == server => Some((s, n.saturating_add(1))),
+            _ => Some((server, 1)),
+        };
+    }
+
+    /// Checks whether `server` has yet to reply to a message this task has
+    /// abandoned.
+    pub fn owes_abandoned_reply(&self, server: TaskId) -> bool {
+        matches!(self.abandoned_replies, Some((s, _)) if s == server)
+    }
+
+    /// Accounts for a reply from `server` to a message this task has
+    /// abandoned, if there is one outstanding.
+    pub fn take_abandoned_reply(&mut self, server: TaskId) {
+        if let Some((s, n)) = self.abandoned_replies {
+            if s == server {
+                self.abandoned_replies = (n > 1).then(|| (s, n - 1));
+            }
+        }
+    }
+
+    /// Arms this task's next SEND to be asynchronous, using async send slot
+    /// `slot` and posting `notification` when it completes.
+    pub fn set_send_async(
+        &mut self,
+        slot: usize,
+        notification: NotificationSet,
+    ) {
+        self.async_sends.armed = Some((slot, notification));
+    }
+
+    /// Takes the async send slot and notification armed for this task's next
+    /// SEND, if any, disarming it.
+    pub fn take_send_async(&mut self) -> Option<(usize, NotificationSet)> {
+        self.async_sends.armed.take()
+    }
+
+    /// Starts an asynchronous send of `args` in slot `slot`, queueing it for
+    /// delivery to `args.callee`.
+    ///
+    /// This fails if the slot is out of range or already in use.
+    pub fn start_async_send(
+        &mut self,
+        slot: usize,
+        notification: NotificationSet,
+        args: SendArgs,
+    ) -> Result<(), UsageError> {
+        if slot >= usize::from(self.descriptor.async_sends) {
+            return Err(UsageError::AsyncSlotOutOfRange);
+        }
+        let slot = &mut self.async_sends.slots[slot];
+        if !matches!(slot.state, AsyncSendState::Free) {
+            return Err(UsageError::AsyncSlotBusy);
+        }
+        slot.state = AsyncSendState::Queued(args);
+        slot.notification = notification;
+        Ok(())
+    }
+
+    /// Checks whether this task has an asynchronous send queued for delivery
+    /// to `callee`, returning its slot if so.
+    ///
+    /// Faulted tasks are not considered to have anything queued, just as they
+    /// are not considered to be blocked in SEND.
+    pub fn queued_async_send_to(&self, callee: TaskId) -> Option<usize> {
+        self.find_async_send(|s| {
+            matches!(s, AsyncSendState::Queued(args) if args.callee == callee)
+        })
+    }
+
+    /// Checks whether this task has an asynchronous send that has been
+    /// delivered to `callee` and is awaiting its reply, returning its slot if
+    /// so.
+    pub fn async_send_in_reply_to(&self, callee: TaskId) -> Option<usize> {
+        self.find_async_send(|s| {
+            matches!(s, AsyncSendState::InReply(args) if args.callee == callee)
+        })
+    }
+
+    /// Checks whether this task has an unfinished asynchronous send to
+    /// `callee`, in any stage.
+    pub fn has_async_send_with(&self, callee: TaskId) -> bool {
+        self.async_sends.slots.iter().any(|s| match &s.state {
+            AsyncSendState::Queued(args) | AsyncSendState::InReply(args) => {
+                args.callee == callee
+            }
+            _ => false,
+        })
+    }
+
+    fn find_async_send(
+        &self,
+        pred: impl Fn(&AsyncSendState) -> bool,
+    ) -> Option<usize> {
+        if !matches!(self.state, TaskState::Healthy(_)) {
+            return None;
+        }
+        self.async_sends.slots.iter().position(|s| pred(&s.state))
+    }
+
+    /// Returns the arguments of the unfinished asynchronous send in `slot`.
+    ///
+    /// # Panics
+    ///
+    /// If there isn't one.
+    pub fn async_send_args(&self, slot: usize) -> SendArgs {
+        match &self.async_sends.slots[slot].state {
+            AsyncSendState::Queued(args) | AsyncSendState::InReply(args) => {
+                args.clone()
+            }
+            _ => panic!(),
+        }
+    }
+
+    /// Records that the asynchronous send in `slot` has been delivered, and
+    /// is now awaiting a reply.
+    ///
+    /// # Panics
+    ///
+    /// If the send isn't queued.
+    pub fn set_async_send_delivered(&mut self, slot: usize) {
+        let state = &mut self.async_sends.slots[slot].state;
+        match core::mem::take(state) {
+            AsyncSendState::Queued(args) => {
+                *state = AsyncSendState::InReply(args);
+            }
+            _ => panic!(),
+        }
+    }
+
+    /// Completes the asynchronous send in `slot` with the given response code
+    /// and length, posting the notification that was requested for it.
+    /// Returns `true` if this wakes the task.
+    pub fn complete_async_send(
+        &mut self,
+        slot: usize,
+        code: u32,
+        len: usize,
+    ) -> bool {
+        let slot = &mut self.async_sends.slots[slot];
+        slot.state = AsyncSendState::Complete { code, len };
+        let notification = slot.notification;
+        self.post(notification)
+    }
+
+    /// Completes every unfinished asynchronous send to `callee` with response
+    /// code `code`. This is used when `callee` is restarted. Returns `true` if
+    /// this wakes the task.
+    pub fn abort_async_sends_to(&mut self, callee: TaskId, code: u32) -> bool {
+        let mut woke = false;
+        for slot in 0..self.async_sends.slots.len() {
+            let matches = match &self.async_sends.slots[slot].state {
+                AsyncSendState::Queued(args)
+                | AsyncSendState::InReply(args) => args.callee == callee,
+                _ => false,
+            };
+            if matches {
+                woke |= self.complete_async_send(slot, code, 0);
+            }
+        }
+        woke
+    }
+
+    /// Reports the status of async send slot `slot`. If the send in it has
+    /// completed, this also returns the response code and length, and frees
+    /// the slot. If `cancel` is set, an unfinished send is abandoned and the
+    /// slot freed: any reply to it will be discarded.
+    pub fn collect_async_send(
+        &mut self,
+        slot: usize,
+        cancel: bool,
+    ) -> Result<(AsyncSendStatus, u32, usize), UsageError> {
+        if slot >= usize::from(self.descriptor.async_sends) {
+            return Err(UsageError::AsyncSlotOutOfRange);
+        }
+        let state = &mut self.async_sends.slots[slot].state;
+        Ok(match *state {
+            AsyncSendState::Free => (AsyncSendStatus::Idle, 0, 0),
+            AsyncSendState::Queued(_) | AsyncSendState::InReply(_) => {
+                if cancel {
+                    *state = AsyncSendState::Free;
+                }
+                (AsyncSendStatus::Pending, 0, 0)
+            }
+            AsyncSendState::Complete { code, len } => {
+                *state = AsyncSendState::Free;
+                (AsyncSendStatus::Complete, code, len)
+            }
+        })
     }
 
     /// Rewrites this task's state back to its initial form, to effect a task
@@ -279,7 +538,10 @@ impl Task {
     /// like to run the task after reinitializing it, you must do so explicitly.
     pub fn reinitialize(&mut self) {
         self.generation = self.generation.wrapping_add(1);
+        self.priority = Priority(self.descriptor.priority);
         self.timer = TimerState::default();
+        self.async_sends = AsyncSends::default();
+        self.abandoned_replies = None;
         self.notifications = 0;
         self.state = TaskState::default();
 
@@ -308,6 +570,87 @@ impl Task {
         self.priority
     }
 
+    /// Changes this task's priority. The change lasts until the task is next
+    /// reinitialized, at which point it reverts to the priority from its
+    /// descriptor.
+    pub fn set_priority(&mut self, priority: Priority) {
+        self.priority = priority;
+    }
+
+    /// Returns this task's CPU usage statistics.
+    pub fn runtime(&self) -> &TaskRuntime {
+        &self.runtime
+    }
+
+    /// Charges a kernel tick to this task. The architecture calls this from
+    /// its tick handler for whichever task was running when the tick arrived.
+    pub fn charge_tick(&mut self) {
+        self.charge_ticks(1);
+    }
+
+    /// Charges several kernel ticks to this task at once. This is used for
+    /// ticks the kernel skipped while sleeping on this task's behalf.
+    pub fn charge_ticks(&mut self, ticks: u64) {
+        self.runtime.ticks = self.runtime.ticks.wrapping_add(ticks);
+    }
+
+    /// Records that the kernel has switched to this task from a different one.
+    /// The architecture calls this when it changes the current task.
+    pub fn note_switched_in(&mut self) {
+        self.runtime.context_switches =
+            self.runtime.context_switches.wrapping_add(1);
+    }
+
+    /// Returns a slice covering this task's stack, if we can find it.
+    ///
+    /// Stacks are placed at the bottom of a task's RAM, so the stack runs from
+    /// the base of the region containing the initial stack pointer up to that
+    /// stack pointer.
+    fn stack_slice(&self) -> Option<USlice<u32>> {
+        let top = self.descriptor.initial_stack;
+        let region = self
+            .region_table
+            .iter()
+            .find(|r| r.base <= top && top - r.base <= r.size)?;
+        USlice::from_raw(
+            region.base as usize,
+            (top - region.base) as usize / core::mem::size_of::<u32>(),
+        )
+        .ok()
+    }
+
+    /// Fills this task's stack with a distinct (and storied) pattern, so that
+    /// `stack_usage` can measure it later.
+    ///
+    /// The architecture calls this from `reinitialize`, once the task's memory
+    /// is accessible but before it sets up the task's initial stack frame.
+    pub fn paint_stack(&mut self) {
+        if let Some(mut stack) = self.stack_slice() {
+            if let Ok(words) = self.try_write(&mut stack) {
+                words.fill(STACK_PAINT);
+            }
+        }
+    }
+
+    /// Measures how much of this task's stack has been used since it was
+    /// last painted, by finding the lowest word that no longer holds the
+    /// pattern.
+    pub fn stack_usage(&self) -> TaskStackUsage {
+        let stack = match self.stack_slice() {
+            Some(stack) => stack,
+            None => return TaskStackUsage::default(),
+        };
+        let words = match self.try_read(&stack) {
+            Ok(words) => words,
+            Err(_) => return TaskStackUsage::default(),
+        };
+        let untouched = words.iter().take_while(|&&w| w == STACK_PAINT).count();
+        TaskStackUsage {
+            size: (words.len() * 4) as u32,
+            peak: ((words.len() - untouched) * 4) as u32,
+        }
+    }
+
     /// Returns a reference to this task's current state, for inspection.
     pub fn state(&self) -> &TaskState {
         &self.state
@@ -417,19 +760,24 @@ pub trait ArchState: Default {
     /// of its code be eliminated and makes text smaller.
     #[inline(always)]
     fn as_recv_args(&self) -> RecvArgs {
+        let sender = self.arg3();
         RecvArgs {
             buffer: USlice::from_raw(
                 self.arg0() as usize,
                 self.arg1() as usize,
             ),
             notification_mask: self.arg2(),
-            specific_sender: {
-                let v = self.arg3();
-                if v & (1 << 31) != 0 {
-                    Some(TaskId(v as u16))
-                } else {
-                    None
-                }
+            specific_sender: if sender & (1 << 31) != 0 {
+                Some(TaskId(sender as u16))
+            } else {
+                None
+            },
+            deadline: if sender & (1 << 30) != 0 {
+                Some(Timestamp::from(
+                    u64::from(self.arg5()) << 32 | u64::from(self.arg4()),
+                ))
+            } else {
+                None
             },
         }
     }
@@ -468,6 +816,52 @@ pub trait ArchState: Default {
                 None
             },
             notification: NotificationSet(self.arg3()),
+            timer: self.arg4() as usize,
+        }
+    }
+
+    /// Interprets arguments as for the `GET_TIMER` syscall and returns the
+    /// results.
+    fn as_get_timer_args(&self) -> GetTimerArgs {
+        GetTimerArgs {
+            timer: self.arg0() as usize,
+        }
+    }
+
+    /// Interprets arguments as for the `SET_SEND_DEADLINE` syscall and returns
+    /// the results.
+    fn as_set_send_deadline_args(&self) -> SetSendDeadlineArgs {
+        SetSendDeadlineArgs {
+            deadline: if self.arg0() != 0 {
+                Some(Timestamp::from(
+                    u64::from(self.arg2()) << 32 | u64::from(self.arg1()),
+                ))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Interprets arguments as for the `IDLE` syscall and returns the results.
+    fn as_idle_args(&self) -> IdleArgs {
+        IdleArgs { state: self.arg0() }
+    }
+
+    /// Interprets arguments as for the `SET_SEND_ASYNC` syscall and returns
+    /// the results.
+    fn as_set_send_async_args(&self) -> SetSendAsyncArgs {
+        SetSendAsyncArgs {
+            slot: self.arg0() as usize,
+            notification: NotificationSet(self.arg1()),
+        }
+    }
+
+    /// Interprets arguments as for the `COLLECT_ASYNC` syscall and returns
+    /// the results.
+    fn as_collect_async_args(&self) -> CollectAsyncArgs {
+        CollectAsyncArgs {
+            slot: self.arg0() as usize,
+            cancel: self.arg1() != 0,
         }
     }
 
@@ -485,6 +879,18 @@ pub trait ArchState: Default {
         }
     }
 
+    /// Interprets arguments as for the `BORROW_READV` and `BORROW_WRITEV`
+    /// syscalls and returns the results.
+    fn as_borrowv_args(&self) -> BorrowvArgs {
+        BorrowvArgs {
+            lender: TaskId(self.arg0() as u16),
+            segments: USlice::from_raw(
+                self.arg1() as usize,
+                self.arg2() as usize,
+            ),
+        }
+    }
+
     /// Interprets arguments as for the `IRQ_CONTROL` syscall and returns the
     /// results.
     fn as_irq_args(&self) -> IrqArgs {
@@ -562,6 +968,18 @@ pub trait ArchState: Default {
         self.ret2(len as u32);
     }
 
+    /// Sets the results of COLLECT_ASYNC.
+    fn set_collect_async_result(
+        &mut self,
+        status: AsyncSendStatus,
+        code: u32,
+        len: usize,
+    ) {
+        self.ret0(status as u32);
+        self.ret1(code);
+        self.ret2(len as u32);
+    }
+
     /// Sets the results of READ_TIMER.
     fn set_time_result(
         &mut self,
@@ -602,6 +1020,7 @@ pub struct RecvArgs {
     pub buffer: Result<USlice<u8>, UsageError>,
     pub notification_mask: u32,
     pub specific_sender: Option<TaskId>,
+    pub deadline: Option<Timestamp>,
 }
 
 /// Decoded arguments for the `REPLY` syscall.
@@ -624,6 +1043,39 @@ pub struct ReplyFaultArgs {
 pub struct SetTimerArgs {
     pub deadline: Option<Timestamp>,
     pub notification: NotificationSet,
+    pub timer: usize,
+}
+
+/// Decoded arguments for the `GET_TIMER` syscall.
+#[derive(Clone, Debug)]
+pub struct GetTimerArgs {
+    pub timer: usize,
+}
+
+/// Decoded arguments for the `SET_SEND_DEADLINE` syscall.
+#[derive(Clone, Debug)]
+pub struct SetSendDeadlineArgs {
+    pub deadline: Option<Timestamp>,
+}
+
+/// Decoded arguments for the `IDLE` syscall.
+#[derive(Clone, Debug)]
+pub struct IdleArgs {
+    pub state: u32,
+}
+
+/// Decoded arguments for the `SET_SEND_ASYNC` syscall.
+#[derive(Clone, Debug)]
+pub struct SetSendAsyncArgs {
+    pub slot: usize,
+    pub notification: NotificationSet,
+}
+
+/// Decoded arguments for the `COLLECT_ASYNC` syscall.
+#[derive(Clone, Debug)]
+pub struct CollectAsyncArgs {
+    pub slot: usize,
+    pub cancel: bool,
 }
 
 /// Decoded arguments for the `BORROW_*` syscalls.
@@ -635,6 +1087,13 @@ pub struct BorrowArgs {
     pub buffer: Result<USlice<u8>, UsageError>,
 }
 
+/// Decoded arguments for the `BORROW_READV` and `BORROW_WRITEV` syscalls.
+#[derive(Clone, Debug)]
+pub struct BorrowvArgs {
+    pub lender: TaskId,
+    pub segments: Result<USlice<BorrowSegment>, UsageError>,
+}
+
 /// Decoded arguments for the `IRQ_CONTROL` syscall.
 #[derive(Clone, Debug)]
 pub struct IrqArgs {
@@ -661,11 +1120,60 @@ pub struct PostArgs {
     pub notification_bits: NotificationSet,
 }
 
-/// State for a task timer.
+/// State for a task's timers.
 ///
 /// Task timers are used to multiplex the hardware timer.
 #[derive(Debug, Default)]
 pub struct TimerState {
+    /// The task's timers. Every task has room for the same number, but can
+    /// only use as many as its descriptor says.
+    timers: [Timer; crate::startup::HUBRIS_TIMERS_PER_TASK],
+    /// Deadline for the SEND or RECV the task is blocked in, if any. This is
+    /// separate from `timers` so that IPC timeouts don't disturb the task's
+    /// notification timers.
+    ipc_deadline: Option<Timestamp>,
+    /// Deadline armed by `SET_SEND_DEADLINE`, to be applied to the task's next
+    /// SEND.
+    send_deadline: Option<Timestamp>,
+}
+
+/// State for a task's asynchronous sends.
+#[derive(Debug, Default)]
+struct AsyncSends {
+    /// The task's slots. As with timers, every task has room for the same
+    /// number, but can only use as many as its descriptor says.
+    slots: [AsyncSend; crate::startup::HUBRIS_ASYNC_SENDS_PER_TASK],
+    /// Slot and notification armed by `SET_SEND_ASYNC`, to be applied to the
+    /// task's next SEND.
+    armed: Option<(usize, NotificationSet)>,
+}
+
+/// A single async send slot.
+#[derive(Debug, Default)]
+struct AsyncSend {
+    state: AsyncSendState,
+    /// Set of notification bits to post to the owning task when the send
+    /// completes.
+    notification: NotificationSet,
+}
+
+/// Progress of an asynchronous send.
+#[derive(Debug, Default)]
+enum AsyncSendState {
+    /// Nothing going on.
+    #[default]
+    Free,
+    /// Waiting for the callee to receive the message.
+    Queued(SendArgs),
+    /// Delivered, and waiting for the callee to reply.
+    InReply(SendArgs),
+    /// Replied to, and waiting for the task to collect the result.
+    Complete { code: u32, len: usize },
+}
+
+/// A single task timer.
+#[derive(Copy, Clone, Debug, Default)]
+struct Timer {
     /// Deadline, in kernel time, at which this timer should fire. If `None`,
     /// the timer is disabled.
     deadline: Option<Timestamp>,
@@ -717,24 +1225,108 @@ impl NextTask {
 
 /// Processes all enabled timers in the task table, posting notifications for
 /// any that have expired by `current_time` (and disabling them atomically).
+///
+/// This also resumes any task whose IPC deadline has expired while it's still
+/// blocked in SEND or RECV, with `abi::TIMED_OUT` as the result. If the server
+/// had already taken the message, its eventual reply will be discarded.
 pub fn process_timers(tasks: &mut [Task], current_time: Timestamp) -> NextTask {
     let mut sched_hint = NextTask::Same;
     for (index, task) in tasks.iter_mut().enumerate() {
-        if let Some(deadline) = task.timer.deadline {
+        if let Some(deadline) = task.timer.ipc_deadline {
             if deadline <= current_time {
-                task.timer.deadline = None;
-                let task_hint = if task.post(task.timer.to_post) {
-                    NextTask::Specific(index)
-                } else {
-                    NextTask::Same
-                };
-                sched_hint = sched_hint.combine(task_hint)
+                task.timer.ipc_deadline = None;
+                if let TaskState::Healthy(SchedState::InReply(server)) =
+                    task.state
+                {
+                    task.abandon_reply(server);
+                }
+                if matches!(
+                    task.state,
+                    TaskState::Healthy(
+                        SchedState::InSend(_)
+                            | SchedState::InReply(_)
+                            | SchedState::InRecv(_)
+                    )
+                ) {
+                    task.save_mut().set_error_response(abi::TIMED_OUT);
+                    task.set_healthy_state(SchedState::Runnable);
+                    sched_hint = sched_hint.combine(NextTask::Specific(index));
+                }
+            }
+        }
+
+        let mut fired = None;
+        for timer in &mut task.timer.timers {
+            if let Some(deadline) = timer.deadline {
+                if deadline <= current_time {
+                    timer.deadline = None;
+                    let bits = fired.unwrap_or(0) | timer.to_post.0;
+                    fired = Some(bits);
+                }
             }
         }
+        if let Some(bits) = fired {
+            let task_hint = if task.post(NotificationSet(bits)) {
+                NextTask::Specific(index)
+            } else {
+                NextTask::Same
+            };
+            sched_hint = sched_hint.combine(task_hint)
+        }
     }
     sched_hint
 }
 
+/// Returns the earliest time at which `process_timers` would have something to
+/// do: the soonest deadline among all armed timers and pending IPC deadlines,
+/// if there are any.
+pub fn next_deadline(tasks: &[Task]) -> Option<Timestamp> {
+    tasks
+        .iter()
+        .flat_map(|task| {
+            let timers = task.timer.timers.iter().map(|t| t.deadline);
+            timers.chain(core::iter::once(task.timer.ipc_deadline))
+        })
+        .flatten()
+        .min()
+}
+
+/// Grants one task permission to send to another task that restricts its
+/// callers.
+///
+/// Tables of these are generated from the application config. Each task that
+/// restricts its callers gets a slice of grants, and every other task accepts
+/// messages from anyone.
+#[derive(Clone, Debug)]
+pub struct IpcGrant {
+    /// Index of the task allowed to send.
+    pub caller: u16,
+    /// Operation codes the caller may use, or `None` to allow any.
+    pub operations: Option<&'static [u16]>,
+}
+
+/// Checks whether task `caller` may send a message with `operation` to task
+/// `callee`, according to `acls`, which holds the grants for each task by
+/// index.
+///
+/// Tasks beyond the end of `acls` are treated as unrestricted.
+pub fn ipc_permitted(
+    acls: &[Option<&[IpcGrant]>],
+    caller: usize,
+    callee: usize,
+    operation: u16,
+) -> bool {
+    match acls.get(callee) {
+        Some(Some(grants)) => grants.iter().any(|grant| {
+            usize::from(grant.caller) == caller
+                && grant
+                    .operations
+                    .map_or(true, |ops| ops.contains(&operation))
+        }),
+        _ => true,
+    }
+}
+
 /// Checks a user-provided `TaskId` for validity against `table`.
 ///
 /// On success, returns an index that can be used to dereference `table` without
@@ -830,6 +1422,11 @@ pub fn force_fault(
     fault: FaultInfo,
 ) -> NextTask {
     let task = &mut tasks[index];
+    crate::eventlog::record(KernelEventKind::Fault {
+        task: TaskId::for_index_and_gen(index, task.generation()),
+        fault,
+        pc: crate::arch::saved_pc(task),
+    });
     task.state = match task.state {
         TaskState::Healthy(sched) => TaskState::Faulted {
             original_state: sched,
@@ -868,3 +1465,283 @@ fn region_covers<T>(region: &abi::RegionDesc, slice: &USlice<T>) -> bool {
     (region.base as usize) <= slice.base_addr()
         && slice.end_addr() <= region_end
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arch::task_table;
+
+    #[test]
+    fn select_prefers_most_important_runnable() {
+        let mut tasks = task_table(&[0, 2, 1, 3], &[]);
+        assert_eq!(select(0, &tasks), 0);
+
+        tasks[0].set_healthy_state(SchedState::InRecv(None));
+        assert_eq!(select(0, &tasks), 2);
+
+        tasks[2].set_healthy_state(SchedState::Stopped);
+        assert_eq!(select(0, &tasks), 1);
+    }
+
+    #[test]
+    fn select_round_robins_within_priority() {
+        let tasks = task_table(&[1, 1, 1], &[]);
+        assert_eq!(select(0, &tasks), 1);
+        assert_eq!(select(1, &tasks), 2);
+        assert_eq!(select(2, &tasks), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn select_panics_with_nothing_runnable() {
+        let mut tasks = task_table(&[0, 1], &[]);
+        for t in &mut tasks {
+            t.set_healthy_state(SchedState::Stopped);
+        }
+        select(0, &tasks);
+    }
+
+    #[test]
+    fn priority_scan_honors_predicate() {
+        let tasks = task_table(&[0, 1, 2, 3], &[]);
+        assert_eq!(priority_scan(0, &tasks, |t| t.priority.0 >= 2), Some(2));
+        assert_eq!(priority_scan(0, &tasks, |_| false), None);
+        // The previous task is considered last, but is still considered.
+        assert_eq!(priority_scan(3, &tasks, |t| t.priority.0 == 3), Some(3));
+    }
+
+    #[test]
+    fn process_timers_posts_expired_timers_once() {
+        let mut tasks = task_table(&[0, 1, 2], &[]);
+        for t in &mut tasks {
+            t.save_mut().args[2] = !0; // notification mask for RECV
+            t.set_healthy_state(SchedState::InRecv(None));
+        }
+        tasks[1].set_timer(
+            0,
+            Some(Timestamp::from(10)),
+            NotificationSet(1 << 3),
+        );
+        tasks[2].set_timer(
+            0,
+            Some(Timestamp::from(20)),
+            NotificationSet(1 << 4),
+        );
+
+        assert_eq!(
+            process_timers(&mut tasks, Timestamp::from(9)),
+            NextTask::Same
+        );
+        assert_eq!(
+            process_timers(&mut tasks, Timestamp::from(10)),
+            NextTask::Specific(1)
+        );
+        assert!(tasks[1].is_runnable());
+        assert_eq!(tasks[1].save().rets[2], 1 << 3);
+        assert_eq!(tasks[1].timer(0).0, None);
+        assert!(!tasks[2].is_runnable());
+
+        // Expired timers are disarmed and don't fire again.
+        tasks[1].set_healthy_state(SchedState::InRecv(None));
+        assert_eq!(
+            process_timers(&mut tasks, Timestamp::from(11)),
+            NextTask::Same
+        );
+        assert!(!tasks[1].is_runnable());
+    }
+
+    #[test]
+    fn timers_fire_independently() {
+        let mut tasks = task_table(&[0, 1], &[]);
+        tasks[1].set_timer(0, Some(Timestamp::from(10)), NotificationSet(1));
+        tasks[1].set_timer(1, Some(Timestamp::from(20)), NotificationSet(2));
+
+        let _ = process_timers(&mut tasks, Timestamp::from(10));
+        assert_eq!(tasks[1].timer(0).0, None);
+        assert_eq!(tasks[1].timer(1).0, Some(Timestamp::from(20)));
+        assert_eq!(tasks[1].notifications, 1);
+
+        // Timers expiring together post their bits together.
+        tasks[1].set_timer(0, Some(Timestamp::from(15)), NotificationSet(4));
+        let _ = process_timers(&mut tasks, Timestamp::from(20));
+        assert_eq!(tasks[1].timer(1).0, None);
+        assert_eq!(tasks[1].notifications, 1 | 2 | 4);
+    }
+
+    #[test]
+    fn next_deadline_finds_earliest() {
+        let mut tasks = task_table(&[0, 1, 2], &[]);
+        assert_eq!(next_deadline(&tasks), None);
+
+        tasks[1].set_timer(1, Some(Timestamp::from(30)), NotificationSet(1));
+        tasks[2].set_timer(0, Some(Timestamp::from(20)), NotificationSet(1));
+        assert_eq!(next_deadline(&tasks), Some(Timestamp::from(20)));
+
+        tasks[0].set_ipc_deadline(Some(Timestamp::from(10)));
+        assert_eq!(next_deadline(&tasks), Some(Timestamp::from(10)));
+    }
+
+    #[test]
+    fn process_timers_combines_hints() {
+        let mut tasks = task_table(&[0, 1, 2], &[]);
+        for t in &mut tasks[1..] {
+            t.save_mut().args[2] = !0;
+            t.set_healthy_state(SchedState::InRecv(None));
+            t.set_timer(0, Some(Timestamp::from(5)), NotificationSet(1));
+        }
+        assert_eq!(
+            process_timers(&mut tasks, Timestamp::from(5)),
+            NextTask::Other
+        );
+    }
+
+    #[test]
+    fn timer_to_task_not_in_recv_leaves_bits_pending() {
+        let mut tasks = task_table(&[0, 1], &[]);
+        tasks[1].set_timer(
+            0,
+            Some(Timestamp::from(1)),
+            NotificationSet(1 << 2),
+        );
+        assert_eq!(
+            process_timers(&mut tasks, Timestamp::from(1)),
+            NextTask::Same
+        );
+        assert_eq!(tasks[1].notifications, 1 << 2);
+    }
+
+    #[test]
+    fn force_fault_notifies_supervisor() {
+        let mut tasks = task_table(&[0, 1], &[]);
+        tasks[0].save_mut().args[2] = HUBRIS_FAULT_NOTIFICATION;
+        tasks[0].set_healthy_state(SchedState::InRecv(None));
+
+        let hint = force_fault(&mut tasks, 1, FaultInfo::Panic);
+        assert_eq!(hint, NextTask::Specific(0));
+        assert_eq!(
+            tasks[1].state,
+            TaskState::Faulted {
+                original_state: SchedState::Runnable,
+                fault: FaultInfo::Panic,
+            }
+        );
+        assert!(tasks[0].is_runnable());
+        assert_eq!(tasks[0].save().rets[2], HUBRIS_FAULT_NOTIFICATION);
+
+        // With the supervisor busy, the notification is left pending.
+        let hint = force_fault(&mut tasks, 1, FaultInfo::DivideByZero);
+        assert_eq!(hint, NextTask::Other);
+        assert_eq!(tasks[0].notifications, HUBRIS_FAULT_NOTIFICATION);
+    }
+
+    #[test]
+    fn double_fault_keeps_original_state() {
+        let mut tasks = task_table(&[0, 1], &[]);
+        let peer = TaskId::for_index_and_gen(0, Generation::default());
+        tasks[1].set_healthy_state(SchedState::InSend(peer));
+        let _ = force_fault(&mut tasks, 1, FaultInfo::Panic);
+        let _ = force_fault(&mut tasks, 1, FaultInfo::DivideByZero);
+        assert_eq!(
+            tasks[1].state,
+            TaskState::Faulted {
+                original_state: SchedState::InSend(peer),
+                fault: FaultInfo::DivideByZero,
+            }
+        );
+    }
+
+    #[test]
+    fn task_id_generation_checks() {
+        let mut tasks = task_table(&[0, 1], &[]);
+        let old_id = current_id(&tasks, 1);
+        assert_eq!(check_task_id_against_table(&tasks, old_id).ok(), Some(1));
+
+        tasks[1].reinitialize();
+        let new_gen = tasks[1].generation();
+        assert_ne!(new_gen, old_id.generation());
+        match check_task_id_against_table(&tasks, old_id) {
+            Err(UserError::Recoverable(code, NextTask::Same)) => {
+                assert_eq!(code, abi::dead_response_code(new_gen));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        match check_task_id_against_table(&tasks, TaskId(2)) {
+            Err(UserError::Unrecoverable(FaultInfo::SyscallUsage(
+                UsageError::TaskOutOfRange,
+            ))) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ipc_acls_restrict_callers_and_operations() {
+        let acls: &[Option<&[IpcGrant]>] = &[
+            None,
+            Some(&[
+                IpcGrant {
+                    caller: 0,
+                    operations: None,
+                },
+                IpcGrant {
+                    caller: 2,
+                    operations: Some(&[1, 3]),
+                },
+            ]),
+            Some(&[]),
+        ];
+
+        // Unrestricted tasks, including those missing from the table.
+        assert!(ipc_permitted(acls, 1, 0, 7));
+        assert!(ipc_permitted(acls, 1, 3, 7));
+
+        assert!(ipc_permitted(acls, 0, 1, 7));
+        assert!(ipc_permitted(acls, 2, 1, 3));
+        assert!(!ipc_permitted(acls, 2, 1, 2));
+        assert!(!ipc_permitted(acls, 3, 1, 1));
+
+        // An empty list of grants shuts a task off from everyone.
+        assert!(!ipc_permitted(acls, 0, 2, 1));
+    }
+
+    #[test]
+    fn stack_usage_measures_painted_stack() {
+        let ram = crate::arch::task_memory(
+            1024,
+            RegionAttributes::READ | RegionAttributes::WRITE,
+        );
+        let regions: &'static [RegionDesc] = Vec::leak(vec![ram.clone()]);
+        let region_table: &'static [&'static RegionDesc] =
+            Vec::leak(regions.iter().collect());
+        // Stack occupies the bottom 256 bytes of RAM.
+        let desc = Box::leak(Box::new(TaskDesc {
+            regions: [0; abi::REGIONS_PER_TASK],
+            entry_point: 0,
+            initial_stack: ram.base + 256,
+            priority: 0,
+            timers: 1,
+            async_sends: 0,
+            flags: TaskFlags::START_AT_BOOT,
+            index: 0,
+        }));
+        let mut task = Task::from_descriptor(desc, region_table);
+        task.reinitialize();
+        assert_eq!(task.stack_usage(), TaskStackUsage { size: 256, peak: 0 });
+
+        // Dirty the top 24 bytes of the stack, as the task would.
+        let mut slice =
+            USlice::<u32>::from_raw(ram.base as usize + 232, 6).unwrap();
+        task.try_write(&mut slice).unwrap().fill(0);
+        assert_eq!(
+            task.stack_usage(),
+            TaskStackUsage {
+                size: 256,
+                peak: 24
+            }
+        );
+
+        // Restarting the task repaints the stack.
+        task.reinitialize();
+        assert_eq!(task.stack_usage().peak, 0);
+    }
+}
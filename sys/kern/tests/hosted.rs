@@ -0,0 +1,119 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Boots the kernel on the hosted backend with a server, a client and an idle
+//! task, and checks that the client can sleep on a timer and then get a reply
+//! out of the server.
+//!
+//! The task table is baked into the kernel when it's built, so this only does
+//! anything when the kernel is configured with `hosted.ron`:
+//!
+//! ```text
+//! HUBRIS_KCONFIG="$(cat sys/kern/tests/hosted.ron)" \
+//!     cargo test -p kern --test hosted
+//! ```
+//!
+//! A successful run ends with the client exiting the process; a hung kernel is
+//! caught by a timeout.
+
+use std::time::Duration;
+
+use abi::Sysnum;
+use kern::arch::syscall;
+
+/// Message buffers, one in each task's RAM region (see `hosted.ron`).
+const SERVER_BUF: u32 = 0x1000_0000;
+const CLIENT_BUF: u32 = 0x1000_1000;
+
+const SERVER: u32 = 0;
+const OP_PING: u32 = 1;
+
+/// How many ticks the client sleeps before sending.
+const SLEEP_TICKS: u64 = 5;
+
+fn main() {
+    if option_env!("HUBRIS_KCONFIG").is_none() {
+        eprintln!("hosted: no HUBRIS_KCONFIG; skipping boot test");
+        return;
+    }
+
+    std::thread::spawn(|| {
+        std::thread::sleep(Duration::from_secs(10));
+        eprintln!("hosted: timed out waiting for the client to finish");
+        std::process::exit(1);
+    });
+
+    kern::arch::configure_task_entries(&[server, client, idle]);
+    // Safety: we're the only caller, and the hosted backend has no further
+    // requirements of its caller.
+    unsafe { kern::startup::start_kernel(1000) }
+}
+
+/// Answers every message with "pong".
+fn server() {
+    loop {
+        let rets =
+            syscall(Sysnum::Recv as u32, [SERVER_BUF, 16, 0, 0, 0, 0, 0]);
+        let (sender, op) = (rets[1], rets[2]);
+        assert_eq!(op, OP_PING);
+        // Safety: SERVER_BUF is in our region, which the kernel has mapped.
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                b"pong".as_ptr(),
+                SERVER_BUF as *mut u8,
+                4,
+            );
+        }
+        syscall(Sysnum::Reply as u32, [sender, 0, SERVER_BUF, 4, 0, 0, 0]);
+    }
+}
+
+/// Sleeps for a few ticks, pings the server, and ends the test.
+fn client() {
+    let rets = syscall(Sysnum::GetTimer as u32, [0; 7]);
+    let start = u64::from(rets[1]) << 32 | u64::from(rets[0]);
+    let deadline = start + SLEEP_TICKS;
+    syscall(
+        Sysnum::SetTimer as u32,
+        [1, deadline as u32, (deadline >> 32) as u32, 1, 0, 0, 0],
+    );
+    let rets = syscall(Sysnum::Recv as u32, [CLIENT_BUF, 0, 1, 0, 0, 0, 0]);
+    assert_eq!(rets[1], u32::from(abi::TaskId::KERNEL.0));
+    let rets = syscall(Sysnum::GetTimer as u32, [0; 7]);
+    let now = u64::from(rets[1]) << 32 | u64::from(rets[0]);
+    if now < deadline {
+        eprintln!("hosted: woke at {}, before deadline {}", now, deadline);
+        std::process::exit(1);
+    }
+
+    // Safety: CLIENT_BUF is in our region, which the kernel has mapped.
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            b"ping".as_ptr(),
+            CLIENT_BUF as *mut u8,
+            4,
+        );
+    }
+    let reply = CLIENT_BUF + 16;
+    let rets = syscall(
+        Sysnum::Send as u32,
+        [SERVER << 16 | OP_PING, CLIENT_BUF, 4, reply, 16, 0, 0],
+    );
+    // Safety: as above.
+    let response = unsafe { std::slice::from_raw_parts(reply as *const u8, 4) };
+    if rets[0] != 0 || rets[1] != 4 || response != b"pong" {
+        eprintln!("hosted: bad reply {:?} {:?}", &rets[..2], response);
+        std::process::exit(1);
+    }
+
+    eprintln!("hosted: booted and exchanged a message");
+    std::process::exit(0);
+}
+
+/// Keeps the simulated CPU busy while the others are blocked, as on hardware.
+fn idle() {
+    loop {
+        kern::arch::wait_for_interrupt();
+    }
+}
@@ -13,9 +13,11 @@ use core::marker::PhantomData;
 use zerocopy::{AsBytes, FromBytes, LayoutVerified};
 
 use crate::{
-    sys_borrow_info, sys_borrow_read, sys_borrow_write, sys_get_timer,
+    sys_borrow_info, sys_borrow_read, sys_borrow_readv, sys_borrow_write,
+    sys_borrow_writev, sys_cancel_async, sys_collect_async, sys_get_timer,
     sys_recv, sys_recv_closed, sys_recv_open, sys_reply, sys_send,
-    sys_set_timer, BorrowInfo, ClosedRecvError, FromPrimitive,
+    sys_send_async, sys_set_timer, BorrowInfo, BorrowSegment, ClosedRecvError,
+    FromPrimitive, Lease,
 };
 
 const INTERNAL_TIMER_NOTIFICATION: u32 = 1 << 31;
@@ -415,6 +417,76 @@ impl Borrow<'_> {
             Some(())
         }
     }
+
+    /// Starting at offset `offset` within the borrow, fills each buffer in
+    /// `dests` in turn from consecutive bytes, using a single syscall.
+    ///
+    /// This fails under the same conditions as `read_fully_at`, in which case
+    /// some of `dests` may have been filled.
+    pub fn read_fully_vectored_at<const N: usize>(
+        &self,
+        offset: usize,
+        mut dests: [&mut [u8]; N],
+    ) -> Option<()> {
+        let segments: [BorrowSegment; N] = self.segments(
+            offset,
+            dests
+                .iter_mut()
+                .map(|d| (d.as_mut_ptr() as *const u8, d.len())),
+        );
+        // Safety: the segments describe `dests`, which we hold exclusively
+        // until the syscall returns.
+        let (rc, n) = unsafe { sys_borrow_readv(self.id, &segments) };
+        if rc != 0 || n != dests.iter().map(|d| d.len()).sum::<usize>() {
+            None
+        } else {
+            Some(())
+        }
+    }
+
+    /// Starting at offset `offset` within the borrow, writes each buffer in
+    /// `srcs` in turn to consecutive bytes, using a single syscall.
+    ///
+    /// This fails under the same conditions as `write_fully_at`, in which case
+    /// some of `srcs` may have been written.
+    pub fn write_fully_vectored_at<const N: usize>(
+        &self,
+        offset: usize,
+        srcs: [&[u8]; N],
+    ) -> Option<()> {
+        let segments: [BorrowSegment; N] =
+            self.segments(offset, srcs.iter().map(|s| (s.as_ptr(), s.len())));
+        // Safety: the segments describe `srcs`, which we're borrowing until
+        // the syscall returns.
+        let (rc, n) = unsafe { sys_borrow_writev(self.id, &segments) };
+        if rc != 0 || n != srcs.iter().map(|s| s.len()).sum::<usize>() {
+            None
+        } else {
+            Some(())
+        }
+    }
+
+    /// Describes a vectored borrow that steps through this borrow from
+    /// `offset`, one buffer (given as base and length) at a time.
+    fn segments<const N: usize>(
+        &self,
+        mut offset: usize,
+        bufs: impl Iterator<Item = (*const u8, usize)>,
+    ) -> [BorrowSegment; N] {
+        let mut segments = [BorrowSegment {
+            lease_number: self.index as u32,
+            offset: 0,
+            base_address: 0,
+            length: 0,
+        }; N];
+        for (segment, (base, len)) in segments.iter_mut().zip(bufs) {
+            segment.offset = offset as u32;
+            segment.base_address = base as u32;
+            segment.length = len as u32;
+            offset += len;
+        }
+        segments
+    }
 }
 
 /// Trait implemented by types that represent a message sent to another task.
@@ -593,6 +665,98 @@ where
     }
 }
 
+/// Sends `outgoing` to `target` without waiting for the reply, and calls
+/// `body` while the send is in progress.
+///
+/// This uses async send slot `slot` (see the `async-sends` key in `app.toml`).
+/// When `target` replies, the response lands in `incoming`, and `notification`
+/// is posted to this task. `body` is handed a `PendingSend` with which it can
+/// check on, or wait for, the reply; sends can be nested to have several in
+/// progress at once.
+///
+/// The kernel uses `outgoing`, `incoming`, and `leases` until the send
+/// completes, which is why they're borrowed for the whole call. If `body`
+/// returns before the reply arrives, the send is cancelled, so the memory is
+/// ours again once this returns. Any reply can then be read from `incoming`.
+///
+/// If `target` has restarted, this returns its dead code without calling
+/// `body`.
+pub fn send_async<R>(
+    target: TaskId,
+    operation: u16,
+    outgoing: &[u8],
+    incoming: &mut [u8],
+    leases: &[Lease<'_>],
+    slot: usize,
+    notification: u32,
+    body: impl FnOnce(&mut PendingSend) -> R,
+) -> Result<R, u32> {
+    // Safety: the borrows passed in last until we return, and we don't return
+    // without cancelling the send.
+    let rc = unsafe {
+        sys_send_async(
+            target,
+            operation,
+            outgoing,
+            incoming,
+            leases,
+            slot,
+            notification,
+        )
+    };
+    if rc != 0 {
+        return Err(rc);
+    }
+
+    let mut pending = PendingSend {
+        slot,
+        notification,
+        result: None,
+    };
+    let r = body(&mut pending);
+    // This is harmless if the send has already been collected. It's done here,
+    // rather than when `pending` is dropped, so that it happens even if `body`
+    // has swapped `pending` with the handle for some other send.
+    sys_cancel_async(slot);
+    Ok(r)
+}
+
+/// An asynchronous send in progress, started by `send_async`.
+pub struct PendingSend {
+    slot: usize,
+    notification: u32,
+    result: Option<(u32, usize)>,
+}
+
+impl PendingSend {
+    /// Checks whether the send has completed, returning the response code and
+    /// length if it has.
+    pub fn poll(&mut self) -> Option<(u32, usize)> {
+        if self.result.is_none() {
+            self.result = sys_collect_async(self.slot);
+        }
+        self.result
+    }
+
+    /// Waits for the send to complete, and returns the response code and
+    /// length.
+    ///
+    /// This consumes the send's notification. Notifications posted to this
+    /// task for other reasons in the meantime are lost, unless they use other
+    /// bits.
+    pub fn wait(&mut self) -> (u32, usize) {
+        loop {
+            if let Some(result) = self.poll() {
+                return result;
+            }
+            // The notification may already have been consumed by another
+            // send's `wait`, if they share a bit, but then the send is complete
+            // and the next `poll` will say so.
+            let _ = sys_recv_closed(&mut [], self.notification, TaskId::KERNEL);
+        }
+    }
+}
+
 /// Suspends the calling task until the kernel time is `>= time`.
 ///
 /// TODO: once we figure out how to convert between ticks and seconds here, this
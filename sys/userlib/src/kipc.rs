@@ -52,6 +52,40 @@ pub fn fault_task(task: usize) {
     assert_eq!(rc, 0);
 }
 
+/// Reads the CPU usage statistics the kernel has accumulated for a task, by
+/// index, since boot.
+pub fn read_task_runtime(task: usize) -> abi::TaskRuntime {
+    // Coerce `task` to a known size (Rust doesn't assume that usize == u32)
+    let task = task as u32;
+    let mut response = [0; core::mem::size_of::<abi::TaskRuntime>()];
+    let (rc, len) = sys_send(
+        TaskId::KERNEL,
+        Kipcnum::ReadTaskRuntime as u16,
+        task.as_bytes(),
+        &mut response,
+        &[],
+    );
+    assert_eq!(rc, 0);
+    ssmarshal::deserialize(&response[..len]).unwrap_lite().0
+}
+
+/// Reads the size of a task's stack, by index, and how much of it the task has
+/// used since it was last started.
+pub fn read_task_stack_usage(task: usize) -> abi::TaskStackUsage {
+    // Coerce `task` to a known size (Rust doesn't assume that usize == u32)
+    let task = task as u32;
+    let mut response = [0; core::mem::size_of::<abi::TaskStackUsage>()];
+    let (rc, len) = sys_send(
+        TaskId::KERNEL,
+        Kipcnum::ReadTaskStackUsage as u16,
+        task.as_bytes(),
+        &mut response,
+        &[],
+    );
+    assert_eq!(rc, 0);
+    ssmarshal::deserialize(&response[..len]).unwrap_lite().0
+}
+
 pub fn system_restart() -> ! {
     let _ = sys_send(TaskId::KERNEL, Kipcnum::Reset as u16, &[], &mut [], &[]);
     panic!();
@@ -70,3 +104,81 @@ pub fn read_image_id() -> u64 {
     assert_eq!(len, 8); // we *really* expect this to be a u64
     ssmarshal::deserialize(&response[..len]).unwrap_lite().0
 }
+
+/// Reads the current priority of a task, by index. This reflects any changes
+/// made by `set_task_priority` since the task last restarted.
+///
+/// Only the supervisor may call this.
+pub fn read_task_priority(task: usize) -> abi::Priority {
+    // Coerce `task` to a known size (Rust doesn't assume that usize == u32)
+    let task = task as u32;
+    let mut response = [0; core::mem::size_of::<u8>()];
+    let (rc, len) = sys_send(
+        TaskId::KERNEL,
+        Kipcnum::ReadTaskPriority as u16,
+        task.as_bytes(),
+        &mut response,
+        &[],
+    );
+    assert_eq!(rc, 0);
+    abi::Priority(ssmarshal::deserialize(&response[..len]).unwrap_lite().0)
+}
+
+/// Changes the priority of a task, by index, until the task is next restarted,
+/// when it reverts to the priority it was built with.
+///
+/// Only the supervisor may call this.
+pub fn set_task_priority(task: usize, priority: abi::Priority) {
+    // Coerce `task` to a known size (Rust doesn't assume that usize == u32)
+    let msg = (task as u32, priority.0);
+    let mut buf = [0; core::mem::size_of::<(u32, u8)>()];
+    ssmarshal::serialize(&mut buf, &msg).unwrap_lite();
+    let (rc, _len) = sys_send(
+        TaskId::KERNEL,
+        Kipcnum::SetTaskPriority as u16,
+        &buf,
+        &mut [],
+        &[],
+    );
+    assert_eq!(rc, 0);
+}
+
+/// Reads the oldest entry in the kernel event log with a sequence number of at
+/// least `seq`, or `None` if no such event has happened yet. To read the whole
+/// log, start from zero and pass one more than the `seq` of each event
+/// returned.
+///
+/// The log survives warm resets, so it may begin with events from earlier
+/// boots. It's always empty if the image doesn't configure one.
+pub fn read_event_log(seq: u32) -> Option<abi::KernelEvent> {
+    let mut response = [0; core::mem::size_of::<Option<abi::KernelEvent>>()];
+    let (rc, len) = sys_send(
+        TaskId::KERNEL,
+        Kipcnum::ReadEventLog as u16,
+        seq.as_bytes(),
+        &mut response,
+        &[],
+    );
+    assert_eq!(rc, 0);
+    ssmarshal::deserialize(&response[..len]).unwrap_lite().0
+}
+
+/// Copies the message that a task passed to `sys_panic` into `buf`, truncating
+/// it if necessary, and returns its length. This only works while the task is
+/// faulted by the panic; for any other task, or once it has been restarted,
+/// the message is empty.
+///
+/// Only the supervisor may call this.
+pub fn read_panic_message(task: usize, buf: &mut [u8]) -> usize {
+    // Coerce `task` to a known size (Rust doesn't assume that usize == u32)
+    let task = task as u32;
+    let (rc, len) = sys_send(
+        TaskId::KERNEL,
+        Kipcnum::ReadPanicMessage as u16,
+        task.as_bytes(),
+        buf,
+        &[],
+    );
+    assert_eq!(rc, 0);
+    len
+}
@@ -42,6 +42,7 @@ use core::marker::PhantomData;
 
 pub mod hl;
 pub mod kipc;
+pub mod shared_region;
 pub mod task_slot;
 pub mod units;
 pub mod util;
@@ -274,6 +275,27 @@ pub enum ClosedRecvError {
     Dead,
 }
 
+/// Performs an "open" RECV, like `sys_recv_open`, but gives up if nothing has
+/// arrived by `deadline` (in ticks since boot).
+///
+/// If `deadline` has already passed, this still collects any message or
+/// notification that is pending, but won't block.
+#[inline(always)]
+pub fn sys_recv_open_with_deadline(
+    buffer: &mut [u8],
+    notification_mask: u32,
+    deadline: u64,
+) -> Result<RecvMessage, TimedOut> {
+    match sys_recv_with_deadline(buffer, notification_mask, None, deadline) {
+        Ok(rm) => Ok(rm),
+        Err(_) => Err(TimedOut),
+    }
+}
+
+/// Error returned by operations that gave up because their deadline passed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TimedOut;
+
 /// General version of RECV that lets you pick closed vs. open receive at
 /// runtime.
 ///
@@ -283,13 +305,39 @@ pub fn sys_recv(
     buffer: &mut [u8],
     notification_mask: u32,
     specific_sender: Option<TaskId>,
+) -> Result<RecvMessage, u32> {
+    sys_recv_inner(buffer, notification_mask, specific_sender, None)
+}
+
+/// General version of RECV with a deadline, in ticks since boot. If the
+/// deadline passes before a message or notification arrives, this fails with
+/// `abi::TIMED_OUT`. Closed receives can also fail with a dead code, as for
+/// `sys_recv`.
+#[inline(always)]
+pub fn sys_recv_with_deadline(
+    buffer: &mut [u8],
+    notification_mask: u32,
+    specific_sender: Option<TaskId>,
+    deadline: u64,
+) -> Result<RecvMessage, u32> {
+    sys_recv_inner(buffer, notification_mask, specific_sender, Some(deadline))
+}
+
+#[inline(always)]
+fn sys_recv_inner(
+    buffer: &mut [u8],
+    notification_mask: u32,
+    specific_sender: Option<TaskId>,
+    deadline: Option<u64>,
 ) -> Result<RecvMessage, u32> {
     use core::mem::MaybeUninit;
 
-    // Flatten option into a packed u32.
+    // Flatten options into a packed u32.
     let specific_sender = specific_sender
         .map(|tid| (1u32 << 31) | u32::from(tid.0))
-        .unwrap_or(0);
+        .unwrap_or(0)
+        | if deadline.is_some() { 1 << 30 } else { 0 };
+    let raw_deadline = deadline.unwrap_or(0);
     let mut out = MaybeUninit::<RawRecvMessage>::uninit();
     let rc = unsafe {
         sys_recv_stub(
@@ -298,6 +346,8 @@ pub fn sys_recv(
             notification_mask,
             specific_sender,
             out.as_mut_ptr(),
+            raw_deadline as u32,
+            (raw_deadline >> 32) as u32,
         )
     };
 
@@ -337,6 +387,8 @@ unsafe extern "C" fn sys_recv_stub(
     _notification_mask: u32,
     _specific_sender: u32,
     _out: *mut RawRecvMessage,
+    _deadline_lo: u32,
+    _deadline_hi: u32,
 ) -> u32 {
     cfg_if::cfg_if! {
         if #[cfg(armv6m)] {
@@ -352,6 +404,12 @@ unsafe extern "C" fn sys_recv_stub(
                 eors r4, r4
                 adds r4, #{sysnum}
                 mov r11, r4
+                @ Load the deadline from the stack, past the output buffer
+                @ pointer and everything we just pushed.
+                ldr r4, [sp, #(10 * 4)]
+                mov r8, r4
+                ldr r4, [sp, #(11 * 4)]
+                mov r9, r4
                 @ Move register arguments into their proper positions.
                 mov r4, r0
                 mov r5, r1
@@ -398,6 +456,9 @@ unsafe extern "C" fn sys_recv_stub(
                 @ is preserved during our syscall. Since we just pushed a
                 @ bunch of stuff, we need to read *past* it.
                 ldr r3, [sp, #(8 * 4)]
+                @ The deadline follows it.
+                ldr r8, [sp, #(9 * 4)]
+                ldr r9, [sp, #(10 * 4)]
                 @ Load the constant syscall number.
                 mov r11, {sysnum}
 
@@ -529,8 +590,37 @@ unsafe extern "C" fn sys_reply_stub(
 /// had it been set earlier -- that is, if the deadline is `<=` the current time
 /// -- the `notifications` will be posted immediately and the timer will not be
 /// enabled.
+///
+/// This operates on the task's first timer, timer 0. Tasks configured with more
+/// than one timer can use the others with `sys_arm_timer` and
+/// `sys_cancel_timer`.
 #[inline(always)]
 pub fn sys_set_timer(deadline: Option<u64>, notifications: u32) {
+    set_timer(0, deadline, notifications)
+}
+
+/// Arms timer number `timer` to post `notifications` to this task at
+/// `deadline` (in ticks since boot), replacing any previous setting of that
+/// timer. This behaves like `sys_set_timer`, including when `deadline` has
+/// already passed.
+///
+/// `timer` must be less than the number of timers given to this task in the
+/// `timers` key of its `app.toml` entry (1 by default), or the task will be
+/// faulted.
+#[inline(always)]
+pub fn sys_arm_timer(timer: usize, deadline: u64, notifications: u32) {
+    set_timer(timer, Some(deadline), notifications)
+}
+
+/// Disarms timer number `timer`, if it was armed. The same range restrictions
+/// apply as for `sys_arm_timer`.
+#[inline(always)]
+pub fn sys_cancel_timer(timer: usize) {
+    set_timer(timer, None, 0)
+}
+
+#[inline(always)]
+fn set_timer(timer: usize, deadline: Option<u64>, notifications: u32) {
     let raw_deadline = deadline.unwrap_or(0);
     unsafe {
         sys_set_timer_stub(
@@ -538,6 +628,7 @@ pub fn sys_set_timer(deadline: Option<u64>, notifications: u32) {
             raw_deadline as u32,
             (raw_deadline >> 32) as u32,
             notifications,
+            timer as u32,
         )
     }
 }
@@ -551,6 +642,121 @@ unsafe extern "C" fn sys_set_timer_stub(
     _deadline_lo: u32,
     _deadline_hi: u32,
     _notification: u32,
+    _timer: u32,
+) {
+    cfg_if::cfg_if! {
+        if #[cfg(armv6m)] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4-r7, lr}}
+                mov r4, r8
+                mov r5, r11
+                push {{r4, r5}}
+
+                @ Load the constant syscall number.
+                eors r4, r4
+                adds r4, #{sysnum}
+                mov r11, r4
+                @ Load the timer number from the stack, past everything we
+                @ just pushed.
+                ldr r4, [sp, #(7 * 4)]
+                mov r8, r4
+                @ Move register arguments into place.
+                mov r4, r0
+                mov r5, r1
+                mov r6, r2
+                mov r7, r3
+
+                @ To the kernel!
+                svc #0
+
+                @ This call has no results.
+
+                @ Restore the registers we used and return.
+                pop {{r4, r5}}
+                mov r8, r4
+                mov r11, r5
+                pop {{r4-r7, pc}}
+                ",
+                sysnum = const Sysnum::SetTimer as u32,
+                options(noreturn),
+            )
+        } else if #[cfg(any(armv7m, armv8m))] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4-r9, r11, lr}}
+
+                @ Move register arguments into place.
+                mov r4, r0
+                mov r5, r1
+                mov r6, r2
+                mov r7, r3
+                @ Load the timer number from the stack, past everything we
+                @ just pushed.
+                ldr r8, [sp, #(8 * 4)]
+                @ Load the constant syscall number.
+                mov r11, {sysnum}
+
+                @ To the kernel!
+                svc #0
+
+                @ This call has no results.
+
+                @ Restore the registers we used and return.
+                pop {{r4-r9, r11, pc}}
+                ",
+                sysnum = const Sysnum::SetTimer as u32,
+                options(noreturn),
+            )
+        } else {
+            compile_error!("missing sys_set_timer_stub for ARM profile")
+        }
+    }
+}
+
+/// Sends a message, like `sys_send`, but gives up if the exchange hasn't
+/// completed by `deadline` (in ticks since boot). The deadline covers both
+/// waiting for `target` to receive the message and waiting for its reply.
+///
+/// On timeout, this returns `abi::TIMED_OUT` as the response code, with no
+/// reply.
+///
+/// Note that a server that has already received the message may still act on
+/// it after we give up. Its eventual reply is discarded, even if we've sent it
+/// another message in the meantime: the kernel counts the replies that
+/// `target` owes us for abandoned messages, and drops that many before
+/// delivering one. That count is kept per server, not per message, so if a
+/// server that defers replies answers our next message before the abandoned
+/// one, the answer to the next message is the one discarded, and the late
+/// reply is delivered in its place. Only one server's count is kept at a time,
+/// too, so timing out on a second server forgets about the first.
+///
+/// This is best used with idempotent operations, with servers that reply in
+/// the order they receive, or with servers that are presumed hung and about to
+/// be restarted.
+#[inline(always)]
+pub fn sys_send_with_deadline(
+    target: TaskId,
+    operation: u16,
+    outgoing: &[u8],
+    incoming: &mut [u8],
+    leases: &[Lease<'_>],
+    deadline: u64,
+) -> (u32, usize) {
+    unsafe {
+        sys_set_send_deadline_stub(1, deadline as u32, (deadline >> 32) as u32)
+    }
+    sys_send(target, operation, outgoing, incoming, leases)
+}
+
+/// Core implementation of the SET_SEND_DEADLINE syscall.
+///
+/// See the note on syscall stubs at the top of this module for rationale.
+#[naked]
+unsafe extern "C" fn sys_set_send_deadline_stub(
+    _set_deadline: u32,
+    _deadline_lo: u32,
+    _deadline_hi: u32,
 ) {
     cfg_if::cfg_if! {
         if #[cfg(armv6m)] {
@@ -568,7 +774,6 @@ unsafe extern "C" fn sys_set_timer_stub(
                 mov r4, r0
                 mov r5, r1
                 mov r6, r2
-                mov r7, r3
 
                 @ To the kernel!
                 svc #0
@@ -580,7 +785,7 @@ unsafe extern "C" fn sys_set_timer_stub(
                 mov r11, r4
                 pop {{r4-r7, pc}}
                 ",
-                sysnum = const Sysnum::SetTimer as u32,
+                sysnum = const Sysnum::SetSendDeadline as u32,
                 options(noreturn),
             )
         } else if #[cfg(any(armv7m, armv8m))] {
@@ -592,7 +797,6 @@ unsafe extern "C" fn sys_set_timer_stub(
                 mov r4, r0
                 mov r5, r1
                 mov r6, r2
-                mov r7, r3
                 @ Load the constant syscall number.
                 mov r11, {sysnum}
 
@@ -604,11 +808,295 @@ unsafe extern "C" fn sys_set_timer_stub(
                 @ Restore the registers we used and return.
                 pop {{r4-r7, r11, pc}}
                 ",
-                sysnum = const Sysnum::SetTimer as u32,
+                sysnum = const Sysnum::SetSendDeadline as u32,
                 options(noreturn),
             )
         } else {
-            compile_error!("missing sys_set_timer_stub for ARM profile")
+            compile_error!("missing sys_set_send_deadline_stub for ARM profile")
+        }
+    }
+}
+
+/// Starts a send to `target` without waiting for the reply.
+///
+/// This returns as soon as the message has been queued. When `target` replies,
+/// the kernel deposits the response in `incoming` and posts `notification` to
+/// this task; `sys_collect_async` then returns the response code and length.
+///
+/// `slot` picks one of the async send slots given to this task by the
+/// `async-sends` key in `app.toml`, and must not already be in use. A task can
+/// only have one exchange in progress with a given server at a time, whether
+/// it was started by this or by an ordinary `sys_send`.
+///
+/// This returns 0 if the message was queued, or a dead code if `target` has
+/// restarted, in which case nothing was sent.
+///
+/// # Safety
+///
+/// The kernel goes on using `outgoing`, `incoming`, and the memory described
+/// by `leases` after this returns, until the send is collected or cancelled
+/// with `sys_cancel_async`. The caller must keep all of it valid, and must not
+/// touch `incoming` or any writable lease, until then.
+#[inline(always)]
+pub unsafe fn sys_send_async(
+    target: TaskId,
+    operation: u16,
+    outgoing: &[u8],
+    incoming: &mut [u8],
+    leases: &[Lease<'_>],
+    slot: usize,
+    notification: u32,
+) -> u32 {
+    sys_set_send_async_stub(slot, notification);
+    sys_send(target, operation, outgoing, incoming, leases).0
+}
+
+/// Core implementation of the SET_SEND_ASYNC syscall.
+///
+/// See the note on syscall stubs at the top of this module for rationale.
+#[naked]
+unsafe extern "C" fn sys_set_send_async_stub(_slot: usize, _notification: u32) {
+    cfg_if::cfg_if! {
+        if #[cfg(armv6m)] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4-r5, lr}}
+                mov r4, r11
+                push {{r4}}
+
+                @ Load the constant syscall number.
+                eors r4, r4
+                adds r4, #{sysnum}
+                mov r11, r4
+                @ Move register arguments into place.
+                mov r4, r0
+                mov r5, r1
+
+                @ To the kernel!
+                svc #0
+
+                @ This call has no results.
+
+                @ Restore the registers we used and return.
+                pop {{r4}}
+                mov r11, r4
+                pop {{r4-r5, pc}}
+                ",
+                sysnum = const Sysnum::SetSendAsync as u32,
+                options(noreturn),
+            )
+        } else if #[cfg(any(armv7m, armv8m))] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4-r5, r11, lr}}
+
+                @ Move register arguments into place.
+                mov r4, r0
+                mov r5, r1
+                @ Load the constant syscall number.
+                mov r11, {sysnum}
+
+                @ To the kernel!
+                svc #0
+
+                @ This call has no results.
+
+                @ Restore the registers we used and return.
+                pop {{r4-r5, r11, pc}}
+                ",
+                sysnum = const Sysnum::SetSendAsync as u32,
+                options(noreturn),
+            )
+        } else {
+            compile_error!("missing sys_set_send_async_stub for ARM profile")
+        }
+    }
+}
+
+/// Checks on the async send in `slot`.
+///
+/// If it has completed, this returns its response code and length, and frees
+/// the slot for reuse. Otherwise -- if it's still waiting on the server, or the
+/// slot is idle -- this returns `None`.
+#[inline(always)]
+pub fn sys_collect_async(slot: usize) -> Option<(u32, usize)> {
+    let raw = collect_async(slot, false);
+    if raw.status == AsyncSendStatus::Complete as u32 {
+        Some((raw.code, raw.len))
+    } else {
+        None
+    }
+}
+
+/// Abandons the async send in `slot`, freeing the slot.
+///
+/// If the server has not yet received the message, it never will. If it has,
+/// its reply will be discarded, and any further attempt to use the leases will
+/// fault the server as though we had been restarted. If the send had already
+/// completed, its result is discarded.
+#[inline(always)]
+pub fn sys_cancel_async(slot: usize) {
+    collect_async(slot, true);
+}
+
+fn collect_async(slot: usize, cancel: bool) -> RawAsyncStatus {
+    use core::mem::MaybeUninit;
+
+    let mut raw = MaybeUninit::<RawAsyncStatus>::uninit();
+    unsafe {
+        sys_collect_async_stub(slot, cancel as u32, raw.as_mut_ptr());
+    }
+    // Safety: stub completely initializes record
+    unsafe { raw.assume_init() }
+}
+
+#[repr(C)] // loaded from assembly, field order must not change
+struct RawAsyncStatus {
+    status: u32,
+    code: u32,
+    len: usize,
+}
+
+/// Core implementation of the COLLECT_ASYNC syscall.
+///
+/// See the note on syscall stubs at the top of this module for rationale.
+#[naked]
+unsafe extern "C" fn sys_collect_async_stub(
+    _slot: usize,
+    _cancel: u32,
+    _out: *mut RawAsyncStatus,
+) {
+    cfg_if::cfg_if! {
+        if #[cfg(armv6m)] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4-r6, lr}}
+                mov r4, r11
+                push {{r4}}
+
+                @ Load the constant syscall number.
+                eors r4, r4
+                adds r4, #{sysnum}
+                mov r11, r4
+                @ Move register arguments into place.
+                mov r4, r0
+                mov r5, r1
+
+                @ To the kernel!
+                svc #0
+
+                @ Move the results into place.
+                stm r2!, {{r4-r6}}
+
+                @ Restore the registers we used and return.
+                pop {{r4}}
+                mov r11, r4
+                pop {{r4-r6, pc}}
+                ",
+                sysnum = const Sysnum::CollectAsync as u32,
+                options(noreturn),
+            )
+        } else if #[cfg(any(armv7m, armv8m))] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4-r6, r11}}
+
+                @ Move register arguments into place.
+                mov r4, r0
+                mov r5, r1
+                @ Load the constant syscall number.
+                mov r11, {sysnum}
+
+                @ To the kernel!
+                svc #0
+
+                @ Move the results into place.
+                stm r2, {{r4-r6}}
+
+                @ Restore the registers we used and return.
+                pop {{r4-r6, r11}}
+                bx lr
+                ",
+                sysnum = const Sysnum::CollectAsync as u32,
+                options(noreturn),
+            )
+        } else {
+            compile_error!("missing sys_collect_async_stub for ARM profile")
+        }
+    }
+}
+
+/// Puts the processor to sleep until there's something to do.
+///
+/// This is meant for the idle task. If no other task is runnable, the kernel
+/// sleeps until an interrupt arrives or the next timer or IPC deadline comes
+/// due, without taking the periodic ticks in between. If some other task is
+/// runnable, this returns without sleeping.
+///
+/// `state` selects a sleep state: 0 is the ordinary, shallow kind. Deeper
+/// states are chip-specific, and only do anything if the board's kernel
+/// configuration provides hooks for them; otherwise they're treated like 0.
+#[inline(always)]
+pub fn sys_idle(state: u32) {
+    unsafe { sys_idle_stub(state) }
+}
+
+/// Core implementation of the IDLE syscall.
+///
+/// See the note on syscall stubs at the top of this module for rationale.
+#[naked]
+unsafe extern "C" fn sys_idle_stub(_state: u32) {
+    cfg_if::cfg_if! {
+        if #[cfg(armv6m)] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4, lr}}
+                mov r4, r11
+                push {{r4}}
+
+                @ Load the constant syscall number.
+                eors r4, r4
+                adds r4, #{sysnum}
+                mov r11, r4
+                @ Move register arguments into place.
+                mov r4, r0
+
+                @ To the kernel!
+                svc #0
+
+                @ This call has no results.
+
+                @ Restore the registers we used and return.
+                pop {{r4}}
+                mov r11, r4
+                pop {{r4, pc}}
+                ",
+                sysnum = const Sysnum::Idle as u32,
+                options(noreturn),
+            )
+        } else if #[cfg(any(armv7m, armv8m))] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4, r11, lr}}
+
+                @ Move register arguments into place.
+                mov r4, r0
+                @ Load the constant syscall number.
+                mov r11, {sysnum}
+
+                @ To the kernel!
+                svc #0
+
+                @ This call has no results.
+
+                @ Restore the registers we used and return.
+                pop {{r4, r11, pc}}
+                ",
+                sysnum = const Sysnum::Idle as u32,
+                options(noreturn),
+            )
+        } else {
+            compile_error!("missing sys_idle_stub for ARM profile")
         }
     }
 }
@@ -806,6 +1294,194 @@ struct BorrowWriteArgs {
     src_len: usize,
 }
 
+/// Performs several borrow reads from `lender` in one syscall, as described
+/// by `segments`: each one copies from a lease, starting at an offset, into a
+/// buffer in this task. Returns the response code and total bytes copied, as
+/// for `sys_borrow_read`.
+///
+/// If a segment fails, the segments before it have still been copied.
+///
+/// # Safety
+///
+/// Each segment's buffer must be memory that this task could safely write
+/// through a `&mut [u8]`.
+#[inline(always)]
+pub unsafe fn sys_borrow_readv(
+    lender: TaskId,
+    segments: &[BorrowSegment],
+) -> (u32, usize) {
+    sys_borrow_readv_stub(lender.0 as u32, segments.as_ptr(), segments.len())
+        .into()
+}
+
+/// Core implementation of the BORROW_READV syscall.
+///
+/// See the note on syscall stubs at the top of this module for rationale.
+#[naked]
+unsafe extern "C" fn sys_borrow_readv_stub(
+    _lender: u32,
+    _segments: *const BorrowSegment,
+    _count: usize,
+) -> RcLen {
+    cfg_if::cfg_if! {
+        if #[cfg(armv6m)] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4-r6, lr}}
+                mov r4, r11
+                push {{r4}}
+
+                @ Load the constant syscall number.
+                movs r4, #0
+                adds r4, #{sysnum}
+                mov r11, r4
+
+                @ Move register arguments into place.
+                mov r4, r0
+                mov r5, r1
+                mov r6, r2
+
+                @ To the kernel!
+                svc #0
+
+                @ Move the results into place.
+                mov r0, r4
+                mov r1, r5
+
+                @ Restore the registers we used and return.
+                pop {{r4}}
+                mov r11, r4
+                pop {{r4-r6, pc}}
+                ",
+                sysnum = const Sysnum::BorrowReadv as u32,
+                options(noreturn),
+            )
+        } else if #[cfg(any(armv7m, armv8m))] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4-r6, r11}}
+
+                @ Move register arguments into place.
+                mov r4, r0
+                mov r5, r1
+                mov r6, r2
+                @ Load the constant syscall number.
+                mov r11, {sysnum}
+
+                @ To the kernel!
+                svc #0
+
+                @ Move the results into place.
+                mov r0, r4
+                mov r1, r5
+
+                @ Restore the registers we used and return.
+                pop {{r4-r6, r11}}
+                bx lr
+                ",
+                sysnum = const Sysnum::BorrowReadv as u32,
+                options(noreturn),
+            )
+        } else {
+            compile_error!("missing sys_borrow_readv_stub for ARM profile")
+        }
+    }
+}
+
+/// Performs several borrow writes to `lender` in one syscall, as described by
+/// `segments`: each one copies from a buffer in this task into a lease,
+/// starting at an offset. Returns the response code and total bytes copied,
+/// as for `sys_borrow_write`.
+///
+/// If a segment fails, the segments before it have still been copied.
+///
+/// # Safety
+///
+/// Each segment's buffer must be memory that this task could safely read
+/// through a `&[u8]`.
+#[inline(always)]
+pub unsafe fn sys_borrow_writev(
+    lender: TaskId,
+    segments: &[BorrowSegment],
+) -> (u32, usize) {
+    sys_borrow_writev_stub(lender.0 as u32, segments.as_ptr(), segments.len())
+        .into()
+}
+
+/// Core implementation of the BORROW_WRITEV syscall.
+///
+/// See the note on syscall stubs at the top of this module for rationale.
+#[naked]
+unsafe extern "C" fn sys_borrow_writev_stub(
+    _lender: u32,
+    _segments: *const BorrowSegment,
+    _count: usize,
+) -> RcLen {
+    cfg_if::cfg_if! {
+        if #[cfg(armv6m)] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4-r6, lr}}
+                mov r4, r11
+                push {{r4}}
+
+                @ Load the constant syscall number.
+                movs r4, #0
+                adds r4, #{sysnum}
+                mov r11, r4
+
+                @ Move register arguments into place.
+                mov r4, r0
+                mov r5, r1
+                mov r6, r2
+
+                @ To the kernel!
+                svc #0
+
+                @ Move the results into place.
+                mov r0, r4
+                mov r1, r5
+
+                @ Restore the registers we used and return.
+                pop {{r4}}
+                mov r11, r4
+                pop {{r4-r6, pc}}
+                ",
+                sysnum = const Sysnum::BorrowWritev as u32,
+                options(noreturn),
+            )
+        } else if #[cfg(any(armv7m, armv8m))] {
+            arch::asm!("
+                @ Spill the registers we're about to use to pass stuff.
+                push {{r4-r6, r11}}
+
+                @ Move register arguments into place.
+                mov r4, r0
+                mov r5, r1
+                mov r6, r2
+                @ Load the constant syscall number.
+                mov r11, {sysnum}
+
+                @ To the kernel!
+                svc #0
+
+                @ Move the results into place.
+                mov r0, r4
+                mov r1, r5
+
+                @ Restore the registers we used and return.
+                pop {{r4-r6, r11}}
+                bx lr
+                ",
+                sysnum = const Sysnum::BorrowWritev as u32,
+                options(noreturn),
+            )
+        } else {
+            compile_error!("missing sys_borrow_writev_stub for ARM profile")
+        }
+    }
+}
+
 #[inline(always)]
 pub fn sys_borrow_info(lender: TaskId, index: usize) -> Option<BorrowInfo> {
     use core::mem::MaybeUninit;
@@ -1055,11 +1731,18 @@ unsafe extern "C" fn sys_panic_stub(_msg: *const u8, _len: usize) -> ! {
 /// `now` is monotonically advancing and can't be changed.
 #[inline(always)]
 pub fn sys_get_timer() -> TimerState {
+    sys_read_timer(0)
+}
+
+/// Like `sys_get_timer`, but reads timer number `timer`, which must be less
+/// than the number of timers given to this task (see `sys_arm_timer`).
+#[inline(always)]
+pub fn sys_read_timer(timer: usize) -> TimerState {
     use core::mem::MaybeUninit;
 
     let mut out = MaybeUninit::<RawTimerState>::uninit();
     unsafe {
-        sys_get_timer_stub(out.as_mut_ptr());
+        sys_get_timer_stub(out.as_mut_ptr(), timer as u32);
     }
     // Safety: stub fully initializes output struct.
     let out = unsafe { out.assume_init() };
@@ -1099,7 +1782,7 @@ struct RawTimerState {
 ///
 /// See the note on syscall stubs at the top of this module for rationale.
 #[naked]
-unsafe extern "C" fn sys_get_timer_stub(_out: *mut RawTimerState) {
+unsafe extern "C" fn sys_get_timer_stub(_out: *mut RawTimerState, _timer: u32) {
     cfg_if::cfg_if! {
         if #[cfg(armv6m)] {
             arch::asm!("
@@ -1114,6 +1797,8 @@ unsafe extern "C" fn sys_get_timer_stub(_out: *mut RawTimerState) {
                 eors r4, r4
                 adds r4, #{sysnum}
                 mov r11, r4
+                @ Pass the timer number.
+                mov r4, r1
 
                 @ To the kernel!
                 svc #0
@@ -1138,6 +1823,8 @@ unsafe extern "C" fn sys_get_timer_stub(_out: *mut RawTimerState) {
             arch::asm!("
                 @ Spill the registers we're about to use to pass stuff.
                 push {{r4-r11}}
+                @ Pass the timer number.
+                mov r4, r1
                 @ Load the constant syscall number.
                 mov r11, {sysnum}
 
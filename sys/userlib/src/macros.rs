@@ -5,6 +5,11 @@
 pub use bstringify;
 pub use paste;
 
+// `sys_log!` formats a message on the target and writes it out over ITM or
+// semihosting, depending on which `log-*` feature is enabled, which is only
+// useful with a debugger attached. It's meant for the supervisor and the test
+// harness, which can't send to another task; other tasks should log through
+// the log task instead (see `task-log-api`).
 cfg_if::cfg_if! {
     if #[cfg(feature = "log-itm")] {
         #[macro_export]
@@ -86,3 +91,28 @@ macro_rules! task_slot {
         }
     };
 }
+
+/// Produces a `SharedRegion` for the shared region `$name`, which must be
+/// declared in `app.toml` with this task listed as one of its users.
+#[macro_export]
+macro_rules! shared_region {
+    ($name:ident) => {
+        $crate::macros::paste::paste! {{
+            #[allow(non_upper_case_globals)]
+            extern "C" {
+                static mut [< __shared_region_ $name _base >]: [u8; 0];
+                static mut [< __shared_region_ $name _end >]: [u8; 0];
+            }
+            // Safety: the build system only defines these symbols for regions
+            // that are mapped into this task.
+            unsafe {
+                $crate::shared_region::SharedRegion::from_bounds(
+                    core::ptr::addr_of_mut!([< __shared_region_ $name _base >])
+                        as *mut u8,
+                    core::ptr::addr_of_mut!([< __shared_region_ $name _end >])
+                        as *mut u8,
+                )
+            }
+        }}
+    };
+}
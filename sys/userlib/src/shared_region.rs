@@ -0,0 +1,74 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Access to memory shared between tasks.
+//!
+//! A shared region is declared in the application's `app.toml` under
+//! `[shared-regions]`, which names the tasks that may read and write it. The
+//! build system places each region in memory, maps it into those tasks, and
+//! tells each task where it ended up through a pair of linker symbols. The
+//! `shared_region!` macro finds those symbols and produces a `SharedRegion`.
+//!
+//! The kernel does nothing to coordinate access to a shared region: that's up
+//! to the tasks involved, usually by exchanging messages or notifications to
+//! hand parts of the region back and forth.
+
+/// A region of memory shared with other tasks.
+pub struct SharedRegion {
+    base: *mut u8,
+    len: usize,
+}
+
+impl SharedRegion {
+    /// Creates a `SharedRegion` covering `base..end`.
+    ///
+    /// # Safety
+    ///
+    /// The range must be mapped into the calling task for as long as the
+    /// result is used. Use `shared_region!` rather than calling this directly.
+    pub unsafe fn from_bounds(base: *mut u8, end: *mut u8) -> Self {
+        Self {
+            base,
+            len: end as usize - base as usize,
+        }
+    }
+
+    /// Returns the address of the start of the region.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.base
+    }
+
+    /// Returns the size of the region in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks whether the region is zero-sized. (It never is, since the build
+    /// system rejects those, but clippy likes to see this next to `len`.)
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Borrows the region's contents.
+    ///
+    /// # Safety
+    ///
+    /// Other tasks may be able to write the region at any time. The caller
+    /// must ensure, by agreement with them, that none do so while the result
+    /// is live.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        core::slice::from_raw_parts(self.base, self.len)
+    }
+
+    /// Borrows the region's contents mutably.
+    ///
+    /// # Safety
+    ///
+    /// As for `as_slice`, and additionally no other task may read the region
+    /// while the result is live. If the task was only given read-only access in
+    /// `app.toml`, writing through the result will fault.
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
+        core::slice::from_raw_parts_mut(self.base, self.len)
+    }
+}
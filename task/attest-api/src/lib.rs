@@ -0,0 +1,34 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Client API for the attestation task.
+
+#![no_std]
+
+use derive_idol_err::IdolError;
+use userlib::{sys_send, FromPrimitive};
+
+/// Length of each measurement in the log.
+pub const MEASUREMENT_LEN: usize = 32;
+
+/// Length of a quote's nonce. It's fixed so that the signed message, the
+/// nonce followed by the log, can only be split one way.
+pub const NONCE_LEN: usize = 32;
+
+/// Length of a quote's Ed25519 signature.
+pub const SIGNATURE_LEN: usize = 64;
+
+#[derive(Copy, Clone, Debug, FromPrimitive, Eq, PartialEq, IdolError)]
+#[repr(u32)]
+pub enum AttestError {
+    /// Stage0 didn't hand off an alias key and certs, e.g. because DICE
+    /// isn't enabled.
+    NoCerts = 1,
+    InvalidIndex = 2,
+    OutOfRange = 3,
+    BadLength = 4,
+    LogFull = 5,
+}
+
+include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
@@ -0,0 +1,34 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cfg = build_util::task_maybe_config::<Config>()?.unwrap_or_default();
+
+    let task_ids = build_util::task_ids();
+    let allowed_callers =
+        task_ids.remap_allowed_caller_names_to_ids(&cfg.allowed_callers)?;
+
+    idol::server::build_restricted_server_support(
+        "../../idl/attest.idol",
+        "server_stub.rs",
+        idol::server::ServerStyle::InOrder,
+        &allowed_callers,
+    )?;
+
+    Ok(())
+}
+
+/// Attestation task-level configuration.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    /// Map of operation names to tasks allowed to call them. Anything that
+    /// can call `record` can put measurements in the log that quotes vouch
+    /// for, so apps should restrict it to the tasks that measure.
+    #[serde(default)]
+    allowed_callers: BTreeMap<String, Vec<String>>,
+}
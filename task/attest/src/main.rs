@@ -0,0 +1,193 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Attestation task.
+//!
+//! This serves the alias key's cert chain, which stage0 hands off to us (see
+//! `dice::AliasData`), and a log of measurements recorded since boot, and
+//! answers challenges by signing a nonce followed by the log with the alias
+//! key. See the README for how a verifier uses these.
+
+#![no_std]
+#![no_main]
+
+use dice::{AliasData, Cert, HandoffData, SeedBuf};
+use idol_runtime::{ClientError, Leased, LenLimit, RequestError, R, W};
+use salty::signature::Keypair;
+use task_attest_api::{AttestError, MEASUREMENT_LEN, NONCE_LEN, SIGNATURE_LEN};
+use userlib::*;
+
+/// Number of measurements that the log can hold.
+const LOG_CAPACITY: usize = 16;
+
+const LOG_SIZE: usize = LOG_CAPACITY * MEASUREMENT_LEN;
+
+const MAX_LEASE: usize = 4096;
+
+/// The alias cert, and the DeviceId cert that signed it.
+const CERT_CHAIN_LEN: u32 = 2;
+
+struct Alias {
+    data: AliasData,
+    keypair: Keypair,
+}
+
+struct ServerImpl {
+    alias: Option<Alias>,
+    log: [u8; LOG_SIZE],
+    log_len: usize,
+}
+
+impl ServerImpl {
+    fn alias(&self) -> Result<&Alias, RequestError<AttestError>> {
+        self.alias
+            .as_ref()
+            .ok_or_else(|| AttestError::NoCerts.into())
+    }
+
+    fn cert(&self, index: u32) -> Result<&[u8], RequestError<AttestError>> {
+        let alias = self.alias()?;
+        match index {
+            0 => Ok(alias.data.alias_cert.as_bytes()),
+            1 => Ok(alias.data.deviceid_cert.as_bytes()),
+            _ => Err(AttestError::InvalidIndex.into()),
+        }
+    }
+}
+
+impl idl::InOrderAttestImpl for ServerImpl {
+    fn cert_chain_len(
+        &mut self,
+        _: &RecvMessage,
+    ) -> Result<u32, RequestError<AttestError>> {
+        self.alias()?;
+        Ok(CERT_CHAIN_LEN)
+    }
+
+    fn cert_len(
+        &mut self,
+        _: &RecvMessage,
+        index: u32,
+    ) -> Result<u32, RequestError<AttestError>> {
+        Ok(self.cert(index)?.len() as u32)
+    }
+
+    fn cert(
+        &mut self,
+        _: &RecvMessage,
+        index: u32,
+        offset: u32,
+        dest: LenLimit<Leased<W, [u8]>, MAX_LEASE>,
+    ) -> Result<(), RequestError<AttestError>> {
+        write_at(self.cert(index)?, offset, dest)
+    }
+
+    fn record(
+        &mut self,
+        _: &RecvMessage,
+        measurement: LenLimit<Leased<R, [u8]>, MEASUREMENT_LEN>,
+    ) -> Result<(), RequestError<AttestError>> {
+        if measurement.len() != MEASUREMENT_LEN {
+            return Err(AttestError::BadLength.into());
+        }
+        let end = self.log_len + MEASUREMENT_LEN;
+        if end > LOG_SIZE {
+            return Err(AttestError::LogFull.into());
+        }
+
+        measurement
+            .read_range(0..MEASUREMENT_LEN, &mut self.log[self.log_len..end])
+            .map_err(|_| RequestError::Fail(ClientError::WentAway))?;
+        self.log_len = end;
+        Ok(())
+    }
+
+    fn log_len(
+        &mut self,
+        _: &RecvMessage,
+    ) -> Result<u32, RequestError<AttestError>> {
+        Ok(self.log_len as u32)
+    }
+
+    fn log(
+        &mut self,
+        _: &RecvMessage,
+        offset: u32,
+        dest: LenLimit<Leased<W, [u8]>, MAX_LEASE>,
+    ) -> Result<(), RequestError<AttestError>> {
+        write_at(&self.log[..self.log_len], offset, dest)
+    }
+
+    fn quote(
+        &mut self,
+        _: &RecvMessage,
+        nonce: LenLimit<Leased<R, [u8]>, NONCE_LEN>,
+        signature: LenLimit<Leased<W, [u8]>, SIGNATURE_LEN>,
+    ) -> Result<u32, RequestError<AttestError>> {
+        let alias = self.alias()?;
+        if nonce.len() != NONCE_LEN || signature.len() != SIGNATURE_LEN {
+            return Err(AttestError::BadLength.into());
+        }
+
+        // The signed message is the nonce followed by the log as it is now;
+        // we return the log's length so that the caller knows how much of
+        // it to check the signature against. The nonce's length is fixed, so
+        // there's only one way to split the message.
+        let mut message = [0; NONCE_LEN + LOG_SIZE];
+        nonce
+            .read_range(0..NONCE_LEN, &mut message[..NONCE_LEN])
+            .map_err(|_| RequestError::Fail(ClientError::WentAway))?;
+        let len = NONCE_LEN + self.log_len;
+        message[NONCE_LEN..len].copy_from_slice(&self.log[..self.log_len]);
+
+        let sig = alias.keypair.sign(&message[..len]);
+        signature
+            .write_range(0..SIGNATURE_LEN, &sig.to_bytes())
+            .map_err(|_| RequestError::Fail(ClientError::WentAway))?;
+        Ok(self.log_len as u32)
+    }
+}
+
+/// Fills `dest` from `src`, starting `offset` bytes in.
+fn write_at<const N: usize>(
+    src: &[u8],
+    offset: u32,
+    dest: LenLimit<Leased<W, [u8]>, N>,
+) -> Result<(), RequestError<AttestError>> {
+    let start = offset as usize;
+    let src = start
+        .checked_add(dest.len())
+        .and_then(|end| src.get(start..end))
+        .ok_or(AttestError::OutOfRange)?;
+
+    dest.write_range(0..src.len(), src)
+        .map_err(|_| RequestError::Fail(ClientError::WentAway))
+}
+
+#[export_name = "main"]
+fn main() -> ! {
+    // If stage0 didn't hand anything off (e.g. because DICE isn't enabled),
+    // we still run, but can only say so.
+    let alias = AliasData::from_mem().map(|data| {
+        let keypair = Keypair::from(data.alias_seed.as_bytes());
+        Alias { data, keypair }
+    });
+
+    let mut server = ServerImpl {
+        alias,
+        log: [0; LOG_SIZE],
+        log_len: 0,
+    };
+    let mut incoming = [0u8; idl::INCOMING_SIZE];
+
+    loop {
+        idol_runtime::dispatch(&mut incoming, &mut server);
+    }
+}
+
+mod idl {
+    use task_attest_api::AttestError;
+
+    include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
+}
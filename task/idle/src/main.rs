@@ -5,8 +5,8 @@
 #![no_std]
 #![no_main]
 
-// Make sure we actually link in userlib, despite not using any of it explicitly
-// - we need it for our _start routine.
+// Make sure we actually link in userlib, even in configurations that don't use
+// any of it explicitly -- we need it for our _start routine.
 extern crate userlib;
 
 #[export_name = "main"]
@@ -22,6 +22,13 @@ fn main() -> ! {
             // by a trap, bringing the system to a halt with no tasks runnable.
             // So, do not get clever and remove this.
             cortex_m::asm::nop();
+        } else if cfg!(feature = "tickless") {
+            // Ask the kernel to sleep until the next interrupt or deadline,
+            // skipping any ticks in between. With the deep-sleep feature, we
+            // ask for sleep state 1, which the kernel hands to a chip-specific
+            // hook if the board provides one.
+            let state = if cfg!(feature = "deep-sleep") { 1 } else { 0 };
+            userlib::sys_idle(state);
         } else {
             // Wait For Interrupt to pause the processor until an ISR arrives,
             // which could wake some higher-priority task.
@@ -6,8 +6,10 @@
 
 #![no_std]
 
+use derive_idol_err::IdolError;
 use serde::{Deserialize, Serialize};
 use userlib::*;
+use zerocopy::{AsBytes, FromBytes};
 
 /// Platform-agnostic (but heavily influenced) reset status bits.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -25,4 +27,84 @@ pub enum ResetReason {
     Unknown, // TODO remove and use `Option<ResetReason>` once we switch to hubpack
 }
 
+/// What the supervisor recorded about software-initiated trouble in the boot
+/// before the most recent reset. This complements the hardware `ResetReason`:
+/// a `SystemCall` reset, for instance, is explained by `reset_requested_by`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashRecord {
+    /// The last task fault seen before the reset, if any. If it was a panic,
+    /// the message can be read with `get_crash_panic_message`.
+    pub fault: Option<TaskFault>,
+    /// Index of the task that called `request_reset`, if the reset happened
+    /// that way.
+    pub reset_requested_by: Option<u16>,
+    /// Index of the task that missed its heartbeat deadline, if the
+    /// supervisor stopped feeding the hardware watchdog because of it.
+    pub missed_heartbeat: Option<u16>,
+}
+
+/// A task fault, as kept in a `CrashRecord`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskFault {
+    /// Index of the task that faulted.
+    pub task: u16,
+    /// What the fault was.
+    pub fault: FaultInfo,
+}
+
+/// How often the supervisor has restarted a task after faults, as reported by
+/// `get_restart_counters`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestartCounters {
+    /// Number of times the task has been restarted after a fault since boot.
+    pub restarts: u32,
+    /// Number of restarts in the task's current restart-policy window. This
+    /// is always zero for tasks without a restart policy.
+    pub recent_restarts: u32,
+    /// How long the next restart in the window will wait, in milliseconds.
+    pub backoff_ms: u64,
+    /// Whether the supervisor has stopped restarting the task, because it
+    /// faulted too often, and is holding it faulted.
+    pub held: bool,
+}
+
+/// A change of system state, as recorded by the supervisor and returned by
+/// `get_state_transition`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, AsBytes, FromBytes)]
+#[repr(C)]
+pub struct StateTransition {
+    /// Time of the change, in kernel ticks since boot.
+    pub timestamp: u64,
+    /// Sequence number of the change. The first change after boot is 0.
+    pub seq: u32,
+    /// The state that the system changed to.
+    pub state: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive, IdolError)]
+#[repr(u32)]
+pub enum StateHistoryError {
+    /// There's no transition with the given sequence number, or a later one,
+    /// in the history.
+    NoSuchTransition = 1,
+}
+
+/// Longest panic message kept in a crash record. Longer messages are
+/// truncated.
+pub const CRASH_PANIC_MESSAGE_LEN: usize = 128;
+
+impl Jefe {
+    /// Gets the system state as the application's own state type, such as
+    /// Gimlet's `PowerState`. This is `None` if the state isn't one of the
+    /// type's values, as is the case before anyone has set it.
+    pub fn get_state_as<S: FromPrimitive>(&self) -> Option<S> {
+        S::from_u32(self.get_state())
+    }
+
+    /// Sets the system state from the application's own state type.
+    pub fn set_state_to<S: ToPrimitive>(&self, state: S) {
+        self.set_state(state.to_u32().unwrap_lite())
+    }
+}
+
 include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
@@ -2,7 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use serde::Deserialize;
 use std::collections::BTreeMap;
 use std::io::Write;
@@ -41,9 +41,133 @@ fn main() -> Result<()> {
     }
     writeln!(out, "];")?;
 
+    let states = match &cfg.states {
+        Some(path) => read_states(path)?,
+        None => BTreeMap::new(),
+    };
+    let mut values = std::collections::BTreeSet::new();
+    writeln!(
+        out,
+        "pub(crate) const STATES: [(&str, u32); {}] = [",
+        states.len()
+    )?;
+    for (name, value) in &states {
+        if !values.insert(value) {
+            bail!("states: more than one state has the value {}", value);
+        }
+        writeln!(out, "    ({:?}, {}),", name, value)?;
+    }
+    writeln!(out, "];")?;
+
+    let backend = [
+        "CARGO_FEATURE_WATCHDOG_STM32G0",
+        "CARGO_FEATURE_WATCHDOG_STM32H7",
+        "CARGO_FEATURE_WATCHDOG_LPC55",
+    ]
+    .iter()
+    .any(|var| std::env::var_os(var).is_some());
+    let watchdog = match cfg.watchdog {
+        Some(watchdog) => {
+            if !backend {
+                bail!(
+                    "jefe has a watchdog config, but no watchdog-* feature \
+                     to say which hardware watchdog to use"
+                );
+            }
+            // Jefe checks heartbeats, and feeds the hardware, from its
+            // 100 ms timer; the hardware must allow for it being a bit late.
+            if watchdog.timeout_ms < 200 {
+                bail!("watchdog timeout-ms must be at least 200");
+            }
+            watchdog
+        }
+        None => Watchdog::default(),
+    };
+    let task_ids = build_util::task_ids();
+    for name in watchdog.heartbeats.keys() {
+        if task_ids.get(name).is_none() {
+            bail!("watchdog heartbeats: unknown task `{}`", name);
+        }
+    }
+
+    writeln!(
+        out,
+        "pub(crate) const WATCHDOG_TIMEOUT_MS: u32 = {};",
+        watchdog.timeout_ms
+    )?;
+    writeln!(
+        out,
+        "pub(crate) const HEARTBEATS: [(usize, u64); {}] = [",
+        watchdog.heartbeats.len()
+    )?;
+    for (name, deadline_ms) in watchdog.heartbeats {
+        writeln!(out, "    ({}::{} as usize, {}),", task, name, deadline_ms)?;
+    }
+    writeln!(out, "];")?;
+
+    writeln!(
+        out,
+        "pub(crate) const RESTART_POLICIES: \
+         [(usize, crate::restart::RestartPolicy); {}] = [",
+        cfg.restart_policy.len()
+    )?;
+    for (name, policy) in cfg.restart_policy {
+        if task_ids.get(&name).is_none() {
+            bail!("restart-policy: unknown task `{}`", name);
+        }
+        let escalation = match policy.escalate {
+            Escalation::Hold => "Hold",
+            Escalation::Reset => "Reset",
+        };
+        writeln!(
+            out,
+            "    ({}::{} as usize, crate::restart::RestartPolicy {{
+        max_restarts: {},
+        window_ms: {},
+        backoff_ms: {},
+        max_backoff_ms: {},
+        escalation: crate::restart::Escalation::{},
+    }}),",
+            task,
+            name,
+            policy.max_restarts,
+            policy.window_ms,
+            policy.backoff_ms,
+            policy.max_backoff_ms.unwrap_or(u64::MAX),
+            escalation,
+        )?;
+    }
+    writeln!(out, "];")?;
+
+    // The build system has already checked these for cycles.
+    let start_after = build_util::task_start_after()?;
+    writeln!(
+        out,
+        "pub(crate) const START_AFTER: [(usize, &[usize]); {}] = [",
+        start_after.len()
+    )?;
+    for (name, deps) in start_after {
+        write!(out, "    ({}::{} as usize, &[", task, name)?;
+        for dep in deps {
+            write!(out, "{}::{} as usize, ", task, dep)?;
+        }
+        writeln!(out, "]),")?;
+    }
+    writeln!(out, "];")?;
+
     Ok(())
 }
 
+/// Reads the application's state names and values from `path`, which is
+/// relative to the root of the repository.
+fn read_states(path: &str) -> Result<BTreeMap<String, u32>> {
+    let path = std::path::Path::new("../..").join(path);
+    println!("cargo:rerun-if-changed={}", path.display());
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("reading {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))
+}
+
 /// Jefe task-level configuration.
 #[derive(Deserialize, Default)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
@@ -52,9 +176,67 @@ struct Config {
     /// `StateChange` record.
     #[serde(default)]
     on_state_change: BTreeMap<String, StateChange>,
+    /// Path, relative to the root of the repository, of a TOML file mapping
+    /// the names of the application's system states to their values. If this
+    /// is given, `set_state` only accepts these values.
+    #[serde(default)]
+    states: Option<String>,
     /// Map of operation names to tasks allowed to call them.
     #[serde(default)]
     allowed_callers: BTreeMap<String, Vec<String>>,
+    /// Map from task name to the policy for restarting it after faults. Tasks
+    /// without a policy are restarted immediately, every time.
+    #[serde(default)]
+    restart_policy: BTreeMap<String, RestartPolicy>,
+    /// Hardware watchdog settings. If this is absent, the hardware watchdog
+    /// is left alone.
+    #[serde(default)]
+    watchdog: Option<Watchdog>,
+}
+
+/// How jefe drives the hardware watchdog.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Watchdog {
+    /// How long the hardware waits to be fed before resetting the chip, in
+    /// milliseconds. This is zero, meaning the watchdog isn't used, if there's
+    /// no `watchdog` section.
+    timeout_ms: u32,
+    /// Map from task name to heartbeat deadline, in milliseconds. Each of
+    /// these tasks must call `heartbeat` at least this often, or jefe stops
+    /// feeding the watchdog.
+    #[serde(default)]
+    heartbeats: BTreeMap<String, u64>,
+}
+
+/// How to restart a task that keeps faulting.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct RestartPolicy {
+    /// Number of restarts allowed within `window-ms` of the first fault.
+    max_restarts: u32,
+    /// Length of the window, in milliseconds.
+    window_ms: u64,
+    /// Delay before the first restart in the window, in milliseconds. Each
+    /// later restart in the window waits twice as long as the one before.
+    #[serde(default)]
+    backoff_ms: u64,
+    /// Longest delay before a restart, in milliseconds. If this is absent,
+    /// delays keep doubling.
+    #[serde(default)]
+    max_backoff_ms: Option<u64>,
+    /// What to do if the task faults again after `max-restarts`.
+    escalate: Escalation,
+}
+
+/// What to do with a task that has run out of restarts.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Escalation {
+    /// Leave the task faulted.
+    Hold,
+    /// Reset the system.
+    Reset,
 }
 
 /// Description of something a task wants done on state change.
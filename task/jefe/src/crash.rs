@@ -0,0 +1,202 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Crash record kept across warm resets.
+//!
+//! We note the most recent task fault (with its panic message, if any), the
+//! task that asked for a reset, and the task that missed its heartbeat
+//! deadline, as they happen, in RAM that startup code doesn't clear. After a
+//! warm reset -- requested, or from a watchdog -- the record written by the
+//! previous boot is still there. We pick it up at startup, serve it through
+//! the `get_crash_record` and `get_crash_panic_message` IPCs, and start a
+//! fresh one for this boot.
+//!
+//! After a cold boot, that RAM holds garbage, so the record is guarded by a
+//! magic number and a checksum, and ignored if either doesn't match. After an
+//! update, it holds a record from a different image, which may be laid out
+//! differently, or number its tasks differently; so the record also carries
+//! a layout version and the image ID, and is ignored unless both are ours.
+
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, Ordering};
+use task_jefe_api::{CrashRecord, TaskFault, CRASH_PANIC_MESSAGE_LEN};
+use userlib::*;
+use zerocopy::{AsBytes, FromBytes};
+
+#[cfg(armv6m)]
+use armv6m_atomic_hack::AtomicBoolExt;
+
+const CRASH_RECORD_MAGIC: u32 = 0x4a45_4645;
+
+/// Version of the layout of `RawCrashRecord` and `CrashRecord`. This must
+/// change whenever either does.
+const CRASH_RECORD_VERSION: u32 = 1;
+
+/// Space for the encoded `CrashRecord`. This is more than enough for any
+/// record; `save` panics if it isn't.
+const RECORD_BYTES: usize = 32;
+
+/// A record of nothing going wrong.
+const NO_CRASH: CrashRecord = CrashRecord {
+    fault: None,
+    reset_requested_by: None,
+    missed_heartbeat: None,
+};
+
+/// The crash record as it's kept in RAM.
+#[derive(AsBytes, FromBytes)]
+#[repr(C)]
+struct RawCrashRecord {
+    magic: u32,
+    version: u32,
+    /// ID of the image that wrote the record.
+    image_id: u64,
+    /// `ssmarshal` encoding of a `CrashRecord`.
+    record: [u8; RECORD_BYTES],
+    /// Number of valid bytes in `panic_message`.
+    panic_len: u32,
+    panic_message: [u8; CRASH_PANIC_MESSAGE_LEN],
+    /// Checksum of all the fields above.
+    checksum: u32,
+}
+
+impl RawCrashRecord {
+    fn compute_checksum(&self) -> u32 {
+        let bytes = self.as_bytes();
+        fnv1a(&bytes[..bytes.len() - core::mem::size_of::<u32>()])
+    }
+
+    /// Decodes the record, if it's intact and was written by the image with
+    /// ID `image_id`.
+    fn decode(&self, image_id: u64) -> Option<(CrashRecord, &[u8])> {
+        if self.magic != CRASH_RECORD_MAGIC
+            || self.version != CRASH_RECORD_VERSION
+            || self.image_id != image_id
+            || self.checksum != self.compute_checksum()
+        {
+            return None;
+        }
+        let (record, _) = ssmarshal::deserialize(&self.record).ok()?;
+        let panic_message =
+            self.panic_message.get(..self.panic_len as usize)?;
+        Some((record, panic_message))
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &b| {
+        (hash ^ u32::from(b)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Our crash records: the one left by the previous boot, and the one we're
+/// keeping for this boot.
+pub struct CrashLog {
+    raw: &'static mut RawCrashRecord,
+    image_id: u64,
+    current: CrashRecord,
+    previous: CrashRecord,
+    previous_panic_message: [u8; CRASH_PANIC_MESSAGE_LEN],
+    previous_panic_len: usize,
+}
+
+impl CrashLog {
+    /// Picks up the record left by the previous boot, if any, and starts a new
+    /// one.
+    ///
+    /// # Panics
+    ///
+    /// If called more than once.
+    pub fn claim() -> Self {
+        static TAKEN: AtomicBool = AtomicBool::new(false);
+        if TAKEN.swap(true, Ordering::Relaxed) {
+            panic!();
+        }
+
+        #[link_section = ".uninit.crash_record"]
+        static mut CRASH_RECORD: MaybeUninit<RawCrashRecord> =
+            MaybeUninit::uninit();
+
+        // Safety: the check above ensures that this reference is unique.
+        // `RawCrashRecord` is `FromBytes`, so whatever the last boot (or
+        // power-on) left in RAM is a valid value, if not a meaningful one --
+        // which `decode` checks.
+        let raw = unsafe {
+            (*core::ptr::addr_of_mut!(CRASH_RECORD)).assume_init_mut()
+        };
+
+        let mut log = CrashLog {
+            raw,
+            image_id: kipc::read_image_id(),
+            current: NO_CRASH,
+            previous: NO_CRASH,
+            previous_panic_message: [0; CRASH_PANIC_MESSAGE_LEN],
+            previous_panic_len: 0,
+        };
+        if let Some((record, panic_message)) = log.raw.decode(log.image_id) {
+            log.previous = record;
+            log.previous_panic_message[..panic_message.len()]
+                .copy_from_slice(panic_message);
+            log.previous_panic_len = panic_message.len();
+        }
+        log.save(Some(&[]));
+        log
+    }
+
+    /// Returns the record left by the previous boot.
+    pub fn previous(&self) -> CrashRecord {
+        self.previous
+    }
+
+    /// Returns the panic message from the record left by the previous boot,
+    /// which is empty if its last fault wasn't a panic.
+    pub fn previous_panic_message(&self) -> &[u8] {
+        &self.previous_panic_message[..self.previous_panic_len]
+    }
+
+    /// Notes that `task` has faulted, replacing any earlier fault. Pass the
+    /// panic message if the fault was a panic, and an empty slice otherwise.
+    pub fn record_fault(
+        &mut self,
+        task: usize,
+        fault: FaultInfo,
+        panic_message: &[u8],
+    ) {
+        self.current.fault = Some(TaskFault {
+            task: task as u16,
+            fault,
+        });
+        self.save(Some(panic_message));
+    }
+
+    /// Notes that `task` has asked for a reset.
+    pub fn record_reset_request(&mut self, task: usize) {
+        self.current.reset_requested_by = Some(task as u16);
+        self.save(None);
+    }
+
+    /// Notes that `task` missed its heartbeat deadline, so the hardware
+    /// watchdog is about to reset us.
+    pub fn record_missed_heartbeat(&mut self, task: usize) {
+        self.current.missed_heartbeat = Some(task as u16);
+        self.save(None);
+    }
+
+    /// Writes the current record out to RAM, replacing the panic message if
+    /// one is given.
+    fn save(&mut self, panic_message: Option<&[u8]>) {
+        let raw = &mut *self.raw;
+        raw.magic = CRASH_RECORD_MAGIC;
+        raw.version = CRASH_RECORD_VERSION;
+        raw.image_id = self.image_id;
+        // RECORD_BYTES is chosen to fit any CrashRecord.
+        ssmarshal::serialize(&mut raw.record, &self.current).unwrap_lite();
+        if let Some(msg) = panic_message {
+            let len = msg.len().min(CRASH_PANIC_MESSAGE_LEN);
+            raw.panic_message[..len].copy_from_slice(&msg[..len]);
+            raw.panic_len = len as u32;
+        }
+        raw.checksum = raw.compute_checksum();
+    }
+}
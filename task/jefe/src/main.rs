@@ -8,12 +8,12 @@
 //!
 //! - Maintaining the system console output (currently via semihosting).
 //! - Monitoring tasks for failures and restarting them.
+//! - Managing a watchdog timer, if the application asks it to.
 //!
 //! It will probably become responsible for:
 //!
 //! - Evacuating kernel log information.
 //! - Coordinating certain shared resources, such as the RCC and GPIO muxing.
-//! - Managing a watchdog timer.
 //!
 //! It's unwise for the supervisor to use `SEND`, ever, except to talk to the
 //! kernel. This is because a `SEND` to a misbehaving task could block forever,
@@ -26,13 +26,26 @@
 #![no_std]
 #![no_main]
 
+mod crash;
 mod external;
+mod restart;
+mod startup;
+mod state;
+mod watchdog;
 
 use core::convert::Infallible;
 
+use crash::CrashLog;
 use hubris_num_tasks::NUM_TASKS;
-use task_jefe_api::ResetReason;
+use restart::Restarts;
+use startup::Startup;
+use state::SystemState;
+use task_jefe_api::{
+    CrashRecord, ResetReason, RestartCounters, StateHistoryError,
+    StateTransition, CRASH_PANIC_MESSAGE_LEN,
+};
 use userlib::*;
+use watchdog::Watchdog;
 
 fn log_fault(t: usize, fault: &abi::FaultInfo) {
     match fault {
@@ -124,18 +137,23 @@ fn main() -> ! {
         [Disposition::Restart; hubris_num_tasks::NUM_TASKS];
     let mut logged: [bool; hubris_num_tasks::NUM_TASKS] =
         [false; hubris_num_tasks::NUM_TASKS];
-    let deadline = sys_get_timer().now + TIMER_INTERVAL;
+    let now = sys_get_timer().now;
+    let deadline = now + TIMER_INTERVAL;
 
     sys_set_timer(Some(deadline), TIMER_MASK);
 
     external::set_ready();
 
     let mut server = ServerImpl {
-        state: 0,
+        state: SystemState::new(),
         deadline,
         disposition: &mut disposition,
         logged: &mut logged,
         reset_reason: ResetReason::Unknown,
+        crash_log: CrashLog::claim(),
+        watchdog: Watchdog::start(now),
+        restarts: Restarts::new(),
+        startup: Startup::new(),
     };
     let mut buf = [0u8; idl::INCOMING_SIZE];
 
@@ -145,18 +163,24 @@ fn main() -> ! {
 }
 
 struct ServerImpl<'s> {
-    state: u32,
+    state: SystemState,
     disposition: &'s mut [Disposition; NUM_TASKS],
     logged: &'s mut [bool; NUM_TASKS],
     deadline: u64,
     reset_reason: ResetReason,
+    crash_log: CrashLog,
+    watchdog: Watchdog,
+    restarts: Restarts,
+    startup: Startup,
 }
 
 impl idl::InOrderJefeImpl for ServerImpl<'_> {
     fn request_reset(
         &mut self,
-        _msg: &userlib::RecvMessage,
+        msg: &userlib::RecvMessage,
     ) -> Result<(), idol_runtime::RequestError<Infallible>> {
+        self.crash_log.record_reset_request(msg.sender.index());
+
         // If we wanted to broadcast to other tasks that a restart is occuring
         // here is where we would do so!
         kipc::system_restart();
@@ -178,11 +202,84 @@ impl idl::InOrderJefeImpl for ServerImpl<'_> {
         Ok(())
     }
 
+    fn get_crash_record(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+    ) -> Result<CrashRecord, idol_runtime::RequestError<Infallible>> {
+        Ok(self.crash_log.previous())
+    }
+
+    fn get_crash_panic_message(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        message: idol_runtime::Leased<idol_runtime::W, [u8]>,
+    ) -> Result<usize, idol_runtime::RequestError<Infallible>> {
+        let panic_message = self.crash_log.previous_panic_message();
+        let len = panic_message.len().min(message.len());
+        message
+            .write_range(0..len, &panic_message[..len])
+            .map_err(|_| {
+                idol_runtime::RequestError::Fail(
+                    idol_runtime::ClientError::WentAway,
+                )
+            })?;
+        Ok(len)
+    }
+
+    fn get_restart_counters(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        task: u32,
+    ) -> Result<RestartCounters, idol_runtime::RequestError<Infallible>> {
+        if task as usize >= NUM_TASKS {
+            return Err(idol_runtime::RequestError::Fail(
+                idol_runtime::ClientError::BadMessageContents,
+            ));
+        }
+        Ok(self.restarts.counters(task as usize))
+    }
+
+    fn heartbeat(
+        &mut self,
+        msg: &userlib::RecvMessage,
+    ) -> Result<(), idol_runtime::RequestError<Infallible>> {
+        self.watchdog
+            .heartbeat(msg.sender.index(), sys_get_timer().now);
+        Ok(())
+    }
+
+    fn report_ready(
+        &mut self,
+        msg: &userlib::RecvMessage,
+    ) -> Result<(), idol_runtime::RequestError<Infallible>> {
+        // Tasks that we start now get their first heartbeat deadline now.
+        let now = sys_get_timer().now;
+        let watchdog = &mut self.watchdog;
+        self.startup.report_ready(msg.sender.index(), |task| {
+            watchdog.heartbeat(task, now)
+        });
+        Ok(())
+    }
+
+    fn get_task_stack_usage(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        task: u32,
+    ) -> Result<TaskStackUsage, idol_runtime::RequestError<Infallible>> {
+        // The kernel faults us for an out-of-range index, so check it here.
+        if task as usize >= NUM_TASKS {
+            return Err(idol_runtime::RequestError::Fail(
+                idol_runtime::ClientError::BadMessageContents,
+            ));
+        }
+        Ok(kipc::read_task_stack_usage(task as usize))
+    }
+
     fn get_state(
         &mut self,
         _msg: &userlib::RecvMessage,
     ) -> Result<u32, idol_runtime::RequestError<Infallible>> {
-        Ok(self.state)
+        Ok(self.state.get())
     }
 
     fn set_state(
@@ -190,18 +287,34 @@ impl idl::InOrderJefeImpl for ServerImpl<'_> {
         _msg: &userlib::RecvMessage,
         state: u32,
     ) -> Result<(), idol_runtime::RequestError<Infallible>> {
-        if self.state != state {
-            self.state = state;
-
-            for (task, mask) in generated::MAILING_LIST {
-                let taskid =
-                    TaskId::for_index_and_gen(task as usize, Generation::ZERO);
-                let taskid = sys_refresh_task_id(taskid);
-                sys_post(taskid, mask);
-            }
+        if !SystemState::is_valid(state) {
+            return Err(idol_runtime::RequestError::Fail(
+                idol_runtime::ClientError::BadMessageContents,
+            ));
         }
+        self.state.set(state, sys_get_timer().now);
         Ok(())
     }
+
+    fn subscribe_state(
+        &mut self,
+        msg: &userlib::RecvMessage,
+        notifications: u32,
+    ) -> Result<(), idol_runtime::RequestError<Infallible>> {
+        self.state.subscribe(msg.sender.index(), notifications);
+        Ok(())
+    }
+
+    fn get_state_transition(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        seq: u32,
+    ) -> Result<StateTransition, idol_runtime::RequestError<StateHistoryError>>
+    {
+        self.state
+            .transition(seq)
+            .ok_or_else(|| StateHistoryError::NoSuchTransition.into())
+    }
 }
 
 impl idol_runtime::NotificationHandler for ServerImpl<'_> {
@@ -212,6 +325,7 @@ impl idol_runtime::NotificationHandler for ServerImpl<'_> {
     fn handle_notification(&mut self, bits: u32) {
         // Check to see if we have any external requests
         let changed = external::check(self.disposition);
+        let now = sys_get_timer().now;
 
         // If our timer went off, we need to reestablish it
         if bits & TIMER_MASK != 0 {
@@ -219,27 +333,52 @@ impl idol_runtime::NotificationHandler for ServerImpl<'_> {
             sys_set_timer(Some(self.deadline), TIMER_MASK);
         }
 
-        // If our disposition has changed or if we have been notified of
-        // a faulting task, we need to iterate over all of our tasks.
-        if changed || (bits & FAULT_MASK) != 0 {
+        // If our disposition has changed, if we have been notified of a
+        // faulting task, or if a delayed restart has come due, we need to
+        // iterate over all of our tasks.
+        if changed || (bits & FAULT_MASK) != 0 || self.restarts.any_due(now) {
             for i in 0..NUM_TASKS {
                 match kipc::read_task_status(i) {
                     abi::TaskState::Faulted { fault, .. } => {
                         if !self.logged[i] {
                             log_fault(i, &fault);
                             self.logged[i] = true;
+                            // It can't check in until it's restarted, which
+                            // may wait out a back-off.
+                            self.watchdog.suspend(i);
+
+                            // Grab the panic message, if any, while the task
+                            // is still around to have one.
+                            let mut panic_message =
+                                [0; CRASH_PANIC_MESSAGE_LEN];
+                            let len =
+                                kipc::read_panic_message(i, &mut panic_message);
+                            self.crash_log.record_fault(
+                                i,
+                                fault,
+                                &panic_message[..len],
+                            );
+
+                            if self.disposition[i] == Disposition::Restart {
+                                self.apply_restart_policy(i, now);
+                            }
                         }
 
-                        if self.disposition[i] == Disposition::Restart {
+                        if self.disposition[i] == Disposition::Restart
+                            && self.restarts.due(i, now)
+                        {
                             // Stand it back up
                             kipc::restart_task(i, true);
                             self.logged[i] = false;
+                            self.restarts.restarted(i);
+                            self.watchdog.heartbeat(i, now);
                         }
                     }
 
                     abi::TaskState::Healthy(abi::SchedState::Stopped) => {
                         if self.disposition[i] == Disposition::Start {
                             kipc::restart_task(i, true);
+                            self.watchdog.heartbeat(i, now);
                         }
                     }
 
@@ -251,6 +390,35 @@ impl idol_runtime::NotificationHandler for ServerImpl<'_> {
                 }
             }
         }
+
+        // Check heartbeats after we've seen any new faults, so that a task
+        // that has faulted isn't held to its deadline.
+        if bits & TIMER_MASK != 0 {
+            if let Some(task) = self.watchdog.check(now, self.disposition) {
+                sys_log!("Task #{} missed its heartbeat deadline", task);
+                self.crash_log.record_missed_heartbeat(task);
+            }
+        }
+    }
+}
+
+impl ServerImpl<'_> {
+    /// Consults the restart policy for task `i`, which has just faulted, and
+    /// escalates if it calls for that.
+    fn apply_restart_policy(&mut self, i: usize, now: u64) {
+        match self.restarts.on_fault(i, now) {
+            restart::Action::Restart => (),
+            restart::Action::Hold => {
+                sys_log!("Task #{} faulted too often; holding it", i);
+                self.disposition[i] = Disposition::Hold;
+            }
+            restart::Action::Reset => {
+                sys_log!("Task #{} faulted too often; resetting", i);
+                // We're the supervisor, so we're task 0.
+                self.crash_log.record_reset_request(0);
+                kipc::system_restart();
+            }
+        }
     }
 }
 
@@ -261,6 +429,10 @@ mod generated {
 
 // And the Idol bits
 mod idl {
-    use task_jefe_api::ResetReason;
+    use task_jefe_api::{
+        CrashRecord, ResetReason, RestartCounters, StateHistoryError,
+        StateTransition,
+    };
+    use userlib::TaskStackUsage;
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
@@ -0,0 +1,174 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Restart policy.
+//!
+//! By default, we restart a faulted task right away, every time. That's the
+//! right thing for the occasional fault, but a task that faults as soon as it
+//! starts would spin forever, eating the CPU time of everything below it. So
+//! `app.toml` can give a task a restart policy, which counts its restarts in a
+//! window of time starting at the first fault:
+//!
+//! - Each restart in the window waits twice as long as the one before it,
+//!   starting from the policy's initial back-off, up to a limit.
+//! - If the task faults again after the policy's maximum number of restarts
+//!   in the window, we escalate: either we stop restarting it and hold it
+//!   faulted (until someone releases it, say, from Humility) or we reset the
+//!   whole system.
+//!
+//! We notice that a back-off has run out on our periodic timer, so delays are
+//! effectively rounded up to its 100 ms interval.
+
+use crate::generated::RESTART_POLICIES;
+use hubris_num_tasks::NUM_TASKS;
+use task_jefe_api::RestartCounters;
+
+/// A task's restart policy, from `app.toml`.
+pub struct RestartPolicy {
+    /// Number of restarts allowed in a window before we escalate.
+    pub max_restarts: u32,
+    /// Length of the window, in milliseconds.
+    pub window_ms: u64,
+    /// Delay before the first restart in a window, in milliseconds.
+    pub backoff_ms: u64,
+    /// Longest delay before a restart, in milliseconds.
+    pub max_backoff_ms: u64,
+    /// What to do when the task runs out of restarts.
+    pub escalation: Escalation,
+}
+
+pub enum Escalation {
+    Hold,
+    Reset,
+}
+
+/// What to do about a task that has just faulted.
+pub enum Action {
+    /// Restart it, once `Restarts::due` says so.
+    Restart,
+    /// Stop restarting it.
+    Hold,
+    /// Reset the system.
+    Reset,
+}
+
+/// Where a task with a restart policy stands in its current window.
+#[derive(Copy, Clone, Default)]
+struct Window {
+    /// When the window started.
+    start: u64,
+    /// Restarts so far in the window.
+    restarts: u32,
+    /// Delay before the next restart.
+    backoff: u64,
+    /// When the pending restart is due, if there is one.
+    restart_at: Option<u64>,
+    /// Whether we escalated by holding the task.
+    held: bool,
+}
+
+pub struct Restarts {
+    /// Restarts since boot, for every task.
+    restarts: [u32; NUM_TASKS],
+    /// Windows for the tasks in `RESTART_POLICIES`.
+    windows: [Window; RESTART_POLICIES.len()],
+}
+
+impl Restarts {
+    pub fn new() -> Self {
+        Restarts {
+            restarts: [0; NUM_TASKS],
+            windows: [Window::default(); RESTART_POLICIES.len()],
+        }
+    }
+
+    /// Finds the restart policy for `task`, and its index in
+    /// `RESTART_POLICIES`.
+    fn policy(task: usize) -> Option<(usize, &'static RestartPolicy)> {
+        RESTART_POLICIES
+            .iter()
+            .enumerate()
+            .find(|(_, (t, _))| *t == task)
+            .map(|(i, (_, policy))| (i, policy))
+    }
+
+    /// Decides what to do about `task`, which has just faulted.
+    pub fn on_fault(&mut self, task: usize, now: u64) -> Action {
+        let (i, policy) = match Self::policy(task) {
+            Some(p) => p,
+            None => return Action::Restart,
+        };
+        let window = &mut self.windows[i];
+
+        if window.restarts == 0
+            || now.saturating_sub(window.start) >= policy.window_ms
+        {
+            *window = Window {
+                start: now,
+                backoff: policy.backoff_ms,
+                ..Window::default()
+            };
+        }
+
+        if window.restarts >= policy.max_restarts {
+            // If the task is released from a hold, give it a fresh window.
+            *window = Window::default();
+            return match policy.escalation {
+                Escalation::Hold => {
+                    window.held = true;
+                    Action::Hold
+                }
+                Escalation::Reset => Action::Reset,
+            };
+        }
+
+        window.restarts += 1;
+        window.restart_at = Some(now.saturating_add(window.backoff));
+        window.backoff =
+            window.backoff.saturating_mul(2).min(policy.max_backoff_ms);
+        Action::Restart
+    }
+
+    /// Checks whether it's time to restart `task`, which has faulted.
+    pub fn due(&self, task: usize, now: u64) -> bool {
+        match Self::policy(task) {
+            Some((i, _)) => {
+                self.windows[i].restart_at.map_or(true, |t| now >= t)
+            }
+            None => true,
+        }
+    }
+
+    /// Checks whether any delayed restart has come due.
+    pub fn any_due(&self, now: u64) -> bool {
+        self.windows
+            .iter()
+            .any(|w| w.restart_at.map_or(false, |t| now >= t))
+    }
+
+    /// Notes that we've restarted `task` after a fault.
+    pub fn restarted(&mut self, task: usize) {
+        self.restarts[task] = self.restarts[task].wrapping_add(1);
+        if let Some((i, _)) = Self::policy(task) {
+            self.windows[i].restart_at = None;
+            self.windows[i].held = false;
+        }
+    }
+
+    pub fn counters(&self, task: usize) -> RestartCounters {
+        let mut counters = RestartCounters {
+            restarts: self.restarts[task],
+            recent_restarts: 0,
+            backoff_ms: 0,
+            held: false,
+        };
+        if let Some((i, _)) = Self::policy(task) {
+            let window = &self.windows[i];
+            counters.recent_restarts = window.restarts;
+            counters.backoff_ms = window.backoff;
+            counters.held = window.held;
+        }
+        counters
+    }
+}
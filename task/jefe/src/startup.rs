@@ -0,0 +1,59 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Staged startup.
+//!
+//! A task with `start-after` dependencies in `app.toml` isn't started by the
+//! kernel at boot. Instead, we start it once every task it depends on has
+//! called `report_ready`. The build system rejects dependency cycles, so
+//! everything gets started eventually, as long as every task that's depended
+//! upon does report in.
+//!
+//! Readiness only gates the first start. If a task that others depend on is
+//! restarted later, its dependents carry on, as they would have without
+//! `start-after`.
+
+use crate::generated::START_AFTER;
+use hubris_num_tasks::NUM_TASKS;
+use userlib::*;
+
+pub struct Startup {
+    /// Which tasks have reported ready.
+    ready: [bool; NUM_TASKS],
+    /// Which tasks in `START_AFTER` we've started.
+    started: [bool; START_AFTER.len()],
+}
+
+impl Startup {
+    pub fn new() -> Self {
+        Startup {
+            ready: [false; NUM_TASKS],
+            started: [false; START_AFTER.len()],
+        }
+    }
+
+    /// Notes that `task` is ready, and starts any tasks that were only waiting
+    /// on it, passing each to `on_start`.
+    pub fn report_ready(
+        &mut self,
+        task: usize,
+        mut on_start: impl FnMut(usize),
+    ) {
+        if self.ready[task] {
+            return;
+        }
+        self.ready[task] = true;
+
+        let ready = &self.ready;
+        for (started, (dependent, deps)) in
+            self.started.iter_mut().zip(START_AFTER)
+        {
+            if !*started && deps.iter().all(|&d| ready[d]) {
+                kipc::restart_task(dependent, true);
+                on_start(dependent);
+                *started = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! System state.
+//!
+//! The system state is a `u32` whose meaning is up to the application: on
+//! Gimlet, for instance, it's the `PowerState`. The application can name its
+//! states in `app.toml`, in which case we refuse to be set to anything else.
+//!
+//! Tasks find out about changes by subscribing, either in `app.toml` (the
+//! `on-state-change` table) or at runtime with `subscribe_state`. Either way,
+//! we post them a notification on every change. Since a notification doesn't
+//! say what happened, and several changes can happen before a subscriber gets
+//! around to looking, we also keep a short history of transitions, which
+//! subscribers (or anyone else) can read back by sequence number.
+
+use crate::generated::{MAILING_LIST, STATES};
+use hubris_num_tasks::NUM_TASKS;
+use task_jefe_api::StateTransition;
+use userlib::*;
+
+/// Number of transitions we remember.
+const HISTORY_LEN: usize = 8;
+
+pub struct SystemState {
+    state: u32,
+    /// Notification bits to post to each task on a change.
+    subscribers: [u32; NUM_TASKS],
+    /// Recent transitions. Transition `n` is at `history[n % HISTORY_LEN]`.
+    history: [StateTransition; HISTORY_LEN],
+    /// Sequence number of the next transition.
+    next_seq: u32,
+}
+
+impl SystemState {
+    pub fn new() -> Self {
+        let mut subscribers = [0; NUM_TASKS];
+        for (task, mask) in MAILING_LIST {
+            subscribers[task as usize] |= mask;
+        }
+        SystemState {
+            state: 0,
+            subscribers,
+            history: [StateTransition::default(); HISTORY_LEN],
+            next_seq: 0,
+        }
+    }
+
+    pub fn get(&self) -> u32 {
+        self.state
+    }
+
+    /// Checks whether `state` is one that the application has defined, if it
+    /// has defined any.
+    pub fn is_valid(state: u32) -> bool {
+        STATES.is_empty() || STATES.iter().any(|&(_, s)| s == state)
+    }
+
+    /// Changes the state, recording the transition and notifying subscribers,
+    /// if `state` is new.
+    pub fn set(&mut self, state: u32, now: u64) {
+        if self.state == state {
+            return;
+        }
+        self.state = state;
+
+        if let Some((name, _)) = STATES.iter().find(|&&(_, s)| s == state) {
+            sys_log!("State changed to {}", name);
+        }
+
+        self.history[self.next_seq as usize % HISTORY_LEN] = StateTransition {
+            timestamp: now,
+            seq: self.next_seq,
+            state,
+        };
+        self.next_seq = self.next_seq.wrapping_add(1);
+
+        for (task, &mask) in self.subscribers.iter().enumerate() {
+            if mask != 0 {
+                let taskid = TaskId::for_index_and_gen(task, Generation::ZERO);
+                let taskid = sys_refresh_task_id(taskid);
+                sys_post(taskid, mask);
+            }
+        }
+    }
+
+    /// Replaces `task`'s subscription. A zero mask unsubscribes it.
+    pub fn subscribe(&mut self, task: usize, mask: u32) {
+        self.subscribers[task] = mask;
+    }
+
+    /// Returns the oldest transition we remember whose sequence number is at
+    /// least `seq`, if any.
+    pub fn transition(&self, seq: u32) -> Option<StateTransition> {
+        let oldest = self.next_seq.saturating_sub(HISTORY_LEN as u32);
+        let seq = seq.max(oldest);
+        if seq < self.next_seq {
+            Some(self.history[seq as usize % HISTORY_LEN])
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,230 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Hardware watchdog supervision.
+//!
+//! If `app.toml` gives us a `watchdog` config, we start the chip's watchdog at
+//! boot and feed it from our periodic timer -- but only while every task with
+//! a heartbeat deadline has called `heartbeat` recently enough. Once a task
+//! misses its deadline, we note which one in the crash record and stop feeding
+//! the watchdog for good, so the chip resets shortly after. If we wedge
+//! ourselves, or a higher-priority task starves us, the watchdog goes off the
+//! same way.
+//!
+//! Deadlines only apply to tasks that we'd restart on a fault. Tasks that
+//! someone has told us to hold (say, from a debugger) are left alone. A task
+//! that faults has no deadline until we restart it, however long its restart
+//! policy has it back off, and then gets a fresh one. A task that isn't
+//! started at boot, because of `start-after` or `start = false`, gets its
+//! first deadline when it's started.
+//!
+//! Once started, these watchdogs can't be stopped short of a reset. That
+//! includes halting the processor in a debugger, unless the debugger freezes
+//! the watchdog.
+
+use crate::generated::{HEARTBEATS, WATCHDOG_TIMEOUT_MS};
+use crate::Disposition;
+use hubris_num_tasks::NUM_TASKS;
+use userlib::*;
+
+pub struct Watchdog {
+    /// Time by which each task in `HEARTBEATS` must next check in, or `None`
+    /// if it hasn't been started yet, or is faulted.
+    deadlines: [Option<u64>; HEARTBEATS.len()],
+    /// Whether a task has missed its deadline, in which case we've stopped
+    /// feeding the hardware.
+    expired: bool,
+    /// Whether the hardware watchdog is running. It may have to wait for its
+    /// clock to be turned on.
+    running: bool,
+}
+
+impl Watchdog {
+    /// Starts the hardware watchdog, if we're configured to use one and it's
+    /// ready, and gives every supervised task that's running its first
+    /// deadline.
+    pub fn start(now: u64) -> Self {
+        let mut watchdog = Watchdog {
+            deadlines: [None; HEARTBEATS.len()],
+            expired: false,
+            running: WATCHDOG_TIMEOUT_MS != 0 && hw::start(WATCHDOG_TIMEOUT_MS),
+        };
+        for (task, _) in HEARTBEATS {
+            let stopped = abi::TaskState::Healthy(abi::SchedState::Stopped);
+            if kipc::read_task_status(task) != stopped {
+                watchdog.heartbeat(task, now);
+            }
+        }
+        watchdog
+    }
+
+    /// Gives `task` a fresh deadline, if it has one. This is for when the task
+    /// checks in, and when we start or restart it.
+    pub fn heartbeat(&mut self, task: usize, now: u64) {
+        for (deadline, (t, period)) in self.deadlines.iter_mut().zip(HEARTBEATS)
+        {
+            if t == task {
+                *deadline = Some(now + period);
+            }
+        }
+    }
+
+    /// Suspends `task`'s deadline, if it has one, because it has faulted. It
+    /// can't check in until we restart it, which `heartbeat` notes.
+    pub fn suspend(&mut self, task: usize) {
+        for (deadline, (t, _)) in self.deadlines.iter_mut().zip(HEARTBEATS) {
+            if t == task {
+                *deadline = None;
+            }
+        }
+    }
+
+    /// Feeds the hardware watchdog, or starts it if it wasn't ready before, if
+    /// every supervised task has met its deadline. Otherwise, the first time
+    /// we notice, returns the index of a task that hasn't.
+    pub fn check(
+        &mut self,
+        now: u64,
+        disposition: &[Disposition; NUM_TASKS],
+    ) -> Option<usize> {
+        if self.expired {
+            return None;
+        }
+        for (&deadline, (task, _)) in self.deadlines.iter().zip(HEARTBEATS) {
+            let late = deadline.map_or(false, |d| now > d);
+            if late && disposition[task] == Disposition::Restart {
+                self.expired = true;
+                return Some(task);
+            }
+        }
+        if self.running {
+            hw::feed();
+        } else if WATCHDOG_TIMEOUT_MS != 0 {
+            self.running = hw::start(WATCHDOG_TIMEOUT_MS);
+        }
+        None
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(any(
+        feature = "watchdog-stm32g0",
+        feature = "watchdog-stm32h7"
+    ))] {
+        /// The STM32 independent watchdog (IWDG).
+        mod hw {
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "watchdog-g030")] {
+                    use stm32g0::stm32g030 as device;
+                } else if #[cfg(feature = "watchdog-g031")] {
+                    use stm32g0::stm32g031 as device;
+                } else if #[cfg(feature = "watchdog-g070")] {
+                    use stm32g0::stm32g070 as device;
+                } else if #[cfg(feature = "watchdog-g0b1")] {
+                    use stm32g0::stm32g0b1 as device;
+                } else if #[cfg(feature = "watchdog-h743")] {
+                    use stm32h7::stm32h743 as device;
+                } else if #[cfg(feature = "watchdog-h753")] {
+                    use stm32h7::stm32h753 as device;
+                } else {
+                    compile_error!("missing watchdog-<chip> feature");
+                }
+            }
+
+            /// The IWDG counts the LSI oscillator, nominally 32 kHz.
+            const LSI_HZ: u64 = 32_000;
+
+            fn iwdg() -> &'static device::iwdg::RegisterBlock {
+                // Safety: the IWDG is mapped for us, and nobody else touches
+                // it.
+                unsafe { &*device::IWDG::ptr() }
+            }
+
+            pub fn start(timeout_ms: u32) -> bool {
+                // Pick the smallest prescaler (dividing by `4 << pr`) that
+                // lets the 12-bit reload value reach the timeout, clamping it
+                // to the longest the hardware can manage (about 32 s).
+                let ticks = u64::from(timeout_ms) * LSI_HZ / 1000;
+                let pr =
+                    (0..6).find(|pr| ticks >> pr <= 4 * 0xFFF).unwrap_or(6);
+                let reload = ((ticks >> pr) / 4).min(0xFFF) as u32;
+
+                let iwdg = iwdg();
+                iwdg.kr.write(|w| w.key().start());
+                iwdg.kr.write(|w| w.key().enable());
+                // Safety: both values are in range for their registers, as
+                // computed above.
+                iwdg.pr.write(|w| unsafe { w.bits(pr) });
+                iwdg.rlr.write(|w| unsafe { w.bits(reload) });
+                // Wait for the new values to reach the LSI clock domain.
+                while iwdg.sr.read().bits() != 0 {}
+                feed();
+                true
+            }
+
+            pub fn feed() {
+                iwdg().kr.write(|w| w.key().reset());
+            }
+        }
+    } else if #[cfg(feature = "watchdog-lpc55")] {
+        /// The LPC55 windowed watchdog (WWDT), used without a window.
+        ///
+        /// SYSCON belongs to the syscon driver, which gives the WWDT its clock
+        /// at startup. We can't ask it to -- it runs at a lower priority than
+        /// we do -- so we wait for the clock to come on before starting the
+        /// watchdog.
+        mod hw {
+            use drv_lpc55_syscon_api::Peripheral;
+            use lpc55_pac as device;
+
+            /// The WWDT counts the 1 MHz FRO, undivided by `WDTCLKDIV`, after a
+            /// fixed divide-by-4.
+            const TICK_HZ: u64 = 250_000;
+
+            fn wwdt() -> &'static device::wwdt::RegisterBlock {
+                // Safety: the WWDT is mapped for us, and nobody else touches
+                // it.
+                unsafe { &*device::WWDT::ptr() }
+            }
+
+            pub fn start(timeout_ms: u32) -> bool {
+                // Safety: we only read SYSCON, which the syscon driver owns.
+                let syscon = unsafe { &*device::SYSCON::ptr() };
+                let clocks = syscon.ahbclkctrl0.read().bits();
+                if clocks & Peripheral::Wwdt.pmask() == 0 {
+                    return false;
+                }
+
+                // The counter is 24 bits, which lets us wait about 67 s.
+                let ticks = (u64::from(timeout_ms) * TICK_HZ / 1000)
+                    .clamp(0xFF, 0xFF_FFFF) as u32;
+
+                let wwdt = wwdt();
+                // Safety: the count is in range for the register, as clamped
+                // above.
+                wwdt.tc.write(|w| unsafe { w.bits(ticks) });
+                wwdt.mod_.write(|w| w.wden().set_bit().wdreset().set_bit());
+                // The watchdog starts counting at the first feed.
+                feed();
+                true
+            }
+
+            pub fn feed() {
+                let wwdt = wwdt();
+                // Safety: these are the two values that make up a feed.
+                wwdt.feed.write(|w| unsafe { w.bits(0xAA) });
+                wwdt.feed.write(|w| unsafe { w.bits(0x55) });
+            }
+        }
+    } else {
+        /// No hardware watchdog. The build script only allows this when we
+        /// have no watchdog config, so these are never called.
+        mod hw {
+            pub fn start(_timeout_ms: u32) -> bool {
+                true
+            }
+            pub fn feed() {}
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Client API for the log task, and the logging macros.
+//!
+//! The macros take a level and a format string, much like `format!`:
+//!
+//! ```ignore
+//! task_log_api::info!("link up on port {} at {} Mbps", port, speed);
+//! task_log_api::log!(Level::Debug, "state {:#x}", state);
+//! ```
+//!
+//! but don't do any formatting. The format string is interned into the task's
+//! ELF file at build time, and only the arguments are sent to the log task,
+//! so a message costs a few dozen bytes of stack and an IPC. Arguments can be
+//! integers, `bool`, `char`, or `&str`; see `log_format::LogArg`. Placeholders
+//! can be `{}`, `{:?}`, or one of the hex or binary forms (`{:x}`, `{:#x}`,
+//! `{:X}`, `{:#X}`, `{:b}`, `{:#b}`), and the build checks that each call
+//! site passes as many arguments as it has placeholders.
+//!
+//! Tasks using these macros need a `log` task slot, and must have a lower
+//! priority than the log task.
+//!
+//! That rules out the supervisor, which has the highest priority and must
+//! never send to another task, so it keeps using `userlib::sys_log!`.
+
+#![no_std]
+
+pub use log_format::{Encoder, Level, LogArg, ARGS_LEN};
+use userlib::*;
+
+task_slot!(LOG, log);
+
+/// Logs a message at the given `Level`.
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $fmt:literal $(, $arg:expr)* $(,)?) => {{
+        const ENTRY: &str = concat!(file!(), ":", line!(), "\0", $fmt, "\0");
+        const NARGS: usize = <[&str]>::len(&[$(stringify!($arg)),*]);
+        #[link_section = ".hubris_log"]
+        static INTERNED: [u8; ENTRY.len() + 1] =
+            $crate::intern(NARGS, ENTRY);
+
+        let mut buf = [0; $crate::ARGS_LEN];
+        #[allow(unused_mut)]
+        let mut e = $crate::Encoder::new(&mut buf);
+        $( $crate::LogArg::encode(&$arg, &mut e); )*
+        let len = e.finish();
+        $crate::send($level, &INTERNED, &buf[..len]);
+    }};
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($t:tt)*) => { $crate::log!($crate::Level::Error, $($t)*) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($t:tt)*) => { $crate::log!($crate::Level::Warn, $($t)*) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($t:tt)*) => { $crate::log!($crate::Level::Info, $($t)*) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($t:tt)*) => { $crate::log!($crate::Level::Debug, $($t)*) };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($t:tt)*) => { $crate::log!($crate::Level::Trace, $($t)*) };
+}
+
+/// Builds a call site's entry in the log section: the argument count
+/// followed by `entry`, which holds its location and format string.
+#[doc(hidden)]
+pub const fn intern<const N: usize>(nargs: usize, entry: &str) -> [u8; N] {
+    assert!(nargs <= u8::MAX as usize, "too many log arguments");
+    let entry = entry.as_bytes();
+    let mut out = [0; N];
+    out[0] = nargs as u8;
+    let mut i = 0;
+    while i < entry.len() {
+        out[i + 1] = entry[i];
+        i += 1;
+    }
+    out
+}
+
+#[doc(hidden)]
+pub fn send(level: Level, interned: &'static [u8], args: &[u8]) {
+    // The log section isn't loaded, and is placed at address zero, so the
+    // address of an entry is its offset in the section: the message id.
+    let id = interned.as_ptr() as u32;
+    Log::from(LOG.get_task_id()).log(level as u8, id, args);
+}
+
+include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
@@ -0,0 +1,88 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+fn main() -> Result<()> {
+    let cfg = build_util::task_maybe_config::<Config>()?.unwrap_or_default();
+
+    let task_ids = build_util::task_ids();
+    let allowed_callers =
+        task_ids.remap_allowed_caller_names_to_ids(&cfg.allowed_callers)?;
+
+    idol::server::build_restricted_server_support(
+        "../../idl/log.idol",
+        "server_stub.rs",
+        idol::server::ServerStyle::InOrder,
+        &allowed_callers,
+    )
+    .unwrap();
+
+    let out_dir = std::env::var("OUT_DIR")?;
+    let dest_path = std::path::Path::new(&out_dir).join("log_config.rs");
+    let mut out =
+        std::fs::File::create(&dest_path).context("creating log_config.rs")?;
+
+    // The record index has to stay consistent when the count wraps.
+    let capacity = cfg.capacity.unwrap_or(64);
+    if !capacity.is_power_of_two() {
+        bail!("log capacity must be a power of two, not {}", capacity);
+    }
+    writeln!(out, "pub(crate) const CAPACITY: usize = {};", capacity)?;
+    writeln!(
+        out,
+        "pub(crate) const DEFAULT_LEVEL: u8 = {};",
+        cfg.level.unwrap_or(Level::Info) as u8
+    )?;
+
+    let task = "hubris_num_tasks::Task";
+    writeln!(
+        out,
+        "pub(crate) const LEVELS: [(usize, u8); {}] = [",
+        cfg.levels.len()
+    )?;
+    for (name, level) in cfg.levels {
+        if task_ids.get(&name).is_none() {
+            bail!("levels: unknown task `{}`", name);
+        }
+        writeln!(out, "    ({}::{} as usize, {}),", task, name, level as u8)?;
+    }
+    writeln!(out, "];")?;
+
+    Ok(())
+}
+
+/// Log task-level configuration.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    /// Number of messages kept, which must be a power of two. Defaults to 64.
+    #[serde(default)]
+    capacity: Option<usize>,
+    /// Most verbose level logged for tasks not in `levels`. Defaults to
+    /// `info`.
+    #[serde(default)]
+    level: Option<Level>,
+    /// Map from task name to the most verbose level logged for it.
+    #[serde(default)]
+    levels: BTreeMap<String, Level>,
+    /// Map of operation names to tasks allowed to call them.
+    #[serde(default)]
+    allowed_callers: BTreeMap<String, Vec<String>>,
+}
+
+/// A level filter. These match `log_format::Level`, plus `Off`.
+#[derive(Deserialize, Copy, Clone)]
+#[serde(rename_all = "kebab-case")]
+enum Level {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
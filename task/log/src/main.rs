@@ -0,0 +1,155 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Log task.
+//!
+//! Other tasks send us messages with the macros in `task-log-api`, already
+//! encoded as described in `log-format`. We drop messages that are more
+//! verbose than their task's level, and stamp the rest with the time, task
+//! and level, and keep them in a circular buffer in RAM: the `HUBRIS_LOG`
+//! symbol. A dump of the buffer can be turned back into text with
+//! `cargo xtask log`.
+//!
+//! Levels start out as configured in `app.toml`, and can be changed at runtime
+//! with `set_level` (say, through Hiffy).
+
+#![no_std]
+#![no_main]
+
+use core::convert::Infallible;
+use hubris_num_tasks::NUM_TASKS;
+use idol_runtime::{ClientError, Leased, LenLimit, RequestError, R};
+use log_format::{Level, LogHeader, LogRecord, ARGS_LEN, LOG_MAGIC};
+use userlib::*;
+
+#[repr(C)]
+struct LogBuffer {
+    header: LogHeader,
+    records: [LogRecord; generated::CAPACITY],
+}
+
+const EMPTY_RECORD: LogRecord = LogRecord {
+    timestamp: 0,
+    id: 0,
+    task: 0,
+    level: 0,
+    len: 0,
+    args: [0; ARGS_LEN],
+};
+
+/// The log buffer itself. This is `no_mangle` so that tools can find it.
+#[no_mangle]
+static mut HUBRIS_LOG: LogBuffer = LogBuffer {
+    header: LogHeader {
+        magic: 0,
+        capacity: 0,
+        count: 0,
+        filtered: 0,
+    },
+    records: [EMPTY_RECORD; generated::CAPACITY],
+};
+
+#[export_name = "main"]
+fn main() -> ! {
+    // Safety: this is the only reference we ever make to the buffer, and we
+    // only make it once.
+    let buffer = unsafe { &mut *core::ptr::addr_of_mut!(HUBRIS_LOG) };
+    buffer.header = LogHeader {
+        magic: LOG_MAGIC,
+        capacity: generated::CAPACITY as u32,
+        count: 0,
+        filtered: 0,
+    };
+
+    let mut levels = [generated::DEFAULT_LEVEL; NUM_TASKS];
+    for (task, level) in generated::LEVELS {
+        levels[task] = level;
+    }
+
+    let mut server = ServerImpl { buffer, levels };
+    let mut buf = [0u8; idl::INCOMING_SIZE];
+
+    loop {
+        idol_runtime::dispatch(&mut buf, &mut server);
+    }
+}
+
+struct ServerImpl {
+    buffer: &'static mut LogBuffer,
+    /// Most verbose level logged for each task, or 0 to log nothing.
+    levels: [u8; NUM_TASKS],
+}
+
+impl idl::InOrderLogImpl for ServerImpl {
+    fn log(
+        &mut self,
+        msg: &RecvMessage,
+        level: u8,
+        id: u32,
+        args: LenLimit<Leased<R, [u8]>, ARGS_LEN>,
+    ) -> Result<(), RequestError<Infallible>> {
+        if Level::from_u8(level).is_none() {
+            return Err(RequestError::Fail(ClientError::BadMessageContents));
+        }
+        let task = msg.sender.index();
+        let header = &mut self.buffer.header;
+        if level > self.levels[task] {
+            header.filtered = header.filtered.wrapping_add(1);
+            return Ok(());
+        }
+
+        let mut record = LogRecord {
+            timestamp: sys_get_timer().now,
+            id,
+            task: task as u16,
+            level,
+            len: args.len() as u8,
+            ..EMPTY_RECORD
+        };
+        args.read_range(0..args.len(), &mut record.args[..args.len()])
+            .map_err(|_| RequestError::Fail(ClientError::WentAway))?;
+
+        let i = header.count as usize % generated::CAPACITY;
+        header.count = header.count.wrapping_add(1);
+        self.buffer.records[i] = record;
+        Ok(())
+    }
+
+    fn get_level(
+        &mut self,
+        _: &RecvMessage,
+        task: u32,
+    ) -> Result<u8, RequestError<Infallible>> {
+        self.levels
+            .get(task as usize)
+            .copied()
+            .ok_or(RequestError::Fail(ClientError::BadMessageContents))
+    }
+
+    fn set_level(
+        &mut self,
+        _: &RecvMessage,
+        task: u32,
+        level: u8,
+    ) -> Result<(), RequestError<Infallible>> {
+        if level != 0 && Level::from_u8(level).is_none() {
+            return Err(RequestError::Fail(ClientError::BadMessageContents));
+        }
+        let slot = self
+            .levels
+            .get_mut(task as usize)
+            .ok_or(RequestError::Fail(ClientError::BadMessageContents))?;
+        *slot = level;
+        Ok(())
+    }
+}
+
+// Place to namespace all the bits generated by our config processor.
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/log_config.rs"));
+}
+
+mod idl {
+    include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
+}
@@ -11,7 +11,7 @@ use task_jefe_api::Jefe;
 use task_net_api::{
     ManagementCounters, ManagementLinkStatus, MgmtError, PhyError,
 };
-use userlib::{sys_recv_closed, task_slot, FromPrimitive, TaskId};
+use userlib::{sys_recv_closed, task_slot, TaskId};
 use vsc7448_pac::types::PhyRegisterAddress;
 
 task_slot!(SPI, spi_driver);
@@ -55,7 +55,7 @@ pub fn preinit() {
     loop {
         // This laborious list is intended to ensure that new power states have
         // to be added explicitly here.
-        match PowerState::from_u32(jefe.get_state()) {
+        match jefe.get_state_as() {
             Some(PowerState::A2)
             | Some(PowerState::A2PlusMono)
             | Some(PowerState::A2PlusFans)
@@ -55,6 +55,8 @@ fn main() -> ! {
         }
 
         let op = (code / FAULT_EVERY) as usize % faultme.len();
+        #[cfg(feature = "log")]
+        task_log_api::warn!("faulting on purpose (op {}) at ping {}", op, code);
         faultme[op]();
         sys_panic(b"unexpected non-fault!");
     }
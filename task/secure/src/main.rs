@@ -15,6 +15,9 @@ use userlib::*;
 static TZ_TABLE: SecureTable = SecureTable {
     magic: TABLE_MAGIC,
     write_to_flash: Some(write_to_flash),
+    read_boot_state: Some(read_boot_state),
+    write_boot_state: Some(write_boot_state),
+    read_image_header: Some(read_image_header),
 };
 
 #[export_name = "main"]
@@ -50,3 +53,60 @@ pub unsafe extern "C" fn write_to_flash(
         options(noreturn)
     );
 }
+
+// These work just like `write_to_flash`, except that they call the `__ns_*`
+// versions, which check that the pointers we're given are to memory that the
+// non-secure caller could access itself.
+
+#[naked]
+#[no_mangle]
+#[link_section = ".nsc"]
+pub unsafe extern "C" fn read_boot_state(_state: *mut BootState) -> HypoStatus {
+    core::arch::asm!(
+        "
+        sg
+        push {{lr}}
+        bl __ns_read_boot_state
+        pop {{lr}}
+        bxns lr
+        ",
+        options(noreturn)
+    );
+}
+
+#[naked]
+#[no_mangle]
+#[link_section = ".nsc"]
+pub unsafe extern "C" fn write_boot_state(
+    _state: *const BootState,
+) -> HypoStatus {
+    core::arch::asm!(
+        "
+        sg
+        push {{lr}}
+        bl __ns_write_boot_state
+        pop {{lr}}
+        bxns lr
+        ",
+        options(noreturn)
+    );
+}
+
+#[naked]
+#[no_mangle]
+#[link_section = ".nsc"]
+pub unsafe extern "C" fn read_image_header(
+    _image_num: UpdateTarget,
+    _header: *mut ImageHeader,
+) -> HypoStatus {
+    core::arch::asm!(
+        "
+        sg
+        push {{lr}}
+        bl __ns_read_image_header
+        pop {{lr}}
+        bxns lr
+        ",
+        options(noreturn)
+    );
+}
@@ -8,6 +8,7 @@
 use drv_sp_ctrl_api::*;
 use ringbuf::*;
 use sha3::{Digest, Sha3_256};
+use task_attest_api::{Attest, AttestError};
 use userlib::*;
 
 const READ_SIZE: usize = 256;
@@ -15,6 +16,7 @@ const READ_SIZE: usize = 256;
 const TRANSACTION_SIZE: u32 = 1024;
 
 task_slot!(SP_CTRL, swd);
+task_slot!(ATTEST, attest);
 
 #[derive(Copy, Clone, PartialEq)]
 enum Trace {
@@ -22,6 +24,7 @@ enum Trace {
     End(u64),
     ShaGood,
     ShaBad,
+    RecordFailed(AttestError),
     None,
 }
 
@@ -68,6 +71,13 @@ fn main() -> ! {
             ringbuf_entry!(Trace::ShaBad);
         }
 
+        // Record what we found, good or bad, so that the RoT's quotes vouch
+        // for it.
+        let attest = Attest::from(ATTEST.get_task_id());
+        if let Err(e) = attest.record(sha_out.as_slice()) {
+            ringbuf_entry!(Trace::RecordFailed(e));
+        }
+
         // Wait for a notification that will never come, politer than
         // busy looping forever
         if sys_recv_closed(&mut [], 1, TaskId::KERNEL).is_err() {
@@ -51,6 +51,8 @@ task_config::task_config! {
 // Actual list of functions with their names.
 test_cases! {
     test_send,
+    test_send_async,
+    test_send_async_poll,
     test_recv_reply,
     test_recv_reply_fault,
     #[cfg(any(armv7m, armv8m))]
@@ -127,6 +129,67 @@ fn test_send() {
     assert_eq!(response, !0xDEADBEEF);
 }
 
+/// Tests that we can send a message to our assistant without blocking, and
+/// collect the reply once it arrives.
+fn test_send_async() {
+    const ARBITRARY_NOTIFICATION: u32 = 1 << 16;
+
+    let assist = assist_task_id();
+    let challenge = 0xDEADBEEF_u32;
+    let mut response = 0_u32;
+    let (rc, len) = hl::send_async(
+        assist,
+        AssistOp::JustReply as u16,
+        &challenge.to_le_bytes(),
+        response.as_bytes_mut(),
+        &[],
+        0,
+        ARBITRARY_NOTIFICATION,
+        |pending| pending.wait(),
+    )
+    .unwrap();
+    assert_eq!(rc, 0);
+    assert_eq!(len, 4);
+    assert_eq!(response, !0xDEADBEEF);
+}
+
+/// Tests that an asynchronous send posts its notification when the reply
+/// arrives, and that its result can be polled for.
+fn test_send_async_poll() {
+    const ARBITRARY_NOTIFICATION: u32 = 1 << 16;
+
+    let assist = assist_task_id();
+    let challenge = 0xDEADBEEF_u32;
+    let mut response = 0_u32;
+    let result = hl::send_async(
+        assist,
+        AssistOp::JustReply as u16,
+        &challenge.to_le_bytes(),
+        response.as_bytes_mut(),
+        &[],
+        0,
+        ARBITRARY_NOTIFICATION,
+        |pending| {
+            let rm = sys_recv_closed(
+                &mut [],
+                ARBITRARY_NOTIFICATION,
+                TaskId::KERNEL,
+            )
+            .unwrap();
+            assert_eq!(rm.sender, TaskId::KERNEL);
+            assert_eq!(rm.operation, ARBITRARY_NOTIFICATION);
+
+            // Once the reply has been collected, polling again gives the same
+            // result.
+            let result = pending.poll();
+            assert_eq!(pending.poll(), result);
+            result
+        },
+    );
+    assert_eq!(result, Ok(Some((0, 4))));
+    assert_eq!(response, !0xDEADBEEF);
+}
+
 /// Tests that we can receive a message from the assistant and reply.
 fn test_recv_reply() {
     let assist = assist_task_id();